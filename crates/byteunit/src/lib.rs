@@ -69,6 +69,8 @@ impl ByteUnit {
     /// # Example
     ///
     /// ```no_run
+    /// use byteunit::ByteUnit;
+    ///
     /// let byte_size = 5.2 as u64 * 1024 * 1024;
     /// let unit = ByteUnit::new(byte_size);
     /// assert_eq!(ByteUnit::MiB(5.0), unit);
@@ -88,6 +90,8 @@ impl ByteUnit {
     /// # Example
     ///
     /// ```no_run
+    /// use byteunit::ByteUnit;
+    ///
     /// let size = 4096;
     /// let unit = ByteUnit::new_kib(size);
     /// assert_eq!(ByteUnit::KiB(4.0), unit);
@@ -0,0 +1,239 @@
+//! Distro catalogs ([debootstrap::Cfg] & [disk::DiskV1]), loaded from ron or toml files at runtime.
+//!
+//! The search path is `--catalog` (files or dirs), then `$XDG_CONFIG_HOME/get-ctr/catalog/` (default: ~/.config/get-ctr/catalog/).
+//! A dir may contain `debian.{ron,toml}`, `ubuntu.{ron,toml}`, `disk.v1.{ron,toml}` & the derivatives, e.g., `devuan.toml`.
+//! A file passed to `--catalog` must be named the same way, e.g., `my-debian.toml` is rejected.
+//! If none of them exists, the embedded catalog is used.
+
+use crate::cfg::{debootstrap, disk};
+use anyhow::{bail, Context};
+use derive_more::Display;
+use serde::de::DeserializeOwned;
+use std::{
+    borrow::Cow,
+    env,
+    ffi::OsStr,
+    fs,
+    path::{self, Path, PathBuf},
+    sync::OnceLock,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+pub(crate) enum Kind {
+    /// debootstrap::Cfg
+    #[display("debian")]
+    Debian,
+
+    /// debootstrap::Cfg
+    #[display("ubuntu")]
+    Ubuntu,
+
+    /// disk::DiskV1
+    #[display("disk.v1")]
+    OldOldDebian,
+
+    /// debootstrap::Cfg
+    #[display("devuan")]
+    Devuan,
+
+    /// debootstrap::Cfg
+    #[display("kali")]
+    Kali,
+
+    /// debootstrap::Cfg
+    #[display("raspbian")]
+    Raspbian,
+
+    /// debootstrap::Cfg
+    #[display("trisquel")]
+    Trisquel,
+}
+
+impl Kind {
+    pub(crate) const ALL: [Self; 7] = [
+        Self::Debian,
+        Self::Ubuntu,
+        Self::OldOldDebian,
+        Self::Devuan,
+        Self::Kali,
+        Self::Raspbian,
+        Self::Trisquel,
+    ];
+
+    pub(crate) const fn embedded(self) -> &'static str {
+        match self {
+            Self::Debian => debootstrap::DEBIAN_RON,
+            Self::Ubuntu => debootstrap::UBUNTU_RON,
+            Self::OldOldDebian => disk::DISK_V1_RON,
+            Self::Devuan => debootstrap::DEVUAN_RON,
+            Self::Kali => debootstrap::KALI_RON,
+            Self::Raspbian => debootstrap::RASPBIAN_RON,
+            Self::Trisquel => debootstrap::TRISQUEL_RON,
+        }
+    }
+}
+
+static SEARCH_PATH: OnceLock<Vec<PathBuf>> = OnceLock::new();
+
+/// The relative paths are resolved against the current dir, so call it before `set_static_workdir()`.
+///
+/// Returns an error if a path does not exist, or a file is not named after a [Kind] (e.g., my-debian.toml).
+pub(crate) fn set_static_search_path(paths: &[PathBuf]) -> anyhow::Result<()> {
+    check_paths(paths)?;
+
+    let mut search_path = paths
+        .iter()
+        .map(|p| path::absolute(p).unwrap_or_else(|_| p.to_owned()))
+        .collect::<Vec<_>>();
+    search_path.extend(default_dir());
+
+    log::debug!("catalog search path: {search_path:?}");
+    if SEARCH_PATH
+        .set(search_path)
+        .is_err()
+    {
+        log::debug!("The catalog search path has already been set")
+    }
+    Ok(())
+}
+
+/// The explicitly passed paths (i.e., `--catalog`) must not be ignored silently.
+fn check_paths(paths: &[PathBuf]) -> anyhow::Result<()> {
+    for p in paths {
+        if !p.exists() {
+            bail!("The catalog does not exist: {p:?}")
+        }
+        if p.is_file()
+            && !Kind::ALL
+                .iter()
+                .any(|k| p.file_stem() == Some(OsStr::new(&k.to_string())))
+        {
+            let names = Kind::ALL.map(|k| k.to_string());
+            bail!(
+                "Unknown catalog file: {p:?}, the file name must be one of {names:?} (.ron or .toml)"
+            )
+        }
+    }
+    Ok(())
+}
+
+fn static_search_path() -> &'static [PathBuf] {
+    SEARCH_PATH.get_or_init(|| {
+        default_dir()
+            .into_iter()
+            .collect()
+    })
+}
+
+/// $XDG_CONFIG_HOME/get-ctr/catalog
+fn default_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| Path::new(&h).join(".config")))
+        .map(|d| {
+            d.join("get-ctr")
+                .join("catalog")
+        })
+}
+
+/// Returns the first catalog file of `kind` in `search_path`.
+fn find_in(search_path: &[PathBuf], kind: Kind) -> Option<PathBuf> {
+    let name = kind.to_string();
+
+    search_path
+        .iter()
+        .find_map(|p| {
+            if p.is_file() {
+                return (p.file_stem() == Some(OsStr::new(&name)))
+                    .then(|| p.to_owned());
+            }
+            ["ron", "toml"]
+                .into_iter()
+                .map(|ext| p.join(format!("{name}.{ext}")))
+                .find(|f| f.is_file())
+        })
+}
+
+fn parse<T: DeserializeOwned>(
+    content: &str,
+    ext: Option<&OsStr>,
+) -> anyhow::Result<T> {
+    let cfg = match ext.and_then(OsStr::to_str) {
+        Some("toml") => toml::from_str(content)?,
+        _ => ron::from_str(content)?,
+    };
+    Ok(cfg)
+}
+
+/// Returns the catalog file of `kind` (None: embedded) & its content.
+pub(crate) fn read(
+    kind: Kind,
+) -> anyhow::Result<(Option<PathBuf>, Cow<'static, str>)> {
+    match find_in(static_search_path(), kind) {
+        Some(file) => {
+            let content = fs::read_to_string(&file)
+                .with_context(|| format!("Failed to read the catalog: {file:?}"))?;
+            Ok((Some(file), content.into()))
+        }
+        _ => Ok((None, kind.embedded().into())),
+    }
+}
+
+/// Loads the catalog file of `kind` from the search path, falls back to the embedded one.
+pub(crate) fn load<T: DeserializeOwned>(kind: Kind) -> anyhow::Result<T> {
+    match read(kind)? {
+        (Some(file), content) => {
+            log::info!("{kind} catalog: {file:?}");
+            parse(&content, file.extension())
+                .with_context(|| format!("Failed to parse the catalog: {file:?}"))
+        }
+        (_, content) => {
+            log::debug!("{kind} catalog: embedded");
+            parse(&content, None).with_context(|| {
+                format!("Failed to parse the embedded {kind} catalog")
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_catalog_files() -> anyhow::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path().to_owned();
+        let ubuntu = dir.join("ubuntu.toml");
+        fs::write(&ubuntu, "")?;
+        let disk = dir.join("my-disk.v1.ron");
+        fs::write(&disk, "")?;
+
+        // named after no kind, or missing
+        assert!(check_paths(&[disk]).is_err());
+        assert!(check_paths(&[dir.join("debian.ron")]).is_err());
+
+        let search_path = [ubuntu.clone(), dir.clone()];
+        check_paths(&search_path)?;
+        assert_eq!(find_in(&search_path, Kind::Ubuntu), Some(ubuntu));
+        assert_eq!(find_in(&search_path, Kind::Debian), None);
+        assert_eq!(find_in(&search_path, Kind::OldOldDebian), None);
+
+        let disk_v1 = dir.join("disk.v1.ron");
+        fs::write(&disk_v1, "")?;
+        assert_eq!(find_in(&[dir], Kind::OldOldDebian), Some(disk_v1));
+
+        // embedded
+        for kind in Kind::ALL {
+            match kind {
+                Kind::OldOldDebian => {
+                    parse::<disk::DiskV1>(kind.embedded(), None)?;
+                }
+                _ => {
+                    parse::<debootstrap::Cfg>(kind.embedded(), None)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -9,7 +9,11 @@ use url::Url;
 pub(crate) const SCRIPT_DIR: &str = "/usr/share/debootstrap/scripts/";
 
 use crate::{
-    cfg::{components, mirror},
+    cfg::{
+        components,
+        mirror::{self, MirrorVariant},
+        project::Project,
+    },
     url::find_mirror_url,
 };
 
@@ -23,6 +27,26 @@ pub(crate) const UBUNTU_RON: &str = include_str!(concat!(
     "/assets/debootstrap/ubuntu.ron"
 ));
 
+pub(crate) const DEVUAN_RON: &str = include_str!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/assets/debootstrap/devuan.ron"
+));
+
+pub(crate) const KALI_RON: &str = include_str!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/assets/debootstrap/kali.ron"
+));
+
+pub(crate) const RASPBIAN_RON: &str = include_str!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/assets/debootstrap/raspbian.ron"
+));
+
+pub(crate) const TRISQUEL_RON: &str = include_str!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/assets/debootstrap/trisquel.ron"
+));
+
 #[derive(Getters, Serialize, Deserialize, Debug, Default, TypedBuilder, Clone)]
 #[getset(get = "pub(crate) with_prefix")]
 #[serde(default)]
@@ -85,15 +109,15 @@ impl Source {
             (Some(src), _) => {
                 let (uuu_mirrors, include_pkgs) = match src.as_str() {
                     "ubuntu" => (
-                        mirror::ubuntu::mirrors(),
+                        MirrorVariant::Ubuntu.mirrors(),
                         Some(mirror::ubuntu::include_pkgs()),
                     ),
                     "ubuntu-ports" => (
-                        mirror::ubuntu_ports::mirrors(),
+                        MirrorVariant::UbuntuPorts.mirrors(),
                         Some(mirror::ubuntu_ports::include_pkgs()),
                     ),
                     // ubuntu-old-releases
-                    _ => (mirror::ubuntu_old::mirrors(), None),
+                    _ => (MirrorVariant::UbuntuOld.mirrors(), None),
                 };
                 find_mirror_url(&uuu_mirrors)
                     .ok()
@@ -111,16 +135,21 @@ impl Source {
                 log::debug!("site_left: {site_left}, suite: {suite}");
                 let mirror_name = site_left.split('/').next()?;
 
+                let debian = components::DEBIAN_BOOTSTRAP;
                 match mirror_name {
                     "debian-archive" => {
-                        find_mirror_url(&mirror::debian_archive::deb_mirrors())
-                            .map(|url| (url, None))
+                        find_mirror_url(&MirrorVariant::DebianArchive.mirrors())
+                            .map(|url| (url, None, debian))
                             .ok()
                     }
                     "debian-elts" => {
-                        find_mirror_url(&mirror::debian_elts::mirrors())
+                        find_mirror_url(&MirrorVariant::DebianELTS.mirrors())
                             .map(|url| {
-                                (url, Some(mirror::debian_elts::include_pkgs()))
+                                (
+                                    url,
+                                    Some(mirror::debian_elts::include_pkgs()),
+                                    debian,
+                                )
                             })
                             .ok()
                     }
@@ -132,24 +161,40 @@ impl Source {
                                 Url::parse(mirror.get_url())
                                     .expect("Invalid ELTS URL"),
                                 Some(mirror::debian_elts::include_pkgs()),
+                                debian,
                             )
                         }),
                     "debian-ports" => {
-                        find_mirror_url(&mirror::debian_ports::mirrors())
+                        find_mirror_url(&MirrorVariant::DebianPorts.mirrors())
+                            .map(|url| {
+                                (
+                                    url,
+                                    Some(mirror::debian_ports::include_pkgs()),
+                                    debian,
+                                )
+                            })
+                            .ok()
+                    }
+                    // debian, devuan, kali, etc.
+                    site => {
+                        let project = Project::from_site(site)
+                            .or_else(|| Project::find("debian"))?;
+                        find_mirror_url(&project.get_variant().mirrors())
                             .map(|url| {
-                                (url, Some(mirror::debian_ports::include_pkgs()))
+                                (
+                                    url,
+                                    *project.get_include_pkgs(),
+                                    *project.get_bootstrap_components(),
+                                )
                             })
                             .ok()
                     }
-                    _ => find_mirror_url(&mirror::debian::mirrors())
-                        .map(|url| (url, Some(mirror::debian::include_pkgs())))
-                        .ok(),
                 }
                 // .ok()
-                .map(|(url, pkgs)| {
+                .map(|(url, pkgs, components)| {
                     DebootstrapSrc::builder()
                         .url(url)
-                        .components(components::DEBIAN_BOOTSTRAP)
+                        .components(components)
                         .suite(suite)
                         .include_pkgs(pkgs)
                         .build()
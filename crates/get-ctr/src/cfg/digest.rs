@@ -1,4 +1,5 @@
 use getset::Getters;
+use repack::compression::ZstdParams;
 use serde::{Deserialize, Serialize};
 use serde_with::skip_serializing_none;
 use std::path::PathBuf;
@@ -28,6 +29,28 @@ pub(crate) struct DockerMirror {
     token: Option<String>,
 }
 
+impl DockerMirror {
+    /// Reads the user & token of the mirror from the env, e.g., GET_CTR_GHCR_USER & GET_CTR_GHCR_TOKEN, my-harbor => GET_CTR_MY_HARBOR_TOKEN
+    pub(crate) fn with_env_credentials(mut self) -> Self {
+        let name = self
+            .name
+            .to_ascii_uppercase()
+            .replace('-', "_");
+        let var = |k: &str| {
+            std::env::var(format!("GET_CTR_{name}_{k}"))
+                .ok()
+                .filter(|v| !v.is_empty())
+        };
+        self.user = self
+            .user
+            .or_else(|| var("USER"));
+        self.token = self
+            .token
+            .or_else(|| var("TOKEN"));
+        self
+    }
+}
+
 #[skip_serializing_none]
 #[derive(Getters, Serialize, Deserialize, Debug, Default, TypedBuilder)]
 #[getset(get = "pub(crate) with_prefix")]
@@ -104,6 +127,104 @@ pub(crate) struct FileSize {
     cmt: Option<String>,
 }
 
+/// The compression codec of the rootfs tarball.
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    clap::ValueEnum,
+    derive_more::Display,
+)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Codec {
+    #[default]
+    #[value(alias = "zst")]
+    #[display("zstd")]
+    Zstd,
+
+    #[display("xz")]
+    Xz,
+
+    /// Note: docker cannot import tar+lzip
+    #[value(alias = "lz")]
+    #[display("lzip")]
+    Lzip,
+}
+
+impl Codec {
+    /// e.g., Zstd => "tar.zst"
+    pub(crate) const fn tar_extension(&self) -> &'static str {
+        match self {
+            Self::Zstd => "tar.zst",
+            Self::Xz => "tar.xz",
+            Self::Lzip => "tar.lz",
+        }
+    }
+
+    pub(crate) const fn default_level(&self) -> u8 {
+        match self {
+            Self::Zstd => 19,
+            _ => 9,
+        }
+    }
+
+    pub(crate) const fn max_level(&self) -> u8 {
+        match self {
+            Self::Zstd => 22,
+            _ => 9,
+        }
+    }
+
+    /// Creates the digest entry of the codec.
+    ///
+    /// `zstd` & `dict` are only used by [Codec::Zstd].
+    pub(crate) fn compression(
+        &self,
+        level: u8,
+        zstd: &ZstdParams,
+        dict: bool,
+    ) -> Compression {
+        match self {
+            Self::Zstd => Compression::Zstd(
+                Zstd::builder()
+                    .level(level)
+                    .long(zstd.window_log)
+                    .long_distance(zstd.long_distance)
+                    .dict(dict)
+                    .build(),
+            ),
+            Self::Xz => Compression::Xz(Lzma { level }),
+            Self::Lzip => Compression::Lzip(Lzma { level }),
+        }
+    }
+}
+
+/// e.g., `{codec: zstd, level: 19, long: 27, long-distance: true}`
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "codec", rename_all = "lowercase")]
+pub(crate) enum Compression {
+    Zstd(Zstd),
+    Xz(Lzma),
+    Lzip(Lzma),
+}
+
+impl Compression {
+    pub(crate) const fn codec(&self) -> Codec {
+        match self {
+            Self::Zstd(_) => Codec::Zstd,
+            Self::Xz(_) => Codec::Xz,
+            Self::Lzip(_) => Codec::Lzip,
+        }
+    }
+}
+
+/// If `long` > 27, decompression requires `zstd -d --long=N`.
+#[skip_serializing_none]
 #[derive(Getters, Serialize, Deserialize, Debug, TypedBuilder)]
 #[getset(get = "pub(crate) with_prefix")]
 #[serde(default)]
@@ -111,12 +232,15 @@ pub(crate) struct FileSize {
 pub(crate) struct Zstd {
     #[builder(default = 18)]
     level: u8,
+    /// window log. None: determined by the level
     #[builder(default = Some(27))]
     long: Option<u32>,
-    // #[serde(rename = "long-distance")]
-    // long_distance: bool,
-    // #[builder(default = false)]
-    // dict: bool,
+    #[serde(rename = "long-distance")]
+    #[builder(default = true)]
+    long_distance: bool,
+    /// Compressed with the trained dictionary (see [ArchiveFile::dict])
+    #[builder(default = false)]
+    dict: bool,
 }
 
 impl Default for Zstd {
@@ -125,6 +249,14 @@ impl Default for Zstd {
     }
 }
 
+/// xz & lzip
+#[derive(Getters, Serialize, Deserialize, Debug, Default)]
+#[getset(get = "pub(crate) with_prefix")]
+#[serde(default)]
+pub(crate) struct Lzma {
+    level: u8,
+}
+
 #[derive(Getters, Serialize, Deserialize, Debug, Default, TypedBuilder)]
 #[getset(get = "pub(crate) with_prefix")]
 #[serde(default)]
@@ -137,8 +269,73 @@ pub(crate) struct ArchiveFile {
     #[builder(default, setter(strip_option))]
     modified_time: Option<time::OffsetDateTime>,
 
+    /// Zstd is written as `zstd: {level, long, long-distance}` (readable by older versions), xz & lzip as `compression: {codec, level}`.
+    #[serde(flatten, with = "compression_field")]
     #[builder(setter(strip_option))]
-    zstd: Option<Zstd>,
+    compression: Option<Compression>,
+
+    digest: Vec<HashDigest>,
+    mirror: Vec<FileMirror>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default, setter(strip_option))]
+    dict: Option<DictFile>,
+}
+
+mod compression_field {
+    use super::{Compression, Zstd};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    struct Fields<Z, C> {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        zstd: Option<Z>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        compression: Option<C>,
+    }
+
+    pub(super) fn serialize<S: Serializer>(
+        compression: &Option<Compression>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let fields = match compression {
+            Some(Compression::Zstd(z)) => Fields {
+                zstd: Some(z),
+                compression: None,
+            },
+            c => Fields {
+                zstd: None,
+                compression: c.as_ref(),
+            },
+        };
+        fields.serialize(serializer)
+    }
+
+    /// Also accepts `compression: {codec: zstd, ...}`.
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Compression>, D::Error> {
+        let fields = Fields::<Zstd, Compression>::deserialize(deserializer)?;
+        Ok(fields.compression.or(fields
+            .zstd
+            .map(Compression::Zstd)))
+    }
+}
+
+/// The zstd dictionary (sidecar file), shared by all architectures of a release.
+#[skip_serializing_none]
+#[derive(Getters, Serialize, Deserialize, Debug, Default, TypedBuilder)]
+#[getset(get = "pub(crate) with_prefix")]
+#[serde(default)]
+#[builder(field_defaults(setter(into)))]
+pub(crate) struct DictFile {
+    name: PathBuf,
+    bytes: u64,
+    #[builder(default)]
+    id: Option<u32>,
+
+    #[builder(default, setter(strip_option))]
+    cmt: Option<String>,
 
     digest: Vec<HashDigest>,
     mirror: Vec<FileMirror>,
@@ -197,3 +394,44 @@ pub(crate) struct OS {
 pub(crate) struct Digests {
     os: Vec<OS>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zstd_field_stays_readable() -> anyhow::Result<()> {
+        let zstd = ArchiveFile::builder()
+            .name("a.tar.zst")
+            .size(FileSize::default())
+            .compression(Codec::Zstd.compression(19, &ZstdParams::default(), false))
+            .digest(vec![])
+            .mirror(vec![])
+            .build();
+        let yaml = serde_yaml::to_string(&zstd)?;
+        assert!(yaml.contains("\nzstd:\n  level: 19\n"), "{yaml}");
+        assert!(!yaml.contains("compression"), "{yaml}");
+
+        // the legacy entry (without `long-distance` & `dict`)
+        let file: ArchiveFile =
+            serde_yaml::from_str("name: a.tar.zst\nzstd:\n  level: 19\n  long: 27")?;
+        let Some(Compression::Zstd(z)) = file.get_compression() else {
+            panic!("{file:?}")
+        };
+        assert_eq!((*z.get_level(), *z.get_long()), (19, Some(27)));
+
+        let file: ArchiveFile = serde_yaml::from_str(
+            "name: a.tar.xz\ncompression:\n  codec: xz\n  level: 9",
+        )?;
+        assert_eq!(
+            file.get_compression()
+                .as_ref()
+                .map(Compression::codec),
+            Some(Codec::Xz)
+        );
+        assert!(
+            serde_yaml::to_string(&file)?.contains("compression:\n  codec: xz\n")
+        );
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
 // use crate::cfg::mirror::Mirror;
+use crate::cfg::catalog;
 use getset::Getters;
 use log::{debug, trace};
 use serde::{Deserialize, Serialize};
@@ -26,6 +27,9 @@ pub(crate) struct OS {
     date: String,
     path: String,
     patch: Option<OsPatch>,
+    /// Same as `zstd --long=N` (0: disabled). The `--zstd-long` option takes precedence.
+    #[serde(rename = "zstd-long", skip_serializing_if = "Option::is_none")]
+    zstd_long: Option<u32>,
     disk: Vec<Disk>,
 }
 
@@ -45,17 +49,18 @@ pub(crate) struct DiskV1 {
     os: Vec<OS>,
 }
 
+pub(crate) const DISK_V1_RON: &str = include_str!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/assets/old_old_debian/disk.v1.ron"
+));
+
 impl DiskV1 {
+    /// Loads the disk cfg from the catalog search path, or the embedded one.
     pub(crate) fn deser() -> anyhow::Result<Self> {
         crate::dir::set_static_workdir();
 
-        let content = include_str!(concat!(
-            env!("CARGO_MANIFEST_DIR"),
-            "/assets/old_old_debian/disk.v1.ron"
-        ));
-
         debug!("deserializing the disk cfg");
-        let cfg = ron::from_str(content)?;
+        let cfg = catalog::load(catalog::Kind::OldOldDebian)?;
         debug!("complete");
 
         trace!("cfg: {:?}", cfg);
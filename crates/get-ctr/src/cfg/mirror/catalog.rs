@@ -0,0 +1,176 @@
+//! A user-supplied mirror catalog (ron or toml), e.g., an internal apt-cacher or a local debmirror.
+//!
+//! ```ron
+//! (
+//!     // true: replaces the built-in mirrors of the same variant
+//!     replace: false,
+//!     mirror: [
+//!         (name: "Cacher", url: "http://10.0.0.2:3142/debian/", variant: Debian),
+//!         (name: "Local", region: Some("LAN"), url: "http://debmirror.lan/ubuntu/", variant: Ubuntu),
+//!     ],
+//! )
+//! ```
+//!
+//! ```toml
+//! replace = false
+//!
+//! [[mirror]]
+//! name = "Cacher"
+//! url = "http://10.0.0.2:3142/debian/"
+//! variant = "Debian"
+//! ```
+
+use crate::cfg::mirror::{Mirror, MirrorVariant};
+use anyhow::Context;
+use serde::Deserialize;
+use std::{ffi::OsStr, fs, path::Path, sync::OnceLock};
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct CatalogFile {
+    replace: bool,
+    mirror: Vec<MirrorEntry>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct MirrorEntry {
+    name: String,
+    region: Option<String>,
+    url: String,
+    variant: MirrorVariant,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Catalog {
+    replace: bool,
+    mirrors: Vec<Mirror<'static>>,
+}
+
+static CATALOG: OnceLock<Catalog> = OnceLock::new();
+
+/// Loads the catalog once per run. The mirrors live until the process exits.
+pub(crate) fn set_static_catalog(path: &Path) -> anyhow::Result<()> {
+    let catalog = Catalog::load(path)
+        .with_context(|| format!("Failed to load the mirror catalog: {path:?}"))?;
+    log::info!(
+        "mirror catalog: {path:?}, mirrors: {}",
+        catalog.mirrors.len()
+    );
+
+    if CATALOG.set(catalog).is_err() {
+        log::warn!("The mirror catalog has already been set")
+    }
+    Ok(())
+}
+
+fn static_catalog() -> &'static Catalog {
+    CATALOG.get_or_init(Catalog::default)
+}
+
+impl Catalog {
+    fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = fs::read_to_string(path)?;
+        Self::parse(&content, path.extension())
+    }
+
+    fn parse(content: &str, ext: Option<&OsStr>) -> anyhow::Result<Self> {
+        let file = match ext.and_then(OsStr::to_str) {
+            Some("toml") => toml::from_str::<CatalogFile>(content)?,
+            _ => ron::from_str::<CatalogFile>(content)?,
+        };
+
+        let mut mirrors = Vec::with_capacity(file.mirror.len());
+        for m in file.mirror {
+            url::Url::parse(&m.url)
+                .with_context(|| format!("Invalid URL of the mirror {}", m.name))?;
+
+            let leak = |s: String| -> &'static str { s.leak() };
+            mirrors.push(Mirror {
+                name: leak(m.name),
+                region: m.region.map(leak),
+                url: leak(m.url),
+                variant: m.variant,
+            })
+        }
+
+        Ok(Self {
+            replace: file.replace,
+            mirrors,
+        })
+    }
+
+    /// The user mirrors come first, so that they take precedence in [crate::url::find_mirror_url].
+    ///
+    /// If `replace` is true & the catalog contains the `variant`, the `builtin` mirrors are dropped.
+    fn merge(
+        &self,
+        variant: MirrorVariant,
+        builtin: &[Mirror<'static>],
+    ) -> Vec<Mirror<'static>> {
+        let mut mirrors = self
+            .mirrors
+            .iter()
+            .filter(|m| m.variant == variant)
+            .copied()
+            .collect::<Vec<_>>();
+
+        if !self.replace || mirrors.is_empty() {
+            mirrors.extend(builtin.iter().filter(|b| {
+                !self
+                    .mirrors
+                    .iter()
+                    .any(|m| m.url == b.url)
+            }))
+        }
+        mirrors
+    }
+}
+
+/// Merges `builtin` with the mirrors of the static catalog.
+pub(crate) fn merge(
+    variant: MirrorVariant,
+    builtin: &[Mirror<'static>],
+) -> Vec<Mirror<'static>> {
+    static_catalog().merge(variant, builtin)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOML: &str = r#"
+replace = true
+
+[[mirror]]
+name = "Cacher"
+url = "http://10.0.0.2:3142/debian/"
+variant = "Debian"
+"#;
+
+    #[test]
+    fn merge_or_replace_builtin_mirrors() -> anyhow::Result<()> {
+        let builtin = crate::cfg::mirror::debian::mirrors();
+
+        let catalog = Catalog::parse(TOML, Some(OsStr::new("toml")))?;
+        let debian = catalog.merge(MirrorVariant::Debian, &builtin);
+        assert_eq!(debian.len(), 1);
+        assert_eq!(debian[0].get_name(), &"Cacher");
+
+        // No ubuntu mirrors in the catalog => built-ins
+        let ubuntu = crate::cfg::mirror::ubuntu::mirrors();
+        assert_eq!(
+            catalog
+                .merge(MirrorVariant::Ubuntu, &ubuntu)
+                .len(),
+            2
+        );
+
+        let ron_str = r#"(mirror: [(name: "Local", region: Some("LAN"), url: "http://debmirror.lan/debian/", variant: Debian)])"#;
+        let catalog = Catalog::parse(ron_str, Some(OsStr::new("ron")))?;
+        let debian = catalog.merge(MirrorVariant::Debian, &builtin);
+        assert_eq!(debian.len(), 3);
+        assert_eq!(debian[0].get_region(), &Some("LAN"));
+        Ok(())
+    }
+}
@@ -1,7 +1,7 @@
-//! const_deb_mirrors:
+//! deb_mirrors:
 //!     - archive.debian.org/debian/
 //!
-//! const_mirrors:
+//! root mirrors (see [super::archive_root_mirrors]):
 //!     - archive.debian.org/
 use crate::cfg::mirror::{Mirror, MirrorVariant};
 
@@ -30,21 +30,7 @@ const fn nju_deb<'m>() -> Mirror<'m> {
 const fn official_deb<'m>() -> Mirror<'m> {
     new_mirror("Official", "https://archive.debian.org/debian/", None)
 }
-const fn official<'m>() -> Mirror<'m> {
-    new_mirror("Official", "https://archive.debian.org/", None)
-}
-const fn nju<'m>() -> Mirror<'m> {
-    new_mirror(
-        "NJU",
-        "https://mirrors.nju.edu.cn/debian-archive/",
-        Some("CN"),
-    )
-}
 
 pub(crate) const fn deb_mirrors() -> [Mirror<'static>; 2] {
     [official_deb(), nju_deb()]
 }
-
-pub(crate) const fn root_mirrors() -> [Mirror<'static>; 2] {
-    [official(), nju()]
-}
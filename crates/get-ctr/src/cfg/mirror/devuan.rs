@@ -0,0 +1,28 @@
+use crate::cfg::mirror::{Mirror, MirrorVariant};
+
+/// Creates a new instance of Mirror (Devuan).
+const fn new_mirror<'m>(
+    name: &'m str,
+    url: &'m str,
+    region: Option<&'m str>,
+) -> Mirror<'m> {
+    Mirror {
+        name,
+        region,
+        url,
+        variant: MirrorVariant::Devuan,
+    }
+}
+
+/// deb.devuan.org is a round-robin DNS of the community mirrors (some are http only).
+const fn official<'m>() -> Mirror<'m> {
+    new_mirror("Official", "https://pkgmaster.devuan.org/merged/", None)
+}
+
+pub(crate) const fn mirrors() -> [Mirror<'static>; 1] {
+    [official()]
+}
+
+pub(crate) const fn include_pkgs() -> &'static str {
+    "devuan-keyring,ca-certificates"
+}
@@ -1,3 +1,4 @@
+pub(crate) mod catalog;
 pub(crate) mod debian;
 pub(crate) mod debian_archive;
 pub(crate) mod debian_debug;
@@ -5,6 +6,11 @@ pub(crate) mod debian_elts;
 pub(crate) mod debian_ports;
 pub(crate) mod debian_security;
 
+pub(crate) mod devuan;
+pub(crate) mod kali;
+pub(crate) mod raspbian;
+pub(crate) mod trisquel;
+
 pub(crate) mod ubuntu;
 pub(crate) mod ubuntu_old;
 pub(crate) mod ubuntu_ports;
@@ -53,14 +59,87 @@ pub(crate) enum MirrorVariant {
 
     #[display("ubuntu-old")]
     UbuntuOld,
+
+    #[display("devuan")]
+    Devuan,
+
+    #[display("kali")]
+    Kali,
+
+    #[display("raspbian")]
+    Raspbian,
+
+    #[display("trisquel")]
+    Trisquel,
 }
 
+#[allow(clippy::derivable_impls)]
 impl Default for MirrorVariant {
     fn default() -> Self {
         Self::DebianArchive
     }
 }
 
+impl MirrorVariant {
+    pub(crate) const ALL: [Self; 13] = [
+        Self::DebianArchive,
+        Self::Debian,
+        Self::DebianPorts,
+        Self::DebianELTS,
+        Self::DebianDebug,
+        Self::DebianSecurity,
+        Self::UbuntuPorts,
+        Self::Ubuntu,
+        Self::UbuntuOld,
+        Self::Devuan,
+        Self::Kali,
+        Self::Raspbian,
+        Self::Trisquel,
+    ];
+
+    /// The built-in mirrors, merged with (or replaced by) the user catalog.
+    pub(crate) fn mirrors(self) -> Vec<Mirror<'static>> {
+        catalog::merge(self, &self.builtin())
+    }
+
+    /// The built-in "Official" mirror, which the user catalog cannot replace.
+    pub(crate) fn official(self) -> Option<Mirror<'static>> {
+        self.builtin()
+            .into_iter()
+            .find(|x| x.get_name() == &"Official")
+    }
+
+    fn builtin(self) -> Vec<Mirror<'static>> {
+        let builtin: &[Mirror<'static>] = match self {
+            Self::DebianArchive => &debian_archive::deb_mirrors(),
+            Self::Debian => &debian::mirrors(),
+            Self::DebianPorts => &debian_ports::mirrors(),
+            Self::DebianELTS => &debian_elts::mirrors(),
+            Self::DebianDebug => &debian_debug::mirrors(),
+            Self::DebianSecurity => &debian_security::mirrors(),
+            Self::UbuntuPorts => &ubuntu_ports::mirrors(),
+            Self::Ubuntu => &ubuntu::mirrors(),
+            Self::UbuntuOld => &ubuntu_old::mirrors(),
+            Self::Devuan => &devuan::mirrors(),
+            Self::Kali => &kali::mirrors(),
+            Self::Raspbian => &raspbian::mirrors(),
+            Self::Trisquel => &trisquel::mirrors(),
+        };
+        builtin.to_vec()
+    }
+}
+
+/// debian-archive mirrors without the "debian/" suffix, e.g., https://archive.debian.org/
+pub(crate) fn archive_root_mirrors() -> Vec<Mirror<'static>> {
+    let mut mirrors = MirrorVariant::DebianArchive.mirrors();
+    for m in &mut mirrors {
+        if let Some(root) = m.url.strip_suffix("debian/") {
+            m.url = root
+        }
+    }
+    mirrors
+}
+
 #[derive(Getters, Serialize, Deserialize, Debug, Default, Clone, Copy)]
 #[getset(get = "pub(crate) with_prefix")]
 #[serde(default)]
@@ -0,0 +1,27 @@
+use crate::cfg::mirror::{Mirror, MirrorVariant};
+
+/// Creates a new instance of Mirror (Raspbian).
+const fn new_mirror<'m>(
+    name: &'m str,
+    url: &'m str,
+    region: Option<&'m str>,
+) -> Mirror<'m> {
+    Mirror {
+        name,
+        region,
+        url,
+        variant: MirrorVariant::Raspbian,
+    }
+}
+
+const fn official<'m>() -> Mirror<'m> {
+    new_mirror("Official", "https://archive.raspbian.org/raspbian/", None)
+}
+
+pub(crate) const fn mirrors() -> [Mirror<'static>; 1] {
+    [official()]
+}
+
+pub(crate) const fn include_pkgs() -> &'static str {
+    "raspbian-archive-keyring,ca-certificates"
+}
@@ -1,7 +1,10 @@
+pub(crate) mod catalog;
 pub(crate) mod debootstrap;
 pub(crate) mod digest;
 pub(crate) mod disk;
 pub(crate) mod mirror;
+pub(crate) mod project;
+pub(crate) mod registry;
 
 pub(crate) mod components {
     /// debian 2.1 ~ debian 11
@@ -12,6 +15,11 @@ pub(crate) mod components {
     pub(crate) const UBUNTU: &str = "main restricted universe multiverse";
     pub(crate) const UBUNTU_BOOTSTRAP: &str = "main,restricted,universe,multiverse";
 
+    pub(crate) const RASPBIAN_BOOTSTRAP: &str = "main,contrib,non-free,rpi";
+
+    /// trisquel only has the free "main" component
+    pub(crate) const TRISQUEL_BOOTSTRAP: &str = "main";
+
     // /// debian 12 +
     // pub(crate) const DEBIAN: &str = "main contrib non-free non-free-firmware";
 }
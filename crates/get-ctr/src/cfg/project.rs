@@ -0,0 +1,188 @@
+//! Debian & its derivatives (e.g., Devuan, Kali) share the same pipeline, each project only differs in the data below.
+
+use crate::cfg::{
+    catalog::Kind,
+    components,
+    mirror::{self, MirrorVariant},
+};
+use getset::Getters;
+
+#[derive(Getters, Debug)]
+#[getset(get = "pub(crate) with_prefix")]
+pub(crate) struct Project {
+    /// --os, e.g., devuan
+    os: &'static str,
+
+    /// GHCR & REG project, e.g., ghcr.io/2cd/devuan
+    name: &'static str,
+
+    /// The date-tagged project of the rolling suite, e.g., devuan-ceres
+    unstable_name: &'static str,
+
+    /// --ver of the rolling suite, e.g., ceres
+    unstable_versions: &'static [&'static str],
+
+    catalog: Kind,
+
+    /// The first segment of the enabled sources, e.g., "devuan/ daedalus" => devuan
+    site: &'static str,
+
+    variant: MirrorVariant,
+
+    /// Signed-By
+    keyring: &'static str,
+
+    /// github.com/2cd/{gh_repo}/releases
+    gh_repo: &'static str,
+
+    /// If debootstrap does not have the script of a suite, it will be linked to this one.
+    script: &'static str,
+
+    /// debootstrap --components
+    bootstrap_components: &'static str,
+
+    /// debootstrap --include
+    include_pkgs: Option<&'static str>,
+}
+
+pub(crate) static PROJECTS: [Project; 6] = [
+    Project {
+        os: "debian",
+        name: "debian",
+        unstable_name: "debian-sid",
+        unstable_versions: &["sid", "unstable"],
+        catalog: Kind::Debian,
+        site: "debian",
+        variant: MirrorVariant::Debian,
+        keyring: "/usr/share/keyrings/debian-archive-keyring.gpg",
+        gh_repo: "debian-museum",
+        script: "sid",
+        bootstrap_components: components::DEBIAN_BOOTSTRAP,
+        include_pkgs: Some(mirror::debian::include_pkgs()),
+    },
+    Project {
+        os: "ubuntu",
+        name: "ubuntu",
+        unstable_name: "ubuntu-dev",
+        unstable_versions: &["devel", "unstable"],
+        catalog: Kind::Ubuntu,
+        site: "ubuntu",
+        variant: MirrorVariant::Ubuntu,
+        keyring: "/usr/share/keyrings/ubuntu-archive-keyring.gpg",
+        gh_repo: "ubuntu-museum",
+        script: "gutsy",
+        bootstrap_components: components::UBUNTU_BOOTSTRAP,
+        include_pkgs: Some(mirror::ubuntu::include_pkgs()),
+    },
+    Project {
+        os: "devuan",
+        name: "devuan",
+        unstable_name: "devuan-ceres",
+        unstable_versions: &["ceres", "unstable"],
+        catalog: Kind::Devuan,
+        site: "devuan",
+        variant: MirrorVariant::Devuan,
+        keyring: "/usr/share/keyrings/devuan-archive-keyring.gpg",
+        gh_repo: "devuan-museum",
+        script: "ceres",
+        bootstrap_components: components::DEBIAN_BOOTSTRAP,
+        include_pkgs: Some(mirror::devuan::include_pkgs()),
+    },
+    Project {
+        os: "kali",
+        name: "kali",
+        unstable_name: "kali-rolling",
+        unstable_versions: &["rolling"],
+        catalog: Kind::Kali,
+        site: "kali",
+        variant: MirrorVariant::Kali,
+        keyring: "/usr/share/keyrings/kali-archive-keyring.gpg",
+        gh_repo: "kali-museum",
+        script: "kali",
+        bootstrap_components: components::DEBIAN_BOOTSTRAP,
+        include_pkgs: Some(mirror::kali::include_pkgs()),
+    },
+    Project {
+        os: "raspbian",
+        name: "raspbian",
+        unstable_name: "raspbian-sid",
+        unstable_versions: &["sid", "unstable"],
+        catalog: Kind::Raspbian,
+        site: "raspbian",
+        variant: MirrorVariant::Raspbian,
+        keyring: "/usr/share/keyrings/raspbian-archive-keyring.gpg",
+        gh_repo: "raspbian-museum",
+        script: "sid",
+        bootstrap_components: components::RASPBIAN_BOOTSTRAP,
+        include_pkgs: Some(mirror::raspbian::include_pkgs()),
+    },
+    Project {
+        os: "trisquel",
+        name: "trisquel",
+        unstable_name: "trisquel-dev",
+        unstable_versions: &[],
+        catalog: Kind::Trisquel,
+        site: "trisquel",
+        variant: MirrorVariant::Trisquel,
+        keyring: "/usr/share/keyrings/trisquel-archive-keyring.gpg",
+        gh_repo: "trisquel-museum",
+        script: "gutsy",
+        bootstrap_components: components::TRISQUEL_BOOTSTRAP,
+        include_pkgs: Some(mirror::trisquel::include_pkgs()),
+    },
+];
+
+impl Project {
+    /// --os (case-insensitive), e.g., Devuan
+    pub(crate) fn find(os: &str) -> Option<&'static Self> {
+        PROJECTS
+            .iter()
+            .find(|p| p.os.eq_ignore_ascii_case(os))
+    }
+
+    /// e.g., debian-sid => debian
+    pub(crate) fn from_name(name: &str) -> Option<&'static Self> {
+        PROJECTS
+            .iter()
+            .find(|p| p.name == name || p.unstable_name == name)
+    }
+
+    /// e.g., "kali/ kali-rolling" => kali
+    pub(crate) fn from_site(site: &str) -> Option<&'static Self> {
+        PROJECTS
+            .iter()
+            .find(|p| p.site == site)
+    }
+
+    pub(crate) fn is_unstable(&self, version: &str) -> bool {
+        self.unstable_versions
+            .contains(&version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_projects() {
+        let devuan = Project::find("Devuan").expect("No devuan");
+        assert_eq!(devuan.get_catalog(), &Kind::Devuan);
+        assert!(devuan.is_unstable("ceres"));
+
+        assert_eq!(
+            Project::from_name("ubuntu-dev").map(|p| *p.get_gh_repo()),
+            Some("ubuntu-museum")
+        );
+        assert_eq!(
+            Project::from_site("kali").map(|p| *p.get_variant()),
+            Some(MirrorVariant::Kali)
+        );
+        assert!(Project::find("arch").is_none());
+
+        // Each project has its own catalog
+        for p in &PROJECTS {
+            assert_eq!(p.get_catalog().to_string(), p.os)
+        }
+    }
+}
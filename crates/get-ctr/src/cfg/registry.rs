@@ -0,0 +1,414 @@
+//! The registries that the images are tagged for & pushed to (default: ghcr.io/2cd & reg.tmoe.me:2096).
+//!
+//! A user-supplied file (ron or toml) adds targets, e.g., Harbor, Quay or Docker Hub:
+//!
+//! ```toml
+//! # true: drops the built-in targets
+//! replace = true
+//!
+//! [[target]]
+//! name = "harbor"
+//! uri = "harbor.lan:8443"
+//! namespace = "museum"
+//! layout = "Series"
+//! date-tagged = true
+//! # The cache image of --save-cache & --restore-cache (default: reg)
+//! cache = true
+//!
+//! # Overrides the templates of the layout, `main[i]` is the manifest list of `arch[i]`
+//! [target.templates]
+//! arch = ["{project}:{series}-{arch}{-tag}", "{project}:{version}-{arch}{-tag}", "{project}:{version}-{arch}{-tag}"]
+//! main = ["{project}:{series}{-tag}", "{project}:{version}{-tag}", "{project}:latest"]
+//! ```
+//!
+//! A target with the same name as a built-in one (ghcr, reg) overrides it.
+//! See [crate::docker::tag_template] for the placeholders.
+
+use crate::docker::tag_template::{validate_reference, Template, Vars};
+use anyhow::{bail, ensure, Context};
+use getset::Getters;
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, ffi::OsStr, fs, path::Path, sync::OnceLock};
+
+pub(crate) const REG_URI: &str = "reg.tmoe.me:2096";
+pub(crate) const GHCR_URI: &str = "ghcr.io";
+
+pub(crate) const GHCR: &str = "ghcr";
+pub(crate) const REG: &str = "reg";
+
+/// How the repositories & tags of a target are named.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum RepoLayout {
+    /// One repository per project, e.g., ghcr.io/2cd/debian:potato-x86-base, date-tagged: ghcr.io/2cd/debian-sid:x64-2024-01-01
+    #[default]
+    Project,
+
+    /// One repository per series, e.g., reg.tmoe.me:2096/debian/potato:x86-base, date-tagged: reg.tmoe.me:2096/debian/sid:x64-2024-01-01
+    Series,
+}
+
+impl RepoLayout {
+    /// The built-in templates, `rolling`: the date-tagged suites, e.g., sid
+    pub(crate) fn templates(self, rolling: bool) -> Templates {
+        use RepoLayout::*;
+        let (arch, main) = match (self, rolling) {
+            (Project, false) => (
+                [
+                    "{project}:{series}-{arch}{-tag}",
+                    "{project}:{version}-{arch}{-tag}",
+                ],
+                ["{project}:{series}{-tag}", "{project}:{version}{-tag}"],
+            ),
+            (Project, true) => (
+                ["{project}:{arch}{-tag}", "{project}:{arch}{-tag}-{date}"],
+                ["{project}:{tag|latest}", "{project}:{tag-}{date}"],
+            ),
+            (Series, false) => (
+                [
+                    "{project}/{series}:{arch}{-tag}",
+                    "{project}/{version}:{arch}{-tag}",
+                ],
+                [
+                    "{project}/{series}:{tag|latest}",
+                    "{project}/{version}:{tag|latest}",
+                ],
+            ),
+            (Series, true) => (
+                [
+                    "{os}/{series}:{arch}{-tag}",
+                    "{os}/{series}:{arch}{-tag}-{date}",
+                ],
+                ["{os}/{series}:{tag|latest}", "{os}/{series}:{tag-}{date}"],
+            ),
+        };
+        let parse = |t: [&str; 2]| {
+            t.map(|s| {
+                s.parse()
+                    .expect("Invalid built-in template")
+            })
+            .to_vec()
+        };
+        Templates {
+            arch: parse(arch),
+            main: parse(main),
+        }
+    }
+}
+
+/// The image references (after [Target::prefix]) of a repository.
+#[derive(Getters, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[getset(get = "pub(crate) with_prefix")]
+pub(crate) struct Templates {
+    /// The arch tags, e.g., `{project}:{series}-{arch}{-tag}` => debian:potato-x86-base
+    arch: Vec<Template>,
+
+    /// The manifest lists, paired with `arch` by index, e.g., `{project}:{series}{-tag}` => debian:potato-base
+    main: Vec<Template>,
+}
+
+#[derive(Getters, Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[getset(get = "pub(crate) with_prefix")]
+#[serde(default)]
+pub(crate) struct Target {
+    /// e.g., ghcr => docker-dir/ghcr.ron, digest (docker.mirror.name) & credentials (GET_CTR_GHCR_TOKEN)
+    name: String,
+
+    /// e.g., ghcr.io, docker.io, harbor.lan:8443
+    uri: String,
+
+    /// e.g., 2cd => ghcr.io/2cd/debian
+    namespace: Option<String>,
+
+    layout: RepoLayout,
+
+    /// The rolling suites (e.g., sid) are also tagged with the date, e.g., x64-2024-01-01
+    #[serde(rename = "date-tagged", alias = "date_tagged")]
+    date_tagged: bool,
+
+    /// None: the templates of the layout
+    templates: Option<Templates>,
+
+    /// The templates of the rolling suites, None: the templates of the layout
+    #[serde(rename = "rolling-templates", alias = "rolling_templates")]
+    rolling_templates: Option<Templates>,
+
+    /// `--oci-push` uses http instead of https, e.g., a local `registry:2`
+    #[serde(rename = "plain-http", alias = "plain_http")]
+    plain_http: bool,
+
+    /// `--save-cache` & `--restore-cache` tag the cache image for this target (built-in: reg), see [cache_target]
+    cache: bool,
+}
+
+impl Target {
+    pub(crate) fn ghcr() -> Self {
+        Self {
+            name: GHCR.into(),
+            uri: GHCR_URI.into(),
+            namespace: Some("2cd".into()),
+            layout: RepoLayout::Project,
+            date_tagged: true,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn reg() -> Self {
+        Self {
+            name: REG.into(),
+            uri: REG_URI.into(),
+            namespace: None,
+            layout: RepoLayout::Series,
+            date_tagged: true,
+            cache: true,
+            ..Default::default()
+        }
+    }
+
+    /// e.g., ghcr.io/2cd, reg.tmoe.me:2096
+    pub(crate) fn prefix(&self) -> String {
+        match self.namespace.as_deref() {
+            Some(ns) if !ns.is_empty() => format!("{}/{ns}", self.uri),
+            _ => self.uri.to_owned(),
+        }
+    }
+
+    /// `rolling`: the date-tagged suites, e.g., sid
+    pub(crate) fn templates(&self, rolling: bool) -> Cow<'_, Templates> {
+        let custom = match rolling {
+            true => &self.rolling_templates,
+            _ => &self.templates,
+        };
+        match custom {
+            Some(t) => Cow::Borrowed(t),
+            _ => Cow::Owned(self.layout.templates(rolling)),
+        }
+    }
+
+    /// Renders & validates the image reference, e.g., ghcr.io/2cd/debian:potato-x86-base
+    pub(crate) fn render(
+        &self,
+        template: &Template,
+        vars: &Vars,
+    ) -> anyhow::Result<String> {
+        let reference = format!("{}/{}", self.prefix(), template.render(vars));
+        // without the registry host, e.g., 2cd/debian:potato-x86-base
+        let name = &reference[self.uri.len() + 1..];
+        validate_reference(name).with_context(|| {
+            format!(
+                "Invalid image reference of the target {}: {reference:?} (template: {template})",
+                self.name
+            )
+        })?;
+        Ok(reference)
+    }
+
+    /// {name}.ron in the docker dir, i.e., the arch tags of the target.
+    pub(crate) fn ron_filename(&self) -> String {
+        format!("{}.ron", self.name)
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        ensure!(
+            !self.name.is_empty()
+                && self
+                    .name
+                    .bytes()
+                    .all(|b| b.is_ascii_lowercase()
+                        || b.is_ascii_digit()
+                        || b == b'-'),
+            "Invalid target name: {:?}, expected: [a-z0-9-]+",
+            self.name
+        );
+        ensure!(
+            !self.uri.is_empty() && !self.uri.contains("://"),
+            "Invalid uri of the target {}: {:?}, e.g., ghcr.io",
+            self.name,
+            self.uri
+        );
+
+        for (rolling, t) in
+            [(false, &self.templates), (true, &self.rolling_templates)]
+        {
+            let Some(t) = t else { continue };
+            ensure!(
+                !t.arch.is_empty() && t.arch.len() == t.main.len(),
+                "The {}templates of the target {} need as many main as arch templates (>= 1)",
+                if rolling { "rolling-" } else { "" },
+                self.name
+            );
+            for template in t.arch.iter().chain(&t.main) {
+                for tag in [Some("base"), None] {
+                    self.render(template, &Vars::sample(tag))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct TargetFile {
+    replace: bool,
+    target: Vec<Target>,
+}
+
+static TARGETS: OnceLock<Vec<Target>> = OnceLock::new();
+
+/// Loads the targets once per run.
+pub(crate) fn set_static_targets(path: &Path) -> anyhow::Result<()> {
+    let targets = load(path)
+        .with_context(|| format!("Failed to load the registry targets: {path:?}"))?;
+    log::info!(
+        "registry targets: {:?}",
+        targets
+            .iter()
+            .map(|t| &t.name)
+            .collect::<Vec<_>>()
+    );
+
+    if TARGETS.set(targets).is_err() {
+        log::warn!("The registry targets have already been set")
+    }
+    Ok(())
+}
+
+/// The first target is the primary one, e.g., docker-dir/tag.ron & the usage in the digest.
+pub(crate) fn targets() -> &'static [Target] {
+    TARGETS.get_or_init(|| vec![Target::ghcr(), Target::reg()])
+}
+
+pub(crate) fn find(name: &str) -> Option<&'static Target> {
+    targets()
+        .iter()
+        .find(|t| t.name == name)
+}
+
+/// The first target with `cache = true` (default: reg), otherwise the primary one.
+pub(crate) fn cache_target() -> &'static Target {
+    let targets = targets();
+    targets
+        .iter()
+        .find(|t| t.cache)
+        .unwrap_or(&targets[0])
+}
+
+/// e.g., ghcr => 0, reg => 1
+pub(crate) fn position(name: &str) -> usize {
+    targets()
+        .iter()
+        .position(|t| t.name == name)
+        .unwrap_or(usize::MAX)
+}
+
+fn load(path: &Path) -> anyhow::Result<Vec<Target>> {
+    let content = fs::read_to_string(path)?;
+    parse(&content, path.extension())
+}
+
+fn parse(content: &str, ext: Option<&OsStr>) -> anyhow::Result<Vec<Target>> {
+    let file = match ext.and_then(OsStr::to_str) {
+        Some("toml") => toml::from_str::<TargetFile>(content)?,
+        _ => ron::from_str::<TargetFile>(content)?,
+    };
+
+    let mut targets = match file.replace {
+        true => Vec::with_capacity(file.target.len()),
+        _ => vec![Target::ghcr(), Target::reg()],
+    };
+    for t in file.target {
+        t.validate()?;
+        match targets
+            .iter_mut()
+            .find(|x| x.name == t.name)
+        {
+            Some(builtin) => *builtin = t,
+            _ => targets.push(t),
+        }
+    }
+
+    if targets.is_empty() {
+        bail!("No registry targets")
+    }
+    Ok(targets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_targets() -> anyhow::Result<()> {
+        const TOML: &str = r#"
+[[target]]
+name = "ghcr"
+uri = "ghcr.io"
+namespace = "museum"
+
+[[target]]
+name = "hub"
+uri = "docker.io"
+namespace = "museum"
+layout = "Series"
+
+[[target]]
+name = "local"
+uri = "localhost:5000"
+plain-http = true
+"#;
+        let targets = parse(TOML, Some(OsStr::new("toml")))?;
+        assert_eq!(targets.len(), 4);
+        assert_eq!(targets[0].prefix(), "ghcr.io/museum");
+        assert_eq!(targets[1], Target::reg());
+        assert_eq!(targets[2].get_layout(), &RepoLayout::Series);
+        assert!(!targets[2].get_date_tagged());
+        assert!(!targets[2].get_plain_http());
+        assert!(targets[3].get_plain_http());
+        assert_eq!(
+            targets
+                .iter()
+                .find(|t| t.cache)
+                .map(Target::prefix),
+            Some(REG_URI.into())
+        );
+
+        let targets = parse(
+            r#"(replace: true, target: [(name: "quay", uri: "quay.io", date_tagged: true)])"#,
+            Some(OsStr::new("ron")),
+        )?;
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[0].prefix(), "quay.io");
+
+        assert!(
+            parse(r#"(target: [(name: "Quay", uri: "quay.io")])"#, None).is_err()
+        );
+        assert!(
+            parse(r#"(target: [(name: "q", uri: "https://quay.io")])"#, None)
+                .is_err()
+        );
+        assert!(parse("(replace: true)", None).is_err());
+
+        // arch & main are paired by index
+        let err = parse(
+            r#"(target: [(name: "q", uri: "quay.io", templates: Some((arch: ["{project}:{arch}"], main: [])))])"#,
+            None,
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("as many main"),
+            "{err}"
+        );
+        assert!(parse(
+            r#"(target: [(name: "q", uri: "quay.io", namespace: Some("Museum"))])"#,
+            None
+        )
+        .is_ok());
+        assert!(parse(
+            r#"(target: [(name: "q", uri: "quay.io", namespace: Some("Museum"), templates: Some((arch: ["{project}:{arch}"], main: ["{project}:latest"])))])"#,
+            None
+        )
+        .is_err());
+        Ok(())
+    }
+}
@@ -1,23 +1,38 @@
 use crate::{
     cfg::{
+        catalog,
         debootstrap::{self, Source},
+        digest::Codec,
         disk::DiskV1,
+        mirror,
+        project::Project,
+        registry,
+    },
+    docker::{
+        oci::LayerCodec,
+        repo::{Repository, SrcFormat},
     },
-    docker::repo::{Repository, SrcFormat},
     logger,
     task::{
         build_rootfs,
         docker::{restore_cache, save_cache},
+        lint,
+        list::{self, ListFormat},
         old_old_debian,
-        pool::global_pool,
+        pool::join_global_pool,
+        verify,
     },
-    url::concat_url_path,
+    url::{concat_url_path, set_mirror_select, MirrorSelect},
 };
-use anyhow::{bail, Context};
+use anyhow::bail;
 use clap::{value_parser, Parser};
 use getset::Getters;
 use log::trace;
-use std::{path::PathBuf, process::exit, sync::OnceLock};
+use std::{
+    path::{Path, PathBuf},
+    process::exit,
+    sync::OnceLock,
+};
 
 pub(crate) const PKG_VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -26,7 +41,7 @@ pub(crate) const PKG_VERSION: &str = env!("CARGO_PKG_VERSION");
 #[command(arg_required_else_help = true)]
 /// Example: --os debian --ver 2.2 --tag base --obtain --build
 pub(crate) struct Cli {
-    /// OS Name, e.g., debian, ubuntu
+    /// OS Name, e.g., debian, ubuntu, devuan, kali, raspbian, trisquel
     #[arg(long, id = "OS_Name", default_value = "debian")]
     os: String,
 
@@ -55,22 +70,75 @@ pub(crate) struct Cli {
     #[arg(long, help_heading = "Operation")]
     obtain: bool,
 
-    /// pack to tar & compress to zstd
+    /// pack to tar & compress to zstd (see also: --format)
     #[arg(long, help_heading = "Operation")]
     repack: bool,
 
-    /// zstd compression level (0 ~ 22)
-    #[arg(long, help_heading = "Operation", value_parser = value_parser!(u8).range(0..=22), requires = "repack")]
-    zstd_level: Option<u8>,
+    /// Compression format. Docker 20.10 cannot import tar+zstd, use xz for old hosts.
+    #[arg(
+        long,
+        help_heading = "Operation",
+        value_enum,
+        default_value_t,
+        requires = "repack"
+    )]
+    format: Codec,
+
+    /// bit-for-bit reproducible rootfs tarballs (sorted entries, mtime clamped to the release date)
+    #[arg(long, help_heading = "Operation", requires = "obtain")]
+    reproducible: bool,
+
+    /// old old debian: only warn if the base tgz is not listed in md5sum.txt or Release (default: error)
+    #[arg(long, help_heading = "Operation", requires = "obtain")]
+    allow_unlisted_tgz: bool,
+
+    /// compression level (zstd: 0 ~ 22, xz & lzip: 0 ~ 9)
+    #[arg(long, alias = "zstd-level", help_heading = "Operation", value_parser = value_parser!(u8).range(0..=22), requires = "repack")]
+    level: Option<u8>,
+
+    /// zstd long distance matching & window log, same as `zstd --long=N` (10 ~ 31, default: 27). 0: disabled. Decompressing with N > 27 requires `zstd -d --long=N`.
+    #[arg(long, help_heading = "Operation", value_parser = parse_zstd_long, requires = "repack")]
+    zstd_long: Option<u32>,
+
+    /// Trains a zstd dictionary (zstd/VERSION-SERIES.dict) shared by all architectures of a release. Decompression requires `zstd -d -D file.dict`.
+    #[arg(long, help_heading = "Operation", requires = "repack")]
+    zstd_dict: bool,
 
     /// build container
     #[arg(long, help_heading = "Docker")]
     build: bool,
 
-    /// push to ghcr & reg
+    /// write an OCI image layout (oci/) without a docker daemon
+    #[arg(long, help_heading = "Docker")]
+    oci: bool,
+
+    /// compression of the OCI layers
+    #[arg(
+        long,
+        help_heading = "Docker",
+        value_enum,
+        default_value_t,
+        requires = "oci"
+    )]
+    oci_layer: LayerCodec,
+
+    /// registry target file (ron or toml), e.g., harbor, quay & docker hub (default: ghcr & reg), the tags can be templates, e.g., "{project}:{series}-{arch}{-tag}"
+    #[arg(
+        long,
+        env = "GET_CTR_REGISTRY_TARGETS",
+        value_name = "FILE",
+        help_heading = "Docker"
+    )]
+    registry_targets: Option<PathBuf>,
+
+    /// push to the registry targets (default: ghcr & reg)
     #[arg(long, help_heading = "Docker")]
     push: bool,
 
+    /// push the OCI layout (--oci) to the registry targets and save the repo digests, without docker. Credentials: GET_CTR_{TARGET}_{USER,TOKEN}, e.g., GET_CTR_GHCR_TOKEN
+    #[arg(long, help_heading = "Docker")]
+    oci_push: bool,
+
     /// i.e., docker:x86 + docker:arm -> docker:latest
     #[arg(long, help_heading = "Docker")]
     create_manifest: bool,
@@ -97,6 +165,66 @@ pub(crate) struct Cli {
     #[arg(long, help_heading = "Save Config")]
     release_tag: bool,
 
+    /// check the files (size & hash) referenced by a digest file, e.g., --verify digest.yml
+    #[arg(long, help_heading = "Verify", value_name = "DIGEST_FILE")]
+    verify: Option<PathBuf>,
+
+    /// dir of the release assets (default: the dir of the digest file)
+    #[arg(long, help_heading = "Verify", requires = "verify")]
+    asset_dir: Option<PathBuf>,
+
+    /// lang: selects by region (LANG); probe: selects the fastest available mirror, a failed download falls back to the next fastest one
+    #[arg(
+        long,
+        env = "GET_CTR_MIRROR_SELECT",
+        value_enum,
+        default_value_t,
+        help_heading = "Mirror"
+    )]
+    mirror_select: MirrorSelect,
+
+    /// mirror list file (ron or toml), merged with or replacing the built-in mirrors
+    #[arg(
+        long,
+        env = "GET_CTR_MIRROR_CATALOG",
+        value_name = "FILE",
+        help_heading = "Mirror"
+    )]
+    mirror_catalog: Option<PathBuf>,
+
+    /// distro catalog files or dirs (ron or toml), e.g., --catalog ./catalog/ (contains debian.ron, ubuntu.toml, disk.v1.ron)
+    #[arg(
+        long,
+        env = "GET_CTR_CATALOG",
+        value_name = "PATH",
+        value_delimiter = ':',
+        help_heading = "Catalog"
+    )]
+    catalog: Vec<PathBuf>,
+
+    /// check the catalogs (e.g., unknown arch, malformed date), then exit
+    #[arg(long, help_heading = "Catalog")]
+    lint: bool,
+
+    /// print the valid --ver, --arch & --tag values of an OS (default: all), then exit
+    #[arg(
+        long,
+        value_name = "OS",
+        help_heading = "Catalog",
+        num_args = 0..=1,
+        default_missing_value = " "
+    )]
+    list: Option<String>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        help_heading = "Catalog",
+        requires = "list"
+    )]
+    list_format: ListFormat,
+
     /// pack the [workdir] to cache.tar, then build & push to REG
     #[arg(long, help_heading = "CI", group = "cache")]
     save_cache: bool,
@@ -123,6 +251,31 @@ impl Cli {
     pub(crate) fn run(&self) -> anyhow::Result<()> {
         self.print_version();
 
+        // Relative to the current dir, not the workdir.
+        if let Some(digest_file) = self.get_verify() {
+            let asset_dir = match (self.get_asset_dir(), digest_file.parent()) {
+                (Some(d), _) => d.as_path(),
+                (_, Some(p)) if !p.as_os_str().is_empty() => p,
+                _ => Path::new("."),
+            };
+            return verify::verify_digest_file(digest_file, asset_dir);
+        }
+
+        set_mirror_select(*self.get_mirror_select());
+        catalog::set_static_search_path(self.get_catalog())?;
+        if *self.get_lint() {
+            return lint::lint_catalogs();
+        }
+        if let Some(os) = self.get_list() {
+            return list::print_list(Some(os), *self.get_list_format());
+        }
+        // Relative to the current dir, not the workdir.
+        if let Some(file) = self.get_mirror_catalog() {
+            mirror::catalog::set_static_catalog(file)?
+        }
+        if let Some(file) = self.get_registry_targets() {
+            registry::set_static_targets(file)?
+        }
         crate::dir::set_static_workdir();
 
         if self.is_old_old_debian() {
@@ -132,12 +285,10 @@ impl Cli {
 
         log::debug!("Not old old debian");
 
-        match self.get_os().as_ref() {
-            "debian" => self.handle_debian()?,
-            // "ubuntu"
-            _ => self.handle_ubuntu()?,
-        }
-        Ok(())
+        let Some(project) = Project::find(self.get_os()) else {
+            bail!("Unknown OS: {}", self.get_os())
+        };
+        self.handle_modern_os(project)
     }
 
     fn handle_old_old_debian(&self) -> anyhow::Result<()> {
@@ -146,6 +297,7 @@ impl Cli {
         {
             let mirror = crate::url::debian_archive()?;
             let mut url_path = String::with_capacity(64);
+            let mut md5sum_path = String::with_capacity(64);
 
             for os in cfg
                 // .get_os()
@@ -153,7 +305,8 @@ impl Cli {
                 .filter(|o| o.get_version() == self.get_ver())
             {
                 for disk in os.get_disk() {
-                    concat_url_path(&mut url_path, os, disk);
+                    concat_url_path(&mut url_path, os, disk, os.get_base_tgz());
+                    concat_url_path(&mut md5sum_path, os, disk, "md5sum.txt");
                     let codename_lower = os
                         .get_codename()
                         .to_ascii_lowercase();
@@ -170,16 +323,29 @@ impl Cli {
                         .project("debian")
                         .osname("Debian")
                         .url(mirror.join(&url_path)?)
+                        .md5sum_url(mirror.join(&md5sum_path)?)
                         .date(disk.get_date())
                         .title_date(os.get_date())
                         .patch(os.get_patch())
+                        .zstd_long(*os.get_zstd_long())
                         .build();
                     repos.push(repo)
                 }
             }
         }
+        if repos.is_empty() {
+            return Err(list::empty_selection(
+                &list::disk_entries(&cfg),
+                self.get_os(),
+                self.get_ver(),
+                self.get_arch().as_deref(),
+            ));
+        }
 
         if *self.get_obtain() {
+            Self::static_reproducible(Some(*self.get_reproducible()));
+            Self::static_single_pass_zstd(Some(self.single_pass_zstd()));
+            Self::static_allow_unlisted_tgz(Some(*self.get_allow_unlisted_tgz()));
             old_old_debian::obtain(&repos)?;
         }
 
@@ -206,37 +372,18 @@ impl Cli {
         exit(0)
     }
 
-    /// debian 2.2 ~ sid
-    fn handle_debian(&self) -> anyhow::Result<()> {
-        let (project, date_tagged) = match self.get_ver().as_str() {
-            "sid" | "unstable" => ("debian-sid", true),
-            _ => ("debian", false),
+    /// debian 2.2 ~ sid, all ubuntu versions & the derivatives (e.g., devuan)
+    fn handle_modern_os(&self, project: &Project) -> anyhow::Result<()> {
+        // e.g., debian sid => debian-sid:x64-2024-01-01
+        let date_tagged = project.is_unstable(self.get_ver());
+        let project_name = match date_tagged {
+            true => project.get_unstable_name(),
+            _ => project.get_name(),
         };
-        self.handle_modern_os(project, date_tagged)
-    }
 
-    /// handles all ubuntu versions
-    fn handle_ubuntu(&self) -> anyhow::Result<()> {
-        let (project, date_tagged) = match self.get_ver().as_str() {
-            "devel" | "unstable" => ("ubuntu-dev", true),
-            _ => ("ubuntu", false),
-        };
-        self.handle_modern_os(project, date_tagged)
-    }
-
-    fn handle_modern_os(
-        &self,
-        project: &str,
-        date_tagged: bool,
-    ) -> anyhow::Result<()> {
-        log::debug!("parsing the {project} (ron config)");
-        let ron_str = match project {
-            "debian" | "debian-sid" => debootstrap::DEBIAN_RON,
-            _ => debootstrap::UBUNTU_RON,
-        };
-
-        let cfg = ron::from_str::<debootstrap::Cfg>(ron_str)
-            .context("Failed to parse ron")?;
+        let kind = *project.get_catalog();
+        log::debug!("loading the {kind} catalog");
+        let cfg = catalog::load::<debootstrap::Cfg>(kind)?;
         log::trace!("cfg: {cfg:?}");
 
         let mut repos = tinyvec::TinyVec::<[Repository; 20]>::new();
@@ -273,9 +420,8 @@ impl Cli {
                     _ => bail!("Empty Debootstrap Source"),
                 };
 
-                let repo_version = match project {
-                    "debian-sid" | "ubuntu-dev" => "unstable",
-                    // "ubuntu-dev" => "devel",
+                let repo_version = match date_tagged {
+                    true => "unstable",
                     _ => os
                         .get_version()
                         .split_ascii_whitespace()
@@ -298,7 +444,7 @@ impl Cli {
                     .deb822(*os.get_deb822_format())
                     .debootstrap_src(deb_src)
                     .deb_arch(tag.get_deb_arch())
-                    .project(project)
+                    .project(project_name)
                     .source(src_fmt)
                     .components(os.get_components().as_deref())
                     .date_tagged(date_tagged)
@@ -306,6 +452,14 @@ impl Cli {
                 repos.push(repo)
             }
         }
+        if repos.is_empty() {
+            return Err(list::empty_selection(
+                &list::debootstrap_entries(project, &cfg),
+                project.get_os(),
+                self.get_ver(),
+                self.get_arch().as_deref(),
+            ));
+        }
         repos.reverse();
 
         if *self.get_obtain() {
@@ -313,6 +467,8 @@ impl Cli {
                 *self.get_auto_add_extra_suites(),
             ));
             Self::static_compatibility_mode(Some(*self.get_compatibility_mode()));
+            Self::static_reproducible(Some(*self.get_reproducible()));
+            Self::static_single_pass_zstd(Some(self.single_pass_zstd()));
 
             build_rootfs::obtain(&repos)?;
         }
@@ -331,16 +487,56 @@ impl Cli {
         *B.get_or_init(|| init.unwrap_or(false))
     }
 
+    pub(crate) fn static_reproducible(init: Option<bool>) -> bool {
+        static B: OnceLock<bool> = OnceLock::new();
+        *B.get_or_init(|| init.unwrap_or(false))
+    }
+
+    pub(crate) fn static_allow_unlisted_tgz(init: Option<bool>) -> bool {
+        static B: OnceLock<bool> = OnceLock::new();
+        *B.get_or_init(|| init.unwrap_or(false))
+    }
+
+    /// `--obtain --repack --format zstd` (without `--zstd-dict`) packs & compresses the rootfs in a single pass.
+    fn single_pass_zstd(&self) -> Option<old_old_debian::SinglePassZstd> {
+        let codec = *self.get_format();
+        (*self.get_repack() && codec == Codec::Zstd && !*self.get_zstd_dict()).then(
+            || old_old_debian::SinglePassZstd {
+                lv: old_old_debian::archive_level(codec, self.get_level().as_ref()),
+                zstd_long: *self.get_zstd_long(),
+            },
+        )
+    }
+
+    pub(crate) fn static_single_pass_zstd(
+        init: Option<Option<old_old_debian::SinglePassZstd>>,
+    ) -> Option<old_old_debian::SinglePassZstd> {
+        static Z: OnceLock<Option<old_old_debian::SinglePassZstd>> = OnceLock::new();
+        *Z.get_or_init(|| init.flatten())
+    }
+
     fn cli_common(&self, repos: Vec<Repository>) -> Result<(), anyhow::Error> {
         if *self.get_repack() {
-            old_old_debian::repack(&repos, self.get_zstd_level().as_ref())?;
+            old_old_debian::repack(
+                &repos,
+                *self.get_format(),
+                self.get_level().as_ref(),
+                *self.get_zstd_long(),
+                *self.get_zstd_dict(),
+            )?;
         }
         if *self.get_build() {
             old_old_debian::docker_task::docker_build(&repos)?;
         }
+        if *self.get_oci() {
+            old_old_debian::docker_task::oci_build(&repos, *self.get_oci_layer())?;
+        }
         if *self.get_push() {
             old_old_debian::docker_task::docker_push(&repos)?;
         }
+        if *self.get_oci_push() {
+            old_old_debian::docker_task::oci_push(&repos)?;
+        }
         if *self.get_create_manifest() {
             old_old_debian::docker_task::create_manifest(&repos)?;
         }
@@ -362,7 +558,7 @@ impl Cli {
             let first = first();
             println!("{}{}", first.get_version(), first.opt_tag_suffix());
         };
-        global_pool().join();
+        join_global_pool()?;
 
         if *self.get_restore_cache() {
             restore_cache(first())?;
@@ -378,6 +574,15 @@ impl Cli {
     }
 }
 
+/// 0 or 10 ~ 31
+fn parse_zstd_long(s: &str) -> Result<u32, String> {
+    match s.parse::<u32>() {
+        Ok(n @ (0 | 10..=31)) => Ok(n),
+        Ok(n) => Err(format!("{n} is not in 0 or 10..=31")),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
 fn get_src_format(sub_src: &Source, main_src: &Source) -> SrcFormat {
     match (
         (sub_src.get_src(), sub_src.get_enabled()),
@@ -1,7 +1,6 @@
 use anyhow::Context;
 use core::fmt::Display;
 use log::{error, info};
-use log_l10n::level::color::OwoColorize;
 use std::{
     ffi::OsStr,
     os::unix::process::ExitStatusExt,
@@ -10,21 +9,9 @@ use std::{
     sync::OnceLock,
 };
 use tinyvec::TinyVec;
-use url::Url;
 
 use crate::task::build_rootfs;
 
-pub(crate) fn run_curl(url: &Url, fname: &str) {
-    info!(
-        "{curl} {lo} {file} {url}",
-        curl = "curl".green(),
-        lo = "-Lo".cyan(),
-        file = fname.magenta(),
-        url = url.yellow()
-    );
-    run("curl", &["-L", "-o", fname, url.as_str()], true);
-}
-
 pub(crate) fn spawn_cmd(cmd: &str, args: &[&str]) -> Child {
     Command::new(cmd)
         .args(args)
@@ -116,6 +103,12 @@ fn check_status_and_exit(status: ExitStatus) {
     }
 }
 
+pub(crate) fn is_root() -> bool {
+    let uid = unsafe { libc::getuid() };
+    log::debug!("uid: {uid}");
+    uid == 0
+}
+
 /// If the current uid is not 0 (non-root user), sudo and doas are automatically detected and a new process are run synchronously and blockingly.
 pub(crate) fn run_as_root<S, A>(
     cmd: S,
@@ -127,9 +120,7 @@ where
     A: AsRef<OsStr>,
     S: AsRef<OsStr>,
 {
-    let uid = unsafe { libc::getuid() };
-    log::debug!("uid: {uid}");
-    if uid == 0 {
+    if is_root() {
         return run(cmd, args, exit_if_failure);
     }
 
@@ -184,6 +175,7 @@ fn doas_or_sudo() -> RootCmd {
 }
 
 fn cmd_exists(bin_name: &str) -> bool {
+    #[allow(clippy::unnecessary_map_or)]
     let which_cmd = Command::new("which")
         .arg(bin_name)
         .stdout(Stdio::null())
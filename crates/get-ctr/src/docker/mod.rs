@@ -2,8 +2,11 @@ use crate::command::spawn_cmd;
 use log::{debug, info};
 use std::{path::Path, process::Child};
 
+pub(crate) mod oci;
+pub(crate) mod registry;
 pub(crate) mod repo;
 pub(crate) mod repo_map;
+pub(crate) mod tag_template;
 // pub(crate) mod
 pub(crate) const DOCKER_FILE_OLD_CONTENT: &str = include_str!(concat!(
     env!("CARGO_MANIFEST_DIR"),
@@ -0,0 +1,500 @@
+//! Writes an [OCI image layout](https://github.com/opencontainers/image-spec/blob/main/image-layout.md) from the rootfs tarball, i.e., the same image as `FROM scratch / ADD base.tar /`, without a docker daemon.
+//!
+//! ```txt
+//! oci/2.2-potato-base/
+//! ├── blobs/sha256/{layer,config,manifest,index}
+//! ├── index.json
+//! └── oci-layout
+//! ```
+
+use anyhow::{bail, Context};
+use clap::ValueEnum;
+use hash_digest::multi::{Algorithm, HashReader, HashWriter, MultiHasher};
+use repack::{
+    compression::{Operation, Upack},
+    io_buffer::{buf_reader, buf_writer},
+};
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub(crate) const MEDIA_TYPE_INDEX: &str = "application/vnd.oci.image.index.v1+json";
+pub(crate) const MEDIA_TYPE_MANIFEST: &str =
+    "application/vnd.oci.image.manifest.v1+json";
+pub(crate) const MEDIA_TYPE_CONFIG: &str =
+    "application/vnd.oci.image.config.v1+json";
+
+/// The tag of a manifest in index.json, e.g., potato-x86-base
+pub(crate) const ANNOTATION_REF_NAME: &str = "org.opencontainers.image.ref.name";
+/// The full name for `ctr image import`, e.g., ghcr.io/2cd/debian:potato-x86-base
+pub(crate) const ANNOTATION_IMAGE_NAME: &str = "io.containerd.image.name";
+
+/// The [pre-defined annotation keys](https://github.com/opencontainers/image-spec/blob/main/annotations.md#pre-defined-annotation-keys), also used as the image labels.
+pub(crate) const ANNOTATION_CREATED: &str = "org.opencontainers.image.created";
+pub(crate) const ANNOTATION_VERSION: &str = "org.opencontainers.image.version";
+pub(crate) const ANNOTATION_SOURCE: &str = "org.opencontainers.image.source";
+pub(crate) const ANNOTATION_REVISION: &str = "org.opencontainers.image.revision";
+pub(crate) const ANNOTATION_TITLE: &str = "org.opencontainers.image.title";
+/// Where the rootfs comes from, e.g., the base tarball or the snapshot of debootstrap.
+///
+/// The URL is on the official mirror (see [crate::url::official_url]), so it is the same on every build host.
+///
+/// Not `org.opencontainers.image.base.name`, which is the reference of the base image (these images are built from scratch).
+pub(crate) const ANNOTATION_ROOTFS_SOURCE: &str = "io.github.2cd.rootfs.source";
+
+pub(crate) type Annotations = BTreeMap<String, String>;
+
+#[derive(ValueEnum, Debug, Clone, Copy, Default, derive_more::Display)]
+pub(crate) enum LayerCodec {
+    /// Supported by all runtimes
+    #[default]
+    #[display("gzip")]
+    Gzip,
+
+    /// Requires docker 23+ or containerd 1.5+
+    #[value(alias = "zst")]
+    #[display("zstd")]
+    Zstd,
+}
+
+impl LayerCodec {
+    pub(crate) const fn media_type(&self) -> &'static str {
+        match self {
+            Self::Gzip => "application/vnd.oci.image.layer.v1.tar+gzip",
+            Self::Zstd => "application/vnd.oci.image.layer.v1.tar+zstd",
+        }
+    }
+
+    const fn tar_extension(&self) -> &'static str {
+        match self {
+            Self::Gzip => "tar.gz",
+            Self::Zstd => "tar.zst",
+        }
+    }
+
+    const fn level(&self) -> u32 {
+        match self {
+            Self::Gzip => 9,
+            Self::Zstd => 19,
+        }
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Descriptor {
+    pub(crate) media_type: String,
+    /// sha256:hex
+    pub(crate) digest: String,
+    pub(crate) size: u64,
+    pub(crate) platform: Option<Platform>,
+    pub(crate) annotations: Option<Annotations>,
+}
+
+impl Descriptor {
+    fn new(media_type: &str, hex: &str, size: u64) -> Self {
+        Self {
+            media_type: media_type.into(),
+            digest: format!("sha256:{hex}"),
+            size,
+            platform: None,
+            annotations: None,
+        }
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Platform {
+    pub(crate) architecture: String,
+    pub(crate) os: String,
+    pub(crate) variant: Option<String>,
+}
+
+impl Platform {
+    /// e.g., linux/arm/v7 => {os: linux, architecture: arm, variant: v7}
+    pub(crate) fn parse(platform: &str) -> Self {
+        let mut parts = platform.splitn(3, '/');
+        let os = parts.next().unwrap_or("linux");
+        let architecture = parts.next().unwrap_or_default();
+        Self {
+            architecture: architecture.into(),
+            os: os.into(),
+            variant: parts.next().map(Into::into),
+        }
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug)]
+struct ImageConfig {
+    created: Option<String>,
+    architecture: String,
+    os: String,
+    variant: Option<String>,
+    config: ExecConfig,
+    rootfs: RootFs,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug)]
+struct ExecConfig {
+    #[serde(rename = "Env")]
+    env: Vec<String>,
+    #[serde(rename = "Cmd")]
+    cmd: Vec<String>,
+    #[serde(rename = "Labels")]
+    labels: Option<Annotations>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct RootFs {
+    #[serde(rename = "type")]
+    kind: String,
+    diff_ids: Vec<String>,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Manifest {
+    pub(crate) schema_version: u8,
+    pub(crate) media_type: String,
+    pub(crate) config: Descriptor,
+    pub(crate) layers: Vec<Descriptor>,
+    pub(crate) annotations: Option<Annotations>,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Index {
+    pub(crate) schema_version: u8,
+    pub(crate) media_type: String,
+    pub(crate) manifests: Vec<Descriptor>,
+    pub(crate) annotations: Option<Annotations>,
+}
+
+impl Index {
+    pub(crate) fn new(manifests: Vec<Descriptor>) -> Self {
+        Self {
+            schema_version: 2,
+            media_type: MEDIA_TYPE_INDEX.into(),
+            manifests,
+            annotations: None,
+        }
+    }
+}
+
+/// The single-layer image of a rootfs tarball.
+#[derive(Debug)]
+pub(crate) struct Image<'a> {
+    /// e.g., linux/arm/v7
+    pub(crate) platform: &'a str,
+    /// e.g., LANG=C.UTF-8
+    pub(crate) env: &'a [&'a str],
+    pub(crate) cmd: &'a [&'a str],
+    /// RFC 3339, e.g., the build time of the rootfs
+    pub(crate) created: Option<String>,
+    pub(crate) codec: LayerCodec,
+    /// The image labels & the manifest annotations, see [crate::docker::repo::Repository::oci_labels]
+    pub(crate) labels: Option<Annotations>,
+}
+
+/// ENV & CMD of [crate::docker::DOCKER_FILE_FOR_NEW_DISTROS] & [crate::docker::DOCKER_FILE_OLD_CONTENT]
+pub(crate) const fn dockerfile_env(xterm: bool) -> &'static [&'static str] {
+    match xterm {
+        true => &["TERM=xterm", "LANG=C.UTF-8"],
+        _ => &["LANG=C.UTF-8"],
+    }
+}
+
+pub(crate) const DOCKERFILE_CMD: &[&str] = &["bash"];
+
+#[derive(Debug)]
+pub(crate) struct Layout {
+    dir: PathBuf,
+}
+
+impl Layout {
+    /// Creates `dir/oci-layout` & `dir/blobs/sha256/`
+    pub(crate) fn create<P: AsRef<Path>>(dir: P) -> anyhow::Result<Self> {
+        let dir = dir.as_ref().to_owned();
+        fs::create_dir_all(dir.join("blobs/sha256"))
+            .with_context(|| format!("Failed to create the OCI layout: {dir:?}"))?;
+        fs::write(dir.join("oci-layout"), r#"{"imageLayoutVersion":"1.0.0"}"#)?;
+        Ok(Self { dir })
+    }
+
+    pub(crate) fn get_dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// blobs/sha256/hex
+    pub(crate) fn blob_path(&self, digest: &str) -> PathBuf {
+        let hex = digest.trim_start_matches("sha256:");
+        self.dir
+            .join("blobs/sha256")
+            .join(hex)
+    }
+
+    pub(crate) fn write_json_blob<T: Serialize>(
+        &self,
+        media_type: &str,
+        value: &T,
+    ) -> anyhow::Result<Descriptor> {
+        let bytes = serde_json::to_vec(value)?;
+        let mut hasher = MultiHasher::new([Algorithm::Sha256]);
+        hasher.update(&bytes);
+        let hex = hasher.finalize()[&Algorithm::Sha256];
+
+        let desc = Descriptor::new(media_type, &hex, bytes.len() as _);
+        fs::write(self.blob_path(&desc.digest), bytes)?;
+        Ok(desc)
+    }
+
+    /// Compresses the tarball to a layer blob, then writes the config & manifest.
+    ///
+    /// Returns the descriptor of the manifest (with the platform).
+    pub(crate) fn add_image(
+        &self,
+        tar: &Path,
+        image: &Image,
+    ) -> anyhow::Result<Descriptor> {
+        log::info!("OCI layer: {tar:?} ({})", image.codec);
+        let tar_file = buf_reader(tar).with_context(|| {
+            format!("Failed to read the rootfs tarball: {tar:?}")
+        })?;
+
+        // The tarball (diff_id) & the layer are hashed while compressing.
+        let tmp = self.dir.join(format!(
+            "blobs/sha256/{}.{}",
+            tar.file_name()
+                .unwrap_or_default()
+                .to_string_lossy(),
+            image.codec.tar_extension()
+        ));
+        let mut tar_reader = HashReader::new(tar_file, [Algorithm::Sha256]);
+        let layer_writer = HashWriter::new(
+            buf_writer(&tmp).with_context(|| format!("Failed to create {tmp:?}"))?,
+            [Algorithm::Sha256],
+        );
+        let (_, layer_digests) = Upack::new(tar, &tmp)
+            .with_operation(Operation::Encode {
+                level: image.codec.level(),
+            })
+            .encode_stream(&mut tar_reader, layer_writer)
+            .with_context(|| format!("Failed to compress {tar:?}"))?
+            .finalize()?;
+        let diff_id = tar_reader.finalize()[&Algorithm::Sha256];
+
+        let layer = Descriptor::new(
+            image.codec.media_type(),
+            &layer_digests[&Algorithm::Sha256],
+            fs::metadata(&tmp)?.len(),
+        );
+        fs::rename(&tmp, self.blob_path(&layer.digest))?;
+
+        let platform = Platform::parse(image.platform);
+        let config = ImageConfig {
+            created: image.created.clone(),
+            architecture: platform.architecture.clone(),
+            os: platform.os.clone(),
+            variant: platform.variant.clone(),
+            config: ExecConfig {
+                env: image
+                    .env
+                    .iter()
+                    .map(|e| e.to_string())
+                    .collect(),
+                cmd: image
+                    .cmd
+                    .iter()
+                    .map(|c| c.to_string())
+                    .collect(),
+                labels: image.labels.clone(),
+            },
+            rootfs: RootFs {
+                kind: "layers".into(),
+                diff_ids: vec![format!("sha256:{diff_id}")],
+            },
+        };
+        let config = self.write_json_blob(MEDIA_TYPE_CONFIG, &config)?;
+
+        let manifest = Manifest {
+            schema_version: 2,
+            media_type: MEDIA_TYPE_MANIFEST.into(),
+            config,
+            layers: vec![layer],
+            annotations: image.labels.clone(),
+        };
+        let desc = self.write_json_blob(MEDIA_TYPE_MANIFEST, &manifest)?;
+        Ok(Descriptor {
+            platform: Some(platform),
+            ..desc
+        })
+    }
+
+    /// Writes index.json
+    pub(crate) fn write_index(&self, index: &Index) -> anyhow::Result<()> {
+        fs::write(
+            self.dir.join("index.json"),
+            serde_json::to_vec_pretty(index)?,
+        )?;
+        Ok(())
+    }
+
+    /// Opens an existing layout, e.g., oci/2.2-potato-base/
+    pub(crate) fn open<P: AsRef<Path>>(dir: P) -> anyhow::Result<Self> {
+        let dir = dir.as_ref().to_owned();
+        if !dir.join("oci-layout").exists() {
+            bail!("{dir:?} is not an OCI layout, you may need to rebuild it using `--oci`.")
+        }
+        Ok(Self { dir })
+    }
+
+    pub(crate) fn read_index(&self) -> anyhow::Result<Index> {
+        let file = self.dir.join("index.json");
+        let content = fs::read(&file)
+            .with_context(|| format!("Failed to read the OCI index: {file:?}"))?;
+        Ok(serde_json::from_slice(&content)?)
+    }
+
+    pub(crate) fn read_blob(&self, digest: &str) -> anyhow::Result<Vec<u8>> {
+        let file = self.blob_path(digest);
+        fs::read(&file).with_context(|| format!("Failed to read the blob: {file:?}"))
+    }
+
+    /// Finds the descriptor by io.containerd.image.name, or by ref.name (e.g., the same tag on reg & ghcr).
+    pub(crate) fn find_ref<'i>(
+        index: &'i Index,
+        image_name: &str,
+    ) -> Option<&'i Descriptor> {
+        let annotations = ref_annotations(image_name);
+        [ANNOTATION_IMAGE_NAME, ANNOTATION_REF_NAME]
+            .into_iter()
+            .find_map(|key| {
+                index
+                    .manifests
+                    .iter()
+                    .find(|d| {
+                        d.annotations
+                            .as_ref()
+                            .and_then(|a| a.get(key))
+                            == Some(&annotations[key])
+                    })
+            })
+    }
+}
+
+/// ref.name & image.name annotations, e.g., `ghcr.io/2cd/debian:potato-x86-base`
+pub(crate) fn ref_annotations(image_name: &str) -> Annotations {
+    let tag = image_name
+        .rsplit_once(':')
+        .filter(|(_, t)| !t.contains('/'))
+        .map_or("latest", |(_, t)| t);
+    Annotations::from([
+        (ANNOTATION_REF_NAME.into(), tag.into()),
+        (ANNOTATION_IMAGE_NAME.into(), image_name.into()),
+    ])
+}
+
+/// The annotations that all `manifests` have in common, e.g., created & version of the arch images in an index.
+pub(crate) fn common_annotations(manifests: &[Annotations]) -> Option<Annotations> {
+    let (first, rest) = manifests.split_first()?;
+    let common = first
+        .iter()
+        .filter(|(k, v)| {
+            rest.iter()
+                .all(|a| a.get(*k) == Some(*v))
+        })
+        .map(|(k, v)| (k.to_owned(), v.to_owned()))
+        .collect::<Annotations>();
+    (!common.is_empty()).then_some(common)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hash_digest::sha256;
+
+    #[test]
+    fn write_oci_layout() -> anyhow::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let tar = tmp.path().join("base.tar");
+        fs::write(&tar, "debian ".repeat(4096))?;
+
+        let layout = Layout::create(tmp.path().join("oci"))?;
+        let image = Image {
+            platform: "linux/arm/v7",
+            env: dockerfile_env(false),
+            cmd: DOCKERFILE_CMD,
+            created: None,
+            codec: LayerCodec::Gzip,
+            labels: Some(Annotations::from([
+                (ANNOTATION_VERSION.into(), "2.2".into()),
+                (ANNOTATION_TITLE.into(), "Debian 2.2 (Potato) armv7".into()),
+            ])),
+        };
+        let desc = layout.add_image(&tar, &image)?;
+        assert_eq!(
+            desc.platform
+                .as_ref()
+                .and_then(|p| p.variant.as_deref()),
+            Some("v7")
+        );
+
+        let manifest: Manifest =
+            serde_json::from_slice(&fs::read(layout.blob_path(&desc.digest))?)?;
+        let layer = layout.blob_path(&manifest.layers[0].digest);
+        assert_eq!(fs::metadata(&layer)?.len(), manifest.layers[0].size);
+        assert_eq!(
+            format!("sha256:{}", sha256::get(&layer)?),
+            manifest.layers[0].digest
+        );
+
+        let config = fs::read_to_string(layout.blob_path(&manifest.config.digest))?;
+        assert!(config.contains(r#""Env":["LANG=C.UTF-8"],"Cmd":["bash"]"#));
+        assert!(config.contains(&format!("sha256:{}", sha256::get(&tar)?)));
+        assert!(config.contains(r#""Labels":{"org.opencontainers.image.title""#));
+        assert_eq!(manifest.annotations, image.labels);
+
+        let x86 = Annotations::from([
+            (ANNOTATION_VERSION.into(), "2.2".into()),
+            (ANNOTATION_TITLE.into(), "Debian 2.2 (Potato) x86".into()),
+        ]);
+        assert_eq!(
+            common_annotations(&[
+                manifest
+                    .annotations
+                    .unwrap_or_default(),
+                x86
+            ])
+            .map(|a| a
+                .into_keys()
+                .collect::<Vec<_>>()),
+            Some(vec![ANNOTATION_VERSION.to_owned()])
+        );
+        assert!(common_annotations(&[]).is_none());
+
+        let mut index = Index::new(vec![desc]);
+        index.manifests[0].annotations =
+            Some(ref_annotations("reg.tmoe.me:2096/debian/potato:x86-base"));
+        layout.write_index(&index)?;
+        let index = Layout::open(layout.get_dir())?.read_index()?;
+        assert_eq!(
+            index.manifests[0]
+                .annotations
+                .as_ref()
+                .map(|a| a[ANNOTATION_REF_NAME].as_str()),
+            Some("x86-base")
+        );
+        assert!(Layout::find_ref(&index, "ghcr.io/2cd/debian:x86-base").is_some());
+        Ok(())
+    }
+}
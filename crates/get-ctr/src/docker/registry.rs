@@ -0,0 +1,542 @@
+//! A minimal [OCI distribution](https://github.com/opencontainers/distribution-spec/blob/main/spec.md) client that pushes the images of an [OCI layout](super::oci::Layout), i.e., `docker push` & `docker manifest push` without a docker daemon.
+//!
+//! Supports anonymous, Basic & Bearer (token) auth. The credentials come from [DockerMirror] (user & token).
+
+use crate::{
+    cfg::digest::DockerMirror,
+    docker::oci::{self, Descriptor, Index, Layout, Manifest},
+    http::{self, io_err, Error},
+};
+use anyhow::{bail, Context};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use hash_digest::multi::{Algorithm, MultiHasher};
+use log::{debug, info};
+use regex::Regex;
+use std::{cell::RefCell, fs::File, path::Path, sync::OnceLock};
+use url::Url;
+
+/// e.g., reg.tmoe.me:2096/debian/potato:x86-base => {registry: reg.tmoe.me:2096, repository: debian/potato, reference: x86-base}
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Reference {
+    pub(crate) registry: String,
+    pub(crate) repository: String,
+    /// tag or digest
+    pub(crate) reference: String,
+    /// http instead of https, see [crate::cfg::registry::Target::get_plain_http]
+    pub(crate) plain_http: bool,
+}
+
+impl Reference {
+    pub(crate) fn parse(image: &str) -> Self {
+        let (name, reference) = match image.rsplit_once('@') {
+            Some((n, digest)) => (n, digest),
+            _ => match image.rsplit_once(':') {
+                Some((n, tag)) if !tag.contains('/') => (n, tag),
+                _ => (image, "latest"),
+            },
+        };
+
+        let (registry, repository) = match name.split_once('/') {
+            Some((host, repo))
+                if host.contains(['.', ':']) || host == "localhost" =>
+            {
+                (host, repo.to_owned())
+            }
+            Some(_) => ("docker.io", name.to_owned()),
+            _ => ("docker.io", format!("library/{name}")),
+        };
+
+        Self {
+            registry: registry.into(),
+            repository,
+            reference: reference.into(),
+            plain_http: false,
+        }
+    }
+
+    /// The same repository with another reference (e.g., sha256:123456abcdef)
+    pub(crate) fn with_reference(&self, reference: &str) -> Self {
+        Self {
+            reference: reference.into(),
+            ..self.clone()
+        }
+    }
+
+    pub(crate) fn with_plain_http(mut self, plain_http: bool) -> Self {
+        self.plain_http = plain_http;
+        self
+    }
+
+    /// e.g., https://ghcr.io/v2/2cd/debian
+    fn api_url(&self) -> String {
+        let host = match self.registry.as_str() {
+            "docker.io" => "registry-1.docker.io",
+            r => r,
+        };
+        let scheme = match self.plain_http {
+            true => "http",
+            _ => "https",
+        };
+        format!("{scheme}://{host}/v2/{}", self.repository)
+    }
+
+    /// xx/yy@sha256:123456abcdef
+    pub(crate) fn repo_digest(&self, digest: &str) -> String {
+        match self.registry.as_str() {
+            "docker.io" => format!("{}@{digest}", self.repository),
+            r => format!("{r}/{}@{digest}", self.repository),
+        }
+    }
+}
+
+/// The body of a request, files are streamed.
+enum Body<'a> {
+    Empty,
+    Bytes(&'a [u8]),
+    File(&'a Path),
+}
+
+#[derive(Debug)]
+pub(crate) struct Client {
+    agent: ureq::Agent,
+    /// Basic user:token
+    basic: Option<String>,
+    /// (repository, Authorization), the last successful auth.
+    auth: RefCell<Option<(String, String)>>,
+}
+
+impl Client {
+    /// If the mirror has no user & token, the registry is accessed anonymously.
+    pub(crate) fn new(mirror: &DockerMirror) -> Self {
+        let basic = match (mirror.get_user(), mirror.get_token()) {
+            (Some(user), Some(token)) => Some(format!(
+                "Basic {}",
+                BASE64_STANDARD.encode(format!("{user}:{token}"))
+            )),
+            _ => None,
+        };
+        Self {
+            agent: http::client().agent().clone(),
+            basic,
+            auth: RefCell::default(),
+        }
+    }
+
+    /// Sends the request; on 401, authenticates with the challenge (WWW-Authenticate), then retries once.
+    fn send(
+        &self,
+        method: &str,
+        url: &str,
+        image: &Reference,
+        content_type: Option<&str>,
+        body: &Body,
+    ) -> Result<ureq::Response, Error> {
+        let mut authenticated = false;
+        loop {
+            let mut req = self.agent.request(method, url);
+            if let Some((_, auth)) = self
+                .auth
+                .borrow()
+                .as_ref()
+                .filter(|(repo, _)| repo == &image.repository)
+            {
+                req = req.set("Authorization", auth)
+            }
+            if let Some(t) = content_type {
+                req = req.set("Content-Type", t)
+            }
+
+            let resp = match body {
+                Body::Empty => req.call(),
+                Body::Bytes(b) => req.send_bytes(b),
+                Body::File(p) => {
+                    let file = File::open(p).map_err(io_err)?;
+                    let len = file
+                        .metadata()
+                        .map_err(io_err)?
+                        .len();
+                    req.set("Content-Length", &len.to_string())
+                        .send(file)
+                }
+            };
+
+            match resp {
+                Err(ureq::Error::Status(401, r)) if !authenticated => {
+                    authenticated = true;
+                    let challenge = r
+                        .header("WWW-Authenticate")
+                        .unwrap_or_default()
+                        .to_owned();
+                    let auth = self
+                        .authenticate(&challenge, image)
+                        .map_err(|e| {
+                            debug!("{e:#}");
+                            Box::new(ureq::Error::Status(401, r))
+                        })?;
+                    *self.auth.borrow_mut() = Some((image.repository.clone(), auth));
+                }
+                r => return r.map_err(Box::new),
+            }
+        }
+    }
+
+    /// Returns the Authorization header for the challenge, e.g., `Bearer realm="https://ghcr.io/token",service="ghcr.io"`
+    fn authenticate(
+        &self,
+        challenge: &str,
+        image: &Reference,
+    ) -> anyhow::Result<String> {
+        let (scheme, params) = challenge
+            .split_once(' ')
+            .unwrap_or((challenge, ""));
+
+        if scheme.eq_ignore_ascii_case("basic") {
+            return self
+                .basic
+                .clone()
+                .context("The registry requires a user & token");
+        }
+
+        let params = parse_challenge(params);
+        let Some(realm) = params
+            .iter()
+            .find_map(|(k, v)| (*k == "realm").then_some(*v))
+        else {
+            bail!("Unsupported challenge: {challenge}")
+        };
+
+        let mut req = self.agent.get(realm).query(
+            "scope",
+            &format!("repository:{}:pull,push", image.repository),
+        );
+        if let Some(service) = params
+            .iter()
+            .find_map(|(k, v)| (*k == "service").then_some(*v))
+        {
+            req = req.query("service", service)
+        }
+        if let Some(basic) = &self.basic {
+            req = req.set("Authorization", basic)
+        }
+
+        info!("Requesting a token from {realm}");
+        let resp = req
+            .call()
+            .map_err(Box::new)
+            .with_context(|| format!("GET {realm}"))?;
+        let json: serde_json::Value = serde_json::from_reader(resp.into_reader())?;
+
+        let token = json["token"]
+            .as_str()
+            .or_else(|| json["access_token"].as_str())
+            .context("No token in the response")?;
+        Ok(format!("Bearer {token}"))
+    }
+
+    /// HEAD /v2/{repo}/blobs/{digest}
+    pub(crate) fn blob_exists(
+        &self,
+        image: &Reference,
+        digest: &str,
+    ) -> anyhow::Result<bool> {
+        let url = format!("{}/blobs/{digest}", image.api_url());
+        match self.send("HEAD", &url, image, None, &Body::Empty) {
+            Ok(_) => Ok(true),
+            Err(e) => match *e {
+                ureq::Error::Status(404, _) => Ok(false),
+                e => Err(e).with_context(|| format!("HEAD {url}")),
+            },
+        }
+    }
+
+    /// Uploads the blob (monolithic: POST, then PUT), unless it already exists.
+    pub(crate) fn push_blob(
+        &self,
+        image: &Reference,
+        digest: &str,
+        blob: &Path,
+    ) -> anyhow::Result<()> {
+        if self.blob_exists(image, digest)? {
+            debug!("The blob already exists: {digest}");
+            return Ok(());
+        }
+        info!(
+            "Uploading {digest} to {}/{}",
+            image.registry, image.repository
+        );
+
+        let url = format!("{}/blobs/uploads/", image.api_url());
+        let resp = self
+            .send("POST", &url, image, None, &Body::Empty)
+            .with_context(|| format!("POST {url}"))?;
+        let location = resp
+            .header("Location")
+            .context("No Location in the response")?;
+
+        // The Location may be relative, e.g., /v2/debian/potato/blobs/uploads/uuid?_state=...
+        let mut upload = Url::parse(&url)?.join(location)?;
+        upload
+            .query_pairs_mut()
+            .append_pair("digest", digest);
+
+        let resp = self
+            .send(
+                "PUT",
+                upload.as_str(),
+                image,
+                Some("application/octet-stream"),
+                &Body::File(blob),
+            )
+            .with_context(|| format!("PUT {upload}"))?;
+
+        match resp.header("Docker-Content-Digest") {
+            Some(d) if d != digest => bail!("Digest mismatch: {d} != {digest}"),
+            _ => Ok(()),
+        }
+    }
+
+    /// PUT /v2/{repo}/manifests/{reference}, returns the digest from the registry (Docker-Content-Digest).
+    pub(crate) fn put_manifest(
+        &self,
+        image: &Reference,
+        media_type: &str,
+        manifest: &[u8],
+    ) -> anyhow::Result<String> {
+        let url = format!("{}/manifests/{}", image.api_url(), image.reference);
+        info!("Pushing the manifest: {url}");
+
+        let resp = self
+            .send("PUT", &url, image, Some(media_type), &Body::Bytes(manifest))
+            .with_context(|| format!("PUT {url}"))?;
+
+        Ok(match resp.header("Docker-Content-Digest") {
+            Some(d) => d.to_owned(),
+            _ => {
+                let mut hasher = MultiHasher::new([Algorithm::Sha256]);
+                hasher.update(manifest);
+                format!("sha256:{}", hasher.finalize()[&Algorithm::Sha256])
+            }
+        })
+    }
+
+    /// Pushes the image (manifest or index) of `desc` from the layout, including its blobs & child manifests.
+    ///
+    /// Returns the digest of the image.
+    pub(crate) fn push_image(
+        &self,
+        layout: &Layout,
+        desc: &Descriptor,
+        image: &Reference,
+    ) -> anyhow::Result<String> {
+        let bytes = layout.read_blob(&desc.digest)?;
+
+        match desc.media_type.as_str() {
+            oci::MEDIA_TYPE_INDEX => {
+                let index: Index = serde_json::from_slice(&bytes)?;
+                for m in &index.manifests {
+                    self.push_image(layout, m, &image.with_reference(&m.digest))?;
+                }
+            }
+            _ => {
+                let manifest: Manifest = serde_json::from_slice(&bytes)?;
+                for blob in [&manifest.config]
+                    .into_iter()
+                    .chain(&manifest.layers)
+                {
+                    self.push_blob(
+                        image,
+                        &blob.digest,
+                        &layout.blob_path(&blob.digest),
+                    )?;
+                }
+            }
+        }
+
+        self.put_manifest(image, &desc.media_type, &bytes)
+    }
+}
+
+/// e.g., `realm="https://ghcr.io/token",service="ghcr.io"` => [(realm, https://ghcr.io/token), (service, ghcr.io)]
+fn parse_challenge(params: &str) -> Vec<(&str, &str)> {
+    static R: OnceLock<Regex> = OnceLock::new();
+    R.get_or_init(|| Regex::new(r#"(\w+)="([^"]*)""#).expect("Invalid regex"))
+        .captures_iter(params)
+        .filter_map(|c| Some((c.get(1)?.as_str(), c.get(2)?.as_str())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{response, serve};
+    use crate::{
+        docker::oci::{Image, LayerCodec},
+        task::old_old_debian::docker_task::MainRepoDigests,
+    };
+    use std::{
+        collections::BTreeMap,
+        fs,
+        sync::{Arc, Mutex},
+    };
+
+    #[test]
+    fn parse_reference() {
+        let r = Reference::parse("reg.tmoe.me:2096/debian/potato:x86-base");
+        assert_eq!(r.registry, "reg.tmoe.me:2096");
+        assert_eq!(r.repository, "debian/potato");
+        assert_eq!(r.reference, "x86-base");
+        assert_eq!(r.api_url(), "https://reg.tmoe.me:2096/v2/debian/potato");
+        assert_eq!(
+            r.repo_digest("sha256:1"),
+            "reg.tmoe.me:2096/debian/potato@sha256:1"
+        );
+
+        let r = Reference::parse("debian");
+        assert_eq!(
+            (
+                r.registry.as_str(),
+                r.repository.as_str(),
+                r.reference.as_str()
+            ),
+            ("docker.io", "library/debian", "latest")
+        );
+        let local = Reference::parse("localhost:5000/2cd/debian@sha256:1");
+        assert_eq!(local.api_url(), "https://localhost:5000/v2/2cd/debian");
+        assert_eq!(
+            local
+                .with_plain_http(true)
+                .api_url(),
+            "http://localhost:5000/v2/2cd/debian"
+        );
+
+        assert_eq!(
+            parse_challenge(r#"realm="https://ghcr.io/token",service="ghcr.io""#),
+            [("realm", "https://ghcr.io/token"), ("service", "ghcr.io")]
+        );
+    }
+
+    /// path => blob or manifest
+    type Store = Arc<Mutex<BTreeMap<String, Vec<u8>>>>;
+
+    /// A stand-in for `registry:2` with Bearer auth. Returns (host:port, blobs & manifests).
+    fn serve_registry() -> (String, Store) {
+        let store = Store::default();
+        let host = Arc::new(OnceLock::<String>::new());
+
+        let (realm_host, data) = (host.clone(), store.clone());
+        let addr = serve(move |req| {
+            let path = req.path();
+            let authorized = req.header("Authorization") == Some("Bearer secret");
+            let mut data = data.lock().expect("lock");
+
+            let (status, extra) = match req.method() {
+                "GET" if path.starts_with("/token") => match req.header("Authorization")
+                    == Some(&format!("Basic {}", BASE64_STANDARD.encode("2cd:pat")))
+                {
+                    true => ("200 OK", String::new()),
+                    _ => ("403 Forbidden", String::new()),
+                },
+                _ if !authorized => (
+                    "401 Unauthorized",
+                    format!(
+                        "WWW-Authenticate: Bearer realm=\"http://{}/token\",service=\"stand-in\"\r\n",
+                        realm_host.get().expect("No host")
+                    ),
+                ),
+                "HEAD" => match data.contains_key(path) {
+                    true => ("200 OK", String::new()),
+                    _ => ("404 Not Found", String::new()),
+                },
+                "POST" => (
+                    "202 Accepted",
+                    "Location: /v2/2cd/debian/blobs/uploads/1?_state=x\r\n".into(),
+                ),
+                "PUT" if path.contains("/blobs/uploads/") => {
+                    let digest = path
+                        .rsplit_once("digest=")
+                        .map_or("", |(_, d)| d)
+                        .replace("%3A", ":");
+                    data.insert(format!("/v2/2cd/debian/blobs/{digest}"), req.body.clone());
+                    ("201 Created", format!("Docker-Content-Digest: {digest}\r\n"))
+                }
+                "PUT" => {
+                    data.insert(path.into(), req.body.clone());
+                    let mut hasher = MultiHasher::new([Algorithm::Sha256]);
+                    hasher.update(&req.body);
+                    let hex = hasher.finalize()[&Algorithm::Sha256];
+                    ("201 Created", format!("Docker-Content-Digest: sha256:{hex}\r\n"))
+                }
+                _ => ("404 Not Found", String::new()),
+            };
+            let body = match path.starts_with("/token") && status.starts_with("200")
+            {
+                true => r#"{"token":"secret"}"#,
+                _ => "",
+            };
+            response(status, &extra, body.as_bytes())
+        });
+        host.set(addr.clone())
+            .expect("host");
+        (addr, store)
+    }
+
+    #[test]
+    fn push_to_stand_in_registry() -> anyhow::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let tar = tmp.path().join("base.tar");
+        fs::write(&tar, "potato ".repeat(1024))?;
+
+        let layout = Layout::create(tmp.path().join("oci"))?;
+        let image = Image {
+            platform: "linux/386",
+            env: oci::dockerfile_env(true),
+            cmd: oci::DOCKERFILE_CMD,
+            created: None,
+            codec: LayerCodec::Gzip,
+            labels: None,
+        };
+        let manifest = layout.add_image(&tar, &image)?;
+        let index = layout.write_json_blob(
+            oci::MEDIA_TYPE_INDEX,
+            &Index::new(vec![manifest.clone()]),
+        )?;
+
+        let (addr, store) = serve_registry();
+        let mirror = DockerMirror::builder()
+            .name("reg")
+            .repositories(MainRepoDigests::new())
+            .user("2cd")
+            .token("pat")
+            .build();
+        let client = Client::new(&mirror);
+
+        let reference = Reference::parse(&format!("{addr}/2cd/debian:potato"))
+            .with_plain_http(true);
+        let digest = client.push_image(&layout, &index, &reference)?;
+        assert_eq!(digest, index.digest);
+
+        let config = serde_json::from_slice::<Manifest>(
+            &layout.read_blob(&manifest.digest)?,
+        )?
+        .config;
+        {
+            let store = store.lock().expect("lock");
+            assert!(store.contains_key("/v2/2cd/debian/manifests/potato"));
+            assert!(store.contains_key(&format!(
+                "/v2/2cd/debian/manifests/{}",
+                manifest.digest
+            )));
+            assert_eq!(
+                store[&format!("/v2/2cd/debian/blobs/{}", config.digest)],
+                layout.read_blob(&config.digest)?
+            );
+        }
+
+        // No token => 401
+        let anonymous = Client::new(&DockerMirror::default());
+        assert!(anonymous
+            .push_image(&layout, &index, &reference)
+            .is_err());
+        Ok(())
+    }
+}
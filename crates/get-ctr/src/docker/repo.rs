@@ -1,11 +1,6 @@
 use getset::Getters;
 use regex::Regex;
-use std::{
-    borrow::Cow,
-    fs, io,
-    path::{Path, PathBuf},
-    sync::OnceLock,
-};
+use std::{borrow::Cow, env, fs, io, path::Path, sync::OnceLock};
 use tinyvec::TinyVec;
 use typed_builder::TypedBuilder;
 use url::Url;
@@ -15,10 +10,19 @@ use crate::{
         components,
         debootstrap::DebootstrapSrc,
         disk::OsPatch,
-        mirror::{self, static_debian_snapshot, ubuntu, ubuntu_ports},
+        mirror::{
+            self, static_debian_snapshot, ubuntu, ubuntu_ports, MirrorVariant,
+        },
+        project::Project,
+        registry::{Target, Templates},
+    },
+    docker::{
+        get_oci_platform,
+        oci::{self, Annotations},
+        repo_map,
+        tag_template::Vars,
     },
-    command::run_and_get_stdout,
-    docker::{get_oci_platform, repo_map},
+    http,
     logger::{self, today_date},
 };
 
@@ -55,12 +59,19 @@ pub(crate) struct Repository<'r> {
     #[builder(setter(strip_option))]
     url: Option<Url>,
 
+    /// old old debian: md5sum.txt in the same disks dir as [Self::url]
+    #[builder(setter(strip_option))]
+    md5sum_url: Option<Url>,
+
     #[builder(setter(strip_option))]
     title_date: Option<&'r str>,
 
     #[builder(setter(into))]
     patch: Option<&'r OsPatch>,
 
+    /// `zstd --long=N` of the repacked tarball (0: disabled)
+    zstd_long: Option<u32>,
+
     deb822: bool,
 
     no_minbase: bool,
@@ -113,24 +124,28 @@ impl SrcFormat {
 
         // let enable_https = today_date().day()
         // match today_date().day()
-        match self {
+        // e.g., Official => Official.list, Official.sources & sources.list -> Official.list
+        let primary = match self {
             Self::Simple(s) => {
-                let mirrors = match s.as_str() {
-                    "ubuntu" => mirror::ubuntu::mirrors(),
-                    "ubuntu-ports" => mirror::ubuntu_ports::mirrors(),
-                    _ => mirror::ubuntu_old::mirrors(),
+                let variant = match s.as_str() {
+                    "ubuntu" => MirrorVariant::Ubuntu,
+                    "ubuntu-ports" => MirrorVariant::UbuntuPorts,
+                    _ => MirrorVariant::UbuntuOld,
                 };
-                for m in mirrors {
-                    let url = convert_to_url_str(&m, http_no_tls);
+                let mirrors = variant.mirrors();
+                for m in &mirrors {
+                    let url = convert_to_url_str(m, http_no_tls);
                     let name = m.get_name();
                     let one_line_style = ubuntu_one_line_style(series, &url);
                     let deb822_style =
                         ubuntu_deb822_style(series, &url, name, deb_arch);
-                    let legacy_file = legacy_src_list_path(&m, mirror_dir, name);
+                    let legacy_file =
+                        mirror_dir.join(format!("{}.list", list_stem(m)));
                     let deb822_file = legacy_file.with_extension("sources");
                     fs::write(legacy_file, one_line_style)?;
                     fs::write(deb822_file, deb822_style)?;
                 }
+                list_stem(&mirrors[0])
             }
             Self::Complex {
                 enabled,
@@ -138,25 +153,28 @@ impl SrcFormat {
             } => {
                 let components = get_debian_components(components);
 
-                let mut official_legacy_style = String::with_capacity(256);
-                let mut cdn_legacy_style = String::with_capacity(256);
-
-                let mut official_deb822_style = String::with_capacity(4096);
-                let mut cdn_deb822_style = String::with_capacity(4096);
                 let deb_vendor = match series {
                     "sarge" | "woody" | "potato" | "warty" => "",
                     _ => "[trusted=yes] ",
                 };
 
-                for src in enabled {
-                    let enabled = true;
-
+                let mut sites = Vec::with_capacity(8);
+                for (src, enabled) in enabled
+                    .iter()
+                    .map(|x| (x, true))
+                    .chain(
+                        disabled_srcs
+                            .iter()
+                            .flatten()
+                            .map(|x| (x, false)),
+                    )
+                {
                     let (suite, site_left, site_suffix) =
                         get_debian_suite_and_site(src)?;
                     let (mirrors, keyring) =
                         get_debian_mirrors_and_keyring(site_left);
 
-                    let mut deb_src = DebianSrc::builder()
+                    let deb_src = DebianSrc::builder()
                         .keyring(keyring)
                         .components(components)
                         .url(convert_to_url_str(&mirrors[0], http_no_tls))
@@ -168,64 +186,51 @@ impl SrcFormat {
                         .plain_http(http_no_tls)
                         .deb_arch(deb_arch)
                         .build();
-
-                    deb_src.update_debian_list(
-                        &mut official_legacy_style,
-                        &mut official_deb822_style,
-                        &mirrors[1],
-                        &mut cdn_legacy_style,
-                        &mut cdn_deb822_style,
-                    );
+                    sites.push((deb_src, mirrors));
                 }
 
-                if let Some(disabled) = disabled_srcs {
-                    for src in disabled {
-                        let enabled = false;
-                        let (suite, site_name, site_suffix) =
-                            get_debian_suite_and_site(src)?;
-                        let (mirrors, keyring) =
-                            get_debian_mirrors_and_keyring(site_name);
-
-                        let mut deb_src = DebianSrc::builder()
-                            .keyring(keyring)
-                            .components(components)
-                            .url(convert_to_url_str(&mirrors[0], http_no_tls))
-                            .url_suffix(site_suffix)
-                            .suite(suite)
-                            .enabled(enabled)
-                            .src(src)
-                            .deb_vendor(deb_vendor)
-                            .plain_http(http_no_tls)
-                            .deb_arch(deb_arch)
-                            .build();
+                // The files are named after the mirrors of the first site, e.g., [Official, NJU.CN] => Official.list & NJU.CN.list
+                let Some((_, primary)) = sites.first() else {
+                    anyhow::bail!("No enabled sources")
+                };
+                let primary = primary.clone();
+
+                for (i, pm) in primary.iter().enumerate() {
+                    let mut legacy_style = String::with_capacity(256);
+                    let mut deb822_style = String::with_capacity(4096);
 
+                    for (deb_src, mirrors) in &mut sites {
                         deb_src.update_debian_list(
-                            &mut official_legacy_style,
-                            &mut official_deb822_style,
-                            &mirrors[1],
-                            &mut cdn_legacy_style,
-                            &mut cdn_deb822_style,
+                            &mut legacy_style,
+                            &mut deb822_style,
+                            corresponding_mirror(mirrors, pm, i),
                         );
                     }
-                }
-
-                fs::write(mirror_dir.join("Official.list"), official_legacy_style)?;
-                fs::write(mirror_dir.join("NJU.CN.list"), cdn_legacy_style)?;
 
-                fs::write(
-                    mirror_dir.join("Official.sources"),
-                    official_deb822_style,
-                )?;
-                fs::write(mirror_dir.join("NJU.CN.sources"), cdn_deb822_style)?;
+                    let stem = list_stem(pm);
+                    fs::write(
+                        mirror_dir.join(format!("{stem}.list")),
+                        legacy_style,
+                    )?;
+                    fs::write(
+                        mirror_dir.join(format!("{stem}.sources")),
+                        deb822_style,
+                    )?;
+                }
+                list_stem(&primary[0])
             }
-        }
-        create_src_list_link(mirror_dir)?;
-        create_deb822_link(mirror_dir)?;
+        };
+        create_src_list_link(mirror_dir, &primary)?;
+        create_deb822_link(mirror_dir, &primary)?;
         Ok(())
     }
 }
 
-pub(crate) fn create_src_list_link(mirror_dir: &Path) -> io::Result<()> {
+/// sources.list -> {primary}.list
+pub(crate) fn create_src_list_link(
+    mirror_dir: &Path,
+    primary: &str,
+) -> io::Result<()> {
     let src_link = mirror_dir.join("sources.list");
 
     // link.exists() returns false when the link file points to a file that does not exist.
@@ -233,25 +238,50 @@ pub(crate) fn create_src_list_link(mirror_dir: &Path) -> io::Result<()> {
         fs::remove_file(&src_link)?;
     }
     std::os::unix::fs::symlink(
-        "../../usr/local/etc/apt/mirrors/Official.list",
+        format!("../../usr/local/etc/apt/mirrors/{primary}.list"),
         src_link,
     )
 }
 
-fn create_deb822_link(mirror_dir: &Path) -> io::Result<()> {
+fn create_deb822_link(mirror_dir: &Path, primary: &str) -> io::Result<()> {
     let deb822_link = mirror_dir.join("mirror.sources");
     if deb822_link.is_symlink() || deb822_link.exists() {
         fs::remove_file(&deb822_link)?;
     }
     std::os::unix::fs::symlink(
-        "../../../usr/local/etc/apt/mirrors/Official.sources",
+        format!("../../../usr/local/etc/apt/mirrors/{primary}.sources"),
         deb822_link,
     )
 }
 
+/// The mirror of another site that corresponds to `primary` (the `i`-th mirror of the first site).
+///
+/// Same name & region > same region (e.g., NJU.CN => ByteDance-Volcengine.CN for debian-debug) > same position > the first one.
+fn corresponding_mirror<'s>(
+    mirrors: &'s [mirror::Mirror<'static>],
+    primary: &mirror::Mirror<'_>,
+    i: usize,
+) -> &'s mirror::Mirror<'static> {
+    let stem = list_stem(primary);
+    mirrors
+        .iter()
+        .find(|m| list_stem(m) == stem)
+        .or_else(|| {
+            primary
+                .get_region()
+                .and_then(|region| {
+                    mirrors
+                        .iter()
+                        .find(|m| m.get_region() == &Some(region))
+                })
+        })
+        .or(mirrors.get(i))
+        .unwrap_or(&mirrors[0])
+}
+
 fn get_debian_mirrors_and_keyring(
     site_name: &str,
-) -> ([mirror::Mirror<'_>; 2], &str) {
+) -> (Vec<mirror::Mirror<'static>>, &str) {
     let mirrors = get_debian_mirrors(site_name);
     let keyring = get_debian_keyring(site_name);
     (mirrors, keyring)
@@ -396,23 +426,14 @@ Check-Valid-Until: no
 
     fn update_debian_list(
         &mut self,
-        official_legacy_style: &mut String,
-        official_deb822_style: &mut String,
-        cdn_mirror: &mirror::Mirror<'_>,
-        cdn_legacy_style: &mut String,
-        cdn_deb822_style: &mut String,
+        legacy_style: &mut String,
+        deb822_style: &mut String,
+        mirror: &mirror::Mirror<'_>,
     ) {
-        // official mirror:
-        official_legacy_style.push_str(&self.one_line_str());
-        official_legacy_style.push_str(&self.one_line_debsrc_str());
-
-        official_deb822_style.push_str(&self.debian_deb822_str());
-
-        // cdn mirror:
-        self.url = convert_to_url_str(cdn_mirror, self.plain_http);
-        cdn_legacy_style.push_str(&self.one_line_str());
-        cdn_legacy_style.push_str(&self.one_line_debsrc_str());
-        cdn_deb822_style.push_str(&self.debian_deb822_str());
+        self.url = convert_to_url_str(mirror, self.plain_http);
+        legacy_style.push_str(&self.one_line_str());
+        legacy_style.push_str(&self.one_line_debsrc_str());
+        deb822_style.push_str(&self.debian_deb822_str());
     }
 }
 
@@ -444,16 +465,19 @@ fn get_snapshot_url(ports: bool) -> Option<Url> {
 
     url.set_query(Some(&format!("year={year}&month={month}")));
 
-    if !check_http_status(url.as_str()) {
+    let client = http::client();
+    if !client.is_ok(url.as_str()) {
         let (new_year, new_month) =
             if month == 1 { (year - 1, 12) } else { (year, month - 1) };
 
         url.set_query(Some(&format!("year={new_year}&month={new_month}")));
-        if !check_http_status(url.as_str()) {
+        if !client.is_ok(url.as_str()) {
             return None;
         }
     }
-    let html = run_and_get_stdout("curl", &["-L", url.as_str()]).ok()?;
+    let html = client
+        .get_string(url.as_str())
+        .ok()?;
     log::trace!("html: {html}");
 
     let snapshot_iso8601 = html
@@ -484,15 +508,6 @@ fn get_snapshot_url(ports: bool) -> Option<Url> {
     Some(snap_url)
 }
 
-fn check_http_status(url: &str) -> bool {
-    let Ok(out) = run_and_get_stdout("curl", &["-LI", url]) else {
-        return false;
-    };
-
-    out.lines()
-        .any(|x| x.contains(" 200 OK"))
-}
-
 fn get_debian_components(components: Option<&str>) -> &str {
     components.unwrap_or(components::OLD_DEBIAN)
 }
@@ -503,19 +518,25 @@ fn get_debian_keyring(site_left: &str) -> &str {
         "debian-elts" | "debian-elts-official" => {
             "/etc/apt/trusted.gpg.d/freexian-archive-extended-lts.gpg"
         }
-        _ => "/usr/share/keyrings/debian-archive-keyring.gpg",
+        // e.g., devuan, kali
+        site => Project::from_site(site)
+            .map_or("/usr/share/keyrings/debian-archive-keyring.gpg", |p| {
+                p.get_keyring()
+            }),
     }
 }
 
-fn get_debian_mirrors(site_left: &str) -> [mirror::Mirror<'_>; 2] {
-    match site_left {
-        "debian-elts" | "debian-elts-official" => mirror::debian_elts::mirrors(),
-        "debian-debug" => mirror::debian_debug::mirrors(),
-        "debian-archive" => mirror::debian_archive::root_mirrors(),
-        "debian-ports" => mirror::debian_ports::mirrors(),
-        "debian-security" => mirror::debian_security::mirrors(),
-        _ => mirror::debian::mirrors(),
-    }
+fn get_debian_mirrors(site_left: &str) -> Vec<mirror::Mirror<'static>> {
+    let variant = match site_left {
+        "debian-elts" | "debian-elts-official" => MirrorVariant::DebianELTS,
+        "debian-debug" => MirrorVariant::DebianDebug,
+        "debian-archive" => return mirror::archive_root_mirrors(),
+        "debian-ports" => MirrorVariant::DebianPorts,
+        "debian-security" => MirrorVariant::DebianSecurity,
+        site => Project::from_site(site)
+            .map_or(MirrorVariant::Debian, |p| *p.get_variant()),
+    };
+    variant.mirrors()
 }
 
 fn ubuntu_one_line_style(suite: &str, url: &str) -> String {
@@ -592,16 +613,12 @@ Check-Valid-Until: no
     )
 }
 
-fn legacy_src_list_path(
-    m: &mirror::Mirror<'_>,
-    mirror_dir: &Path,
-    name: &str,
-) -> PathBuf {
-    let (region_prefix, region) = match m.get_region() {
-        Some(r) => (".", r),
-        _ => ("", &""),
-    };
-    mirror_dir.join(format!("{name}{region_prefix}{region}.list"))
+/// e.g., Official, NJU.CN
+pub(crate) fn list_stem(m: &mirror::Mirror<'_>) -> String {
+    match m.get_region() {
+        Some(region) => format!("{}.{region}", m.get_name()),
+        _ => m.get_name().to_string(),
+    }
 }
 
 pub(crate) fn convert_to_url_str(
@@ -640,9 +657,6 @@ pub(crate) type NormalRepos = TinyVec<[String; 2]>;
 pub(crate) type MainRepos = TinyVec<[repo_map::MainRepo; 2]>;
 
 impl<'r> Repository<'r> {
-    pub(crate) const REG_URI: &'static str = "reg.tmoe.me:2096";
-    pub(crate) const GHCR_URI: &'static str = "ghcr.io";
-
     pub(crate) fn oci_platform(&self) -> &str {
         get_oci_platform(self.arch)
     }
@@ -668,6 +682,7 @@ impl<'r> Repository<'r> {
         )
     }
 
+    #[allow(mismatched_lifetime_syntaxes)]
     pub(crate) fn opt_tag_suffix(&self) -> Cow<str> {
         match self.tag {
             Some(t) => Cow::from(format!("-{t}")),
@@ -675,225 +690,378 @@ impl<'r> Repository<'r> {
         }
     }
 
-    pub(crate) fn opt_tag_prefix(&self) -> Cow<str> {
-        match self.tag {
-            Some(t) => Cow::from(format!("{t}-")),
-            _ => Cow::from(""),
+    /// SOURCE_DATE_EPOCH of the rootfs tarball.
+    ///
+    /// Priority: env(SOURCE_DATE_EPOCH) > date (old old debian) > title_date
+    ///
+    /// A negative or non-numeric SOURCE_DATE_EPOCH is ignored.
+    pub(crate) fn source_date_epoch(&self) -> Option<i64> {
+        if let Ok(s) = env::var("SOURCE_DATE_EPOCH") {
+            match s.trim().parse::<i64>() {
+                Ok(epoch) if epoch >= 0 => return Some(epoch),
+                _ => log::warn!(
+                    "Invalid SOURCE_DATE_EPOCH: {s:?}, expected a non-negative integer"
+                ),
+            }
         }
-    }
 
-    /// -> `[ghcr.io/xx/yy, ghcr.io/xx/zz]`
-    /// > xx/yy/zz from Self.
-    pub(crate) fn ghcr_repos(&self) -> NormalRepos {
-        let suffix = self.opt_tag_suffix();
-        let uri = Self::GHCR_URI;
-        let Self {
-            owner,
-            project,
-            series,
-            arch,
-            version,
-            ..
-        } = self;
+        let date = match self.date.trim() {
+            "1900-01-01" | "" => self.title_date?,
+            d => d,
+        };
+        parse_iso_date(date).map(|d| {
+            d.midnight()
+                .assume_utc()
+                .unix_timestamp()
+        })
+    }
 
-        [
-            format!(
-                // "{}-{}{}",
-                // ghcr.io/2cd/debian:potato-x86-base OR 2cd/debian:bo-x86
-                "{}/{}/{}:{}-{}{}",
-                uri, owner, project, series, arch, suffix
-            ),
-            format!(
-                // ghcr: "{}-{}{}",
-                // ghcr.io/2cd/debian:potato-x86-base OR ghcr.io/2cd/debian:bo-x86
-                "{}/{}/{}:{}-{}{}",
-                uri, owner, project, version, arch, suffix
-            ),
-        ]
-        .into()
+    /// e.g., debian-sid => debian
+    pub(crate) fn get_reg_date_tagged_owner(&self) -> &str {
+        let project = *self.get_project();
+        Project::from_name(project).map_or(project, |p| p.get_name())
     }
 
-    pub(crate) fn ghcr_date_tagged_repos(&self) -> NormalRepos {
-        let suffix = self.opt_tag_suffix();
-        let uri = Self::GHCR_URI;
+    /// Whether the rolling suite is date-tagged on the target.
+    fn is_date_tagged_on(&self, target: &Target) -> bool {
+        self.date_tagged && *target.get_date_tagged()
+    }
 
-        let Self {
-            owner,
-            project,
-            // series,
-            // version,
-            arch,
-            ..
-        } = self;
+    /// The image labels & the manifest annotations, e.g., title: Debian 2.2 (Potato) x86 base
+    ///
+    /// - created: RFC 3339, e.g., build-time.ron
+    /// - revision: `GITHUB_SHA`, if set
+    /// - rootfs.source: see [oci::ANNOTATION_ROOTFS_SOURCE]
+    pub(crate) fn oci_labels(&self, created: Option<&str>) -> Annotations {
+        let gh_repo = Project::from_name(self.project)
+            .map_or("debian-museum", |p| p.get_gh_repo());
+
+        let mut title = format!("{} {}", self.osname, self.version);
+        if !self
+            .codename
+            .eq_ignore_ascii_case(self.version)
+        {
+            title.push_str(&format!(" ({})", self.codename))
+        }
+        title.push_str(&format!(" {}", self.arch));
+        if let Some(tag) = self.tag {
+            title.push_str(&format!(" {tag}"))
+        }
 
-        [
-            format!(
-                // ghcr.io/2cd/debian-sid:x64 OR x64-base
-                "{uri}/{owner}/{project}:{arch}{suffix}",
+        let rootfs_source = self
+            .url
+            .as_ref()
+            .or(self
+                .debootstrap_src
+                .as_ref()
+                .map(|s| s.get_url()))
+            .map(|u| crate::url::official_url(u).to_string());
+        let revision = env::var("GITHUB_SHA")
+            .ok()
+            .filter(|s| !s.trim().is_empty());
+
+        let mut labels = Annotations::from([
+            (oci::ANNOTATION_VERSION.into(), self.version.into()),
+            (
+                oci::ANNOTATION_SOURCE.into(),
+                format!("https://github.com/{}/{gh_repo}", self.owner),
             ),
-            format!(
-                // ghcr.io/2cd/debian-sid:x64-2024-01-01
-                // OR: x64-base-2024-01-01
-                "{uri}/{owner}/{project}:{arch}{suffix}-{today}",
-                today = logger::today()
-            ),
-        ]
-        .into()
+            (oci::ANNOTATION_TITLE.into(), title),
+        ]);
+        labels.extend(
+            [
+                (oci::ANNOTATION_CREATED, created.map(Into::into)),
+                (oci::ANNOTATION_REVISION, revision),
+                (oci::ANNOTATION_ROOTFS_SOURCE, rootfs_source),
+            ]
+            .into_iter()
+            .filter_map(|(k, v)| Some((k.into(), v?))),
+        );
+        labels
     }
 
-    pub(crate) fn get_reg_date_tagged_owner(&self) -> &str {
-        match *self.get_project() {
-            "debian-sid" => "debian",
-            "ubuntu-dev" => "ubuntu",
-            p => p,
+    /// The values of the [crate::docker::tag_template] placeholders, e.g., {series} => potato
+    pub(crate) fn template_vars(&self) -> Vars<'_> {
+        Vars {
+            series: &self.series,
+            version: self.version,
+            codename: self.codename,
+            arch: self.arch,
+            deb_arch: self
+                .deb_arch
+                .unwrap_or_else(|| {
+                    archmap::debian_arch::map()
+                        .get(self.arch)
+                        .map_or("", |a| *a)
+                }),
+            tag: self.tag,
+            date: logger::today(),
+            project: self.project,
+            os: self.get_reg_date_tagged_owner(),
         }
     }
 
-    pub(crate) fn reg_date_tagged_repos(&self) -> NormalRepos {
-        let suffix = self.opt_tag_suffix();
-        let uri = Self::REG_URI;
-
-        let Self {
-            // owner,
-            // project,
-            series,
-            // version,
-            arch,
-            ..
-        } = self;
-
-        let owner = self.get_reg_date_tagged_owner();
-
-        [
-            format!(
-                // REG_URI/debian/sid:x64 OR x64-base
-                "{uri}/{owner}/{series}:{arch}{suffix}",
-            ),
-            format!(
-                // REG_URI/debian/sid:x64-2024-01-01
-                "{uri}/{owner}/{series}:{arch}{suffix}-{date}",
-                date = logger::today(),
-            ),
-        ]
-        .into()
+    /// The arch & main templates of the target. A date-tagged repo only has its first pair, if the target is not date-tagged.
+    fn target_templates<'t>(
+        &self,
+        target: &'t Target,
+    ) -> (Cow<'t, Templates>, usize) {
+        let templates = target.templates(self.date_tagged);
+        let n = match self.date_tagged && !self.is_date_tagged_on(target) {
+            true => 1,
+            _ => templates.get_arch().len(),
+        };
+        (templates, n)
     }
 
-    /// -> `[reg.tmoe.me/xx/yy, reg.tmoe.me/xx/zz]`
-    /// > xx/yy/zz from Self.
-    pub(crate) fn reg_repos(&self) -> NormalRepos {
-        let suffix = self.opt_tag_suffix();
-        let uri = Self::REG_URI;
-        let Self {
-            project,
-            series,
-            version,
-            arch,
-            ..
-        } = self;
-
-        [
-            format!(
-                // REG_URI/debian/potato:x86-base OR REG_URI/debian/bo-x86
-                "{}/{}/{}:{}{}",
-                uri, project, series, arch, suffix
-            ),
-            format!(
-                // REG_URI/debian/2.2:x86-base OR REG_URI/debian/1.3:x86
-                "{}/{}/{}:{}{}",
-                uri, project, version, arch, suffix
-            ),
-        ]
-        .into()
+    /// The arch tags of the target, e.g.,
+    ///
+    /// - Project: `[ghcr.io/2cd/debian:potato-x86-base, ghcr.io/2cd/debian:2.2-x86-base]`
+    /// - Series: `[reg.tmoe.me:2096/debian/potato:x86-base, reg.tmoe.me:2096/debian/2.2:x86-base]`
+    pub(crate) fn target_repos(
+        &self,
+        target: &Target,
+    ) -> anyhow::Result<NormalRepos> {
+        let (templates, n) = self.target_templates(target);
+        let vars = self.template_vars();
+        templates
+            .get_arch()
+            .iter()
+            .take(n)
+            .map(|t| target.render(t, &vars))
+            .collect()
     }
 
-    /// e.g. -> `[MainRepo::Reg(REG_URI/debian/bo:latest), MainRepo::Reg(REG_URI/debian/1.3:latest)]`
-    pub(crate) fn reg_main_repos(&self) -> MainRepos {
-        let uri = Self::REG_URI;
-        let tag = self.tag.unwrap_or("latest");
-        let Self {
-            project,
-            series,
-            version,
-            ..
-        } = self;
-
-        [
-            // REG_URI/debian/potato:base OR REG_URI/debian/bo:latest
-            format!("{}/{}/{}:{}", uri, project, series, tag),
-            // REG_URI/debian/2.2:base OR REG_URI/debian/1.3:latest
-            format!("{}/{}/{}:{}", uri, project, version, tag),
-        ]
-        .map(repo_map::MainRepo::Reg)
-        .into()
+    /// The manifest lists of the target, e.g.,
+    ///
+    /// - Project: `[ghcr.io/2cd/debian:potato-base, ghcr.io/2cd/debian:2.2-base]`
+    /// - Series: `[REG_URI/debian/bo:latest, REG_URI/debian/1.3:latest]`
+    pub(crate) fn target_main_repos(
+        &self,
+        target: &Target,
+    ) -> anyhow::Result<MainRepos> {
+        let (templates, n) = self.target_templates(target);
+        let vars = self.template_vars();
+        templates
+            .get_main()
+            .iter()
+            .take(n)
+            .map(|t| {
+                target
+                    .render(t, &vars)
+                    .map(|r| repo_map::MainRepo::new(target.get_name(), r))
+            })
+            .collect()
     }
+}
 
-    pub(crate) fn ghcr_main_repos(&self) -> MainRepos {
-        let suffix = self.opt_tag_suffix();
-        let uri = Self::GHCR_URI;
-        let Self {
-            owner,
-            project,
-            series,
-            version,
-            ..
-        } = self;
-
-        [
-            // ghcr.io/2cd/debian:potato-base OR ghcr.io/2cd/debian:bo
-            format!("{}/{}/{}:{}{}", uri, owner, project, series, suffix),
-            // ghcr.io/2cd/debian:2.2-base OR ghcr.io/2cd/debian:1.3
-            format!("{}/{}/{}:{}{}", uri, owner, project, version, suffix),
-        ]
-        .map(repo_map::MainRepo::Ghcr)
-        .into()
-    }
-    // date_tagged_repos
-
-    pub(crate) fn reg_main_date_tagged_repos(&self) -> MainRepos {
-        let uri = Self::REG_URI;
-        let tag = self.tag.unwrap_or("latest");
-        let series = self.get_series();
-        let prefix = self.opt_tag_prefix();
-
-        let owner = self.get_reg_date_tagged_owner();
-
-        // REG_URI/debian/sid:tag
-        // REG_URI/debian/sid:2024-01-01 OR base-2024-01-01
-        [
-            format!("{uri}/{owner}/{series}:{tag}"),
-            format!(
-                "{uri}/{owner}/{series}:{prefix}{date}",
-                date = logger::today()
-            ),
-        ]
-        .map(repo_map::MainRepo::Reg)
-        .into()
-    }
+/// "2024-06-10" => Date(2024-06-10)
+fn parse_iso_date(s: &str) -> Option<time::Date> {
+    let mut iter = s.trim().splitn(3, '-');
+    let mut next = || iter.next()?.parse::<u16>().ok();
 
-    pub(crate) fn ghcr_main_date_tagged_repos(&self) -> MainRepos {
-        let prefix = self.opt_tag_prefix();
+    let (year, month, day) = (next()?, next()?, next()?);
+    let month = time::Month::try_from(u8::try_from(month).ok()?).ok()?;
+    time::Date::from_calendar_date(year as _, month, day as _).ok()
+}
 
-        let tag = self.tag.unwrap_or("latest");
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn target_repos() -> anyhow::Result<()> {
+        let potato = Repository::builder()
+            .codename("Potato")
+            .series("potato")
+            .version("2.2")
+            .arch("x86")
+            .tag(Some("base"))
+            .build();
+        let (ghcr, reg) = (Target::ghcr(), Target::reg());
+
+        assert_eq!(
+            potato
+                .target_repos(&ghcr)?
+                .as_slice(),
+            [
+                "ghcr.io/2cd/debian:potato-x86-base",
+                "ghcr.io/2cd/debian:2.2-x86-base"
+            ]
+        );
+        assert_eq!(
+            potato.target_main_repos(&reg)?[0],
+            repo_map::MainRepo::new("reg", "reg.tmoe.me:2096/debian/potato:base")
+        );
+
+        let sid = Repository::builder()
+            .project("debian-sid")
+            .codename("sid")
+            .series("sid")
+            .version("sid")
+            .arch("x64")
+            .date_tagged(true)
+            .build();
+        let today = logger::today();
+        assert_eq!(
+            sid.target_repos(&reg)?
+                .as_slice(),
+            [
+                "reg.tmoe.me:2096/debian/sid:x64".to_owned(),
+                format!("reg.tmoe.me:2096/debian/sid:x64-{today}")
+            ]
+        );
+        assert_eq!(
+            sid.target_main_repos(&ghcr)?[1].get_repo(),
+            &format!("ghcr.io/2cd/debian-sid:{today}")
+        );
+
+        // No date tags on the target
+        let hub: Target = toml::from_str(
+            r#"name = "hub"
+uri = "docker.io"
+namespace = "museum""#,
+        )
+        .expect("Invalid target");
+        assert_eq!(
+            sid.target_repos(&hub)?
+                .as_slice(),
+            ["docker.io/museum/debian-sid:x64"]
+        );
+        assert_eq!(
+            sid.target_main_repos(&hub)?
+                .len(),
+            1
+        );
+
+        // A latest alias & a semver-like tag without code changes
+        let hub: Target = toml::from_str(
+            r#"name = "hub"
+uri = "docker.io"
+namespace = "museum"
+
+[templates]
+arch = ["{project}:{version}.0-{deb_arch}{-tag}", "{project}:{series}-{arch}{-tag}"]
+main = ["{project}:{version}.0{-tag}", "{project}:latest"]"#,
+        )?;
+        assert_eq!(
+            potato
+                .target_repos(&hub)?
+                .as_slice(),
+            [
+                "docker.io/museum/debian:2.2.0-i386-base",
+                "docker.io/museum/debian:potato-x86-base"
+            ]
+        );
+        assert_eq!(
+            potato.target_main_repos(&hub)?[1].get_repo(),
+            "docker.io/museum/debian:latest"
+        );
+
+        // {codename} is not lowercase
+        let bad: Target = toml::from_str(
+            r#"name = "bad"
+uri = "docker.io"
+[templates]
+arch = ["{codename}:{arch}"]
+main = ["{codename}:latest"]"#,
+        )?;
+        assert!(potato
+            .target_repos(&bad)
+            .is_err());
+        Ok(())
+    }
 
-        let uri = Self::GHCR_URI;
-        let Self {
-            owner,
-            project,
-            // series,
-            // version,
-            ..
-        } = self;
+    #[test]
+    fn debug_sources_follow_the_debian_mirrors() -> anyhow::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        SrcFormat::Complex {
+            enabled: vec![
+                "debian/ trixie".into(),
+                "debian-debug/ trixie-debug".into(),
+            ],
+            disabled: None,
+        }
+        .create_src_list("trixie", tmp.path(), None, None, None)?;
+
+        let mut files = fs::read_dir(tmp.path())?
+            .map(|e| {
+                Ok(e?
+                    .file_name()
+                    .into_string()
+                    .unwrap_or_default())
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        files.sort();
+        assert_eq!(
+            files,
+            [
+                "NJU.CN.list",
+                "NJU.CN.sources",
+                "Official.list",
+                "Official.sources",
+                "mirror.sources",
+                "sources.list"
+            ]
+        );
+
+        let official = fs::read_to_string(tmp.path().join("Official.list"))?;
+        assert!(official.contains("https://deb.debian.org/debian-debug/"));
+        let cn = fs::read_to_string(tmp.path().join("NJU.CN.list"))?;
+        assert!(cn.contains("https://mirrors.nju.edu.cn/debian/ trixie"));
+        assert!(cn.contains("https://mirrors.volces.com/debian-debug/"));
+        Ok(())
+    }
 
-        // ghcr.io/2cd/debian-sid:TAG, if TAG is empty => latest
-        // ghcr.io/2cd/debian-sid:2024-01-01 OR base-2024-01-01
-        [
-            format!("{uri}/{owner}/{project}:{tag}"),
-            format!(
-                "{uri}/{owner}/{project}:{prefix}{date}",
-                date = logger::today()
-            ),
-        ]
-        .map(repo_map::MainRepo::Ghcr)
-        .into()
+    #[test]
+    fn oci_labels() -> anyhow::Result<()> {
+        let url = Url::parse("https://snapshot.debian.org/archive/debian-archive/20240101T000000Z/debian/dists/potato/main/disks-i386/current/base2_2.tgz")?;
+        let potato = Repository::builder()
+            .codename("Potato")
+            .series("potato")
+            .version("2.2")
+            .arch("x86")
+            .tag(Some("base"))
+            .url(url.clone())
+            .build();
+
+        let labels = potato.oci_labels(Some("2024-06-10T08:00:00Z"));
+        assert_eq!(
+            labels[oci::ANNOTATION_TITLE],
+            "Debian 2.2 (Potato) x86 base"
+        );
+        assert_eq!(labels[oci::ANNOTATION_VERSION], "2.2");
+        assert_eq!(
+            labels[oci::ANNOTATION_SOURCE],
+            "https://github.com/2cd/debian-museum"
+        );
+        assert_eq!(labels[oci::ANNOTATION_ROOTFS_SOURCE], url.as_str());
+        assert!(!labels.contains_key("org.opencontainers.image.base.name"));
+        assert_eq!(labels[oci::ANNOTATION_CREATED], "2024-06-10T08:00:00Z");
+
+        // not the mirror picked on this host
+        let nju = Url::parse("https://mirrors.nju.edu.cn/debian-archive/debian/dists/potato/main/disks-i386/current/base2_2.tgz")?;
+        let labels = Repository {
+            url: Some(nju),
+            ..potato
+        }
+        .oci_labels(None);
+        assert_eq!(
+            labels[oci::ANNOTATION_ROOTFS_SOURCE],
+            "https://archive.debian.org/debian/dists/potato/main/disks-i386/current/base2_2.tgz"
+        );
+
+        let sid = Repository::builder()
+            .project("debian-sid")
+            .codename("sid")
+            .series("sid")
+            .version("sid")
+            .arch("x64")
+            .build();
+        let labels = sid.oci_labels(None);
+        assert_eq!(labels[oci::ANNOTATION_TITLE], "Debian sid x64");
+        assert!(!labels.contains_key(oci::ANNOTATION_CREATED));
+        assert!(!labels.contains_key(oci::ANNOTATION_ROOTFS_SOURCE));
+        Ok(())
     }
 }
@@ -1,26 +1,49 @@
+use crate::{cfg::registry, task::old_old_debian::deser_ron};
+use getset::Getters;
 use log::{debug, trace};
 use serde::{Deserialize, Serialize};
+use std::path::Path;
 use tinyvec::TinyVec;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
-pub(crate) enum MainRepo {
-    Reg(String),
-    Ghcr(String),
+/// The manifest list of a registry target, e.g., {target: reg, repo: reg.tmoe.me:2096/debian/potato:base}
+#[derive(
+    Getters, Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize,
+)]
+#[getset(get = "pub(crate) with_prefix")]
+pub(crate) struct MainRepo {
+    /// [crate::cfg::registry::Target] name, e.g., ghcr
+    target: String,
+    repo: String,
 }
 
 impl MainRepo {
-    /// Returns `true` if the main repo is [`Ghcr`].
-    ///
-    /// [`Ghcr`]: MainRepo::Ghcr
+    pub(crate) fn new<T: Into<String>, R: Into<String>>(target: T, repo: R) -> Self {
+        Self {
+            target: target.into(),
+            repo: repo.into(),
+        }
+    }
+
+    /// Returns `true` if the main repo belongs to the ghcr target.
     #[allow(unused)]
     pub(crate) fn is_ghcr(&self) -> bool {
-        matches!(self, Self::Ghcr(..))
+        self.target == registry::GHCR
     }
 }
 
-impl Default for MainRepo {
-    fn default() -> Self {
-        Self::Ghcr(Default::default())
+/// The key of the RepoMap files written before the registry targets, e.g., `Reg("reg.tmoe.me:2096/debian/potato:base")`
+#[derive(Debug, PartialEq, Eq, Hash, Deserialize)]
+enum LegacyMainRepo {
+    Reg(String),
+    Ghcr(String),
+}
+
+impl From<LegacyMainRepo> for MainRepo {
+    fn from(legacy: LegacyMainRepo) -> Self {
+        match legacy {
+            LegacyMainRepo::Reg(repo) => Self::new(registry::REG, repo),
+            LegacyMainRepo::Ghcr(repo) => Self::new(registry::GHCR, repo),
+        }
     }
 }
 
@@ -30,6 +53,28 @@ pub(crate) type Repos = TinyVec<[String; 16]>;
 pub(crate) struct RepoMap(ahash::HashMap<MainRepo, Repos>);
 
 impl RepoMap {
+    /// Loads a RepoMap (ron) file, including the legacy format (see [LegacyMainRepo]).
+    pub(crate) fn deser<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        match deser_ron::<Self, _>(path) {
+            Ok(map) => Ok(map),
+            Err(e) => {
+                match deser_ron::<ahash::HashMap<LegacyMainRepo, Repos>, _>(path) {
+                    Ok(legacy) => {
+                        debug!("legacy RepoMap: {path:?}");
+                        Ok(Self(
+                            legacy
+                                .into_iter()
+                                .map(|(k, v)| (k.into(), v))
+                                .collect(),
+                        ))
+                    }
+                    _ => Err(e.into()),
+                }
+            }
+        }
+    }
+
     /// Instead of resetting to a new value, this function pushes a new element to the value(&mut TinyVec) corresponding to the key.
     ///
     /// Note: If the corresponding key does not exist in the map, the Key and Value are created.
@@ -39,7 +84,7 @@ impl RepoMap {
     /// ```no_run
     /// let mut map = RepoMap::default();
     ///
-    /// let key = MainRepo::Ghcr("ghcr.io/xx/yy:latest".into());
+    /// let key = MainRepo::new("ghcr", "ghcr.io/xx/yy:latest");
     ///
     /// map.push_to_value(key.to_owned(), "ghcr.io/xx/yy:x64".into());
     /// map.push_to_value(key.to_owned(), "ghcr.io/xx/yy:rv64gc".into());
@@ -76,7 +121,7 @@ mod tests {
     fn tag_map() {
         let mut map = RepoMap::default();
 
-        let key = MainRepo::Ghcr("ghcr.io/xx/yy:latest".into());
+        let key = MainRepo::new("ghcr", "ghcr.io/xx/yy:latest");
 
         map.push_to_value(key.clone(), "ghcr.io/xx/yy:x64".into());
         map.push_to_value(key.clone(), "ghcr.io/xx/yy:rv64gc".into());
@@ -88,4 +133,25 @@ mod tests {
         assert_eq!(value[0], "ghcr.io/xx/yy:x64");
         assert_eq!(value[1], "ghcr.io/xx/yy:rv64gc");
     }
+
+    #[test]
+    fn legacy_repo_map() -> anyhow::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let file = tmp
+            .path()
+            .join("2.2-potato-base.ron");
+        std::fs::write(
+            &file,
+            r#"{Reg("reg.tmoe.me:2096/debian/potato:base"):["reg.tmoe.me:2096/debian/potato:x86-base"]}"#,
+        )?;
+
+        let map = RepoMap::deser(&file)?;
+        let key =
+            MainRepo::new(registry::REG, "reg.tmoe.me:2096/debian/potato:base");
+        assert_eq!(map[&key][0], "reg.tmoe.me:2096/debian/potato:x86-base");
+
+        std::fs::write(&file, ron::to_string(&map)?)?;
+        assert_eq!(RepoMap::deser(&file)?[&key].len(), 1);
+        Ok(())
+    }
 }
@@ -0,0 +1,294 @@
+//! Image reference templates of the registry targets, e.g., `{project}:{series}-{arch}{-tag}` => debian:potato-x86-base
+//!
+//! - `{name}`: series, version, codename, arch, deb_arch, tag, date (today), project (e.g., debian-sid), os (e.g., debian)
+//! - `{-name}`, `{name-}`: the separator (`-`, `.` or `_`) is only added if the value is not empty, e.g., `{arch}{-tag}` => x64 OR x64-base
+//! - `{name|fallback}`: e.g., `{tag|latest}`
+
+use anyhow::{bail, ensure};
+use regex::Regex;
+use std::{fmt, str::FromStr, sync::OnceLock};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Var {
+    Series,
+    Version,
+    Codename,
+    Arch,
+    DebArch,
+    Tag,
+    Date,
+    Project,
+    Os,
+}
+
+const VARS: [(&str, Var); 9] = [
+    ("series", Var::Series),
+    ("version", Var::Version),
+    ("codename", Var::Codename),
+    ("arch", Var::Arch),
+    ("deb_arch", Var::DebArch),
+    ("tag", Var::Tag),
+    ("date", Var::Date),
+    ("project", Var::Project),
+    ("os", Var::Os),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Lit(String),
+    Var {
+        var: Var,
+        before: String,
+        after: String,
+        fallback: Option<String>,
+    },
+}
+
+/// The values of the placeholders, see [crate::docker::repo::Repository::template_vars]
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Vars<'a> {
+    pub(crate) series: &'a str,
+    pub(crate) version: &'a str,
+    pub(crate) codename: &'a str,
+    pub(crate) arch: &'a str,
+    pub(crate) deb_arch: &'a str,
+    pub(crate) tag: Option<&'a str>,
+    pub(crate) date: &'a str,
+    pub(crate) project: &'a str,
+    pub(crate) os: &'a str,
+}
+
+impl Vars<'_> {
+    fn get(&self, var: Var) -> &str {
+        use Var::*;
+        match var {
+            Series => self.series,
+            Version => self.version,
+            Codename => self.codename,
+            Arch => self.arch,
+            DebArch => self.deb_arch,
+            Tag => self.tag.unwrap_or_default(),
+            Date => self.date,
+            Project => self.project,
+            Os => self.os,
+        }
+    }
+
+    /// Used to check the templates, e.g., debian/sid:x64-base
+    pub(crate) fn sample(tag: Option<&'static str>) -> Vars<'static> {
+        Vars {
+            series: "sid",
+            version: "13",
+            codename: "trixie",
+            arch: "x64",
+            deb_arch: "amd64",
+            tag,
+            date: "2024-01-01",
+            project: "debian-sid",
+            os: "debian",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub(crate) struct Template {
+    src: String,
+    tokens: Vec<Token>,
+}
+
+impl Template {
+    pub(crate) fn render(&self, vars: &Vars) -> String {
+        let mut out = String::with_capacity(self.src.len() + 16);
+        for t in &self.tokens {
+            match t {
+                Token::Lit(s) => out.push_str(s),
+                Token::Var {
+                    var,
+                    before,
+                    after,
+                    fallback,
+                } => match (vars.get(*var), fallback) {
+                    ("", Some(f)) => out.push_str(f),
+                    ("", _) => {}
+                    (v, _) => {
+                        out.push_str(before);
+                        out.push_str(v);
+                        out.push_str(after);
+                    }
+                },
+            }
+        }
+        out
+    }
+
+    /// Renders the template with [Vars::sample] (with & without the tag), then checks the [validate_reference].
+    pub(crate) fn check(&self) -> anyhow::Result<()> {
+        for tag in [Some("base"), None] {
+            let r = self.render(&Vars::sample(tag));
+            validate_reference(&r).map_err(|e| {
+                anyhow::anyhow!("Invalid template {:?} (e.g., {r:?}): {e}", self.src)
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Template {
+    type Err = anyhow::Error;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        const SEP: &[char] = &['-', '.', '_'];
+        let mut tokens = Vec::with_capacity(8);
+        let mut rest = src;
+
+        while let Some(start) = rest.find(['{', '}']) {
+            if rest[start..].starts_with('}') {
+                bail!("Unmatched '}}' in the template: {src:?}")
+            }
+            if start > 0 {
+                tokens.push(Token::Lit(rest[..start].into()))
+            }
+            let Some(len) = rest[start..].find('}') else {
+                bail!("Unclosed '{{' in the template: {src:?}")
+            };
+            let inner = &rest[start + 1..start + len];
+            rest = &rest[start + len + 1..];
+
+            let (main, fallback) = match inner.split_once('|') {
+                Some((m, f)) => (m, Some(f.to_owned())),
+                _ => (inner, None),
+            };
+            let name = main.trim_matches(SEP);
+            let name_start = main.len()
+                - main
+                    .trim_start_matches(SEP)
+                    .len();
+            let name_end = main.trim_end_matches(SEP).len();
+            let (before, after) = (&main[..name_start], &main[name_end..]);
+
+            let Some(var) = VARS
+                .iter()
+                .find_map(|(k, v)| (*k == name).then_some(*v))
+            else {
+                bail!(
+                    "Unknown placeholder {{{inner}}} in the template: {src:?}, valid: {}",
+                    VARS.map(|(k, _)| k).join(", ")
+                )
+            };
+            tokens.push(Token::Var {
+                var,
+                before: before.into(),
+                after: after.into(),
+                fallback,
+            })
+        }
+        if !rest.is_empty() {
+            tokens.push(Token::Lit(rest.into()))
+        }
+
+        Ok(Self {
+            src: src.into(),
+            tokens,
+        })
+    }
+}
+
+impl TryFrom<String> for Template {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        let t = s.parse::<Self>()?;
+        t.check()?;
+        Ok(t)
+    }
+}
+
+impl From<Template> for String {
+    fn from(t: Template) -> Self {
+        t.src
+    }
+}
+
+impl fmt::Display for Template {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.src)
+    }
+}
+
+/// Checks `name:tag` (without the registry host) against the [OCI distribution spec](https://github.com/opencontainers/distribution-spec/blob/main/spec.md#pulling-manifests), e.g., 2cd/debian:potato-x86-base
+pub(crate) fn validate_reference(reference: &str) -> anyhow::Result<()> {
+    static NAME: OnceLock<Regex> = OnceLock::new();
+    static TAG: OnceLock<Regex> = OnceLock::new();
+    let name_re = NAME.get_or_init(|| {
+        Regex::new(r"^[a-z0-9]+((\.|_|__|-+)[a-z0-9]+)*(/[a-z0-9]+((\.|_|__|-+)[a-z0-9]+)*)*$")
+            .expect("Invalid regex")
+    });
+    let tag_re = TAG.get_or_init(|| {
+        Regex::new(r"^[a-zA-Z0-9_][a-zA-Z0-9._-]{0,127}$").expect("Invalid regex")
+    });
+
+    let Some((name, tag)) = reference.rsplit_once(':') else {
+        bail!("No tag, e.g., {reference}:latest")
+    };
+    ensure!(
+        name_re.is_match(name),
+        "Invalid repository name: {name:?}, expected: lowercase [a-z0-9] components separated by '/', '.', '_' or '-'"
+    );
+    ensure!(
+        tag_re.is_match(tag),
+        "Invalid tag: {tag:?}, expected: [a-zA-Z0-9_][a-zA-Z0-9._-]{{0,127}}"
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_templates() -> anyhow::Result<()> {
+        let t = "{project}:{series}-{arch}{-tag}".parse::<Template>()?;
+        let mut vars = Vars::sample(Some("base"));
+        assert_eq!(t.render(&vars), "debian-sid:sid-x64-base");
+
+        vars.tag = None;
+        assert_eq!(t.render(&vars), "debian-sid:sid-x64");
+
+        let t = "{os}/{series}:{tag|latest}".parse::<Template>()?;
+        assert_eq!(t.render(&vars), "debian/sid:latest");
+        let t = "{os}:{tag-}{date}".parse::<Template>()?;
+        assert_eq!(t.render(&vars), "debian:2024-01-01");
+        assert!(t.check().is_ok());
+
+        let err = "{project}:{suite}"
+            .parse::<Template>()
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("valid: series,"),
+            "{err}"
+        );
+        assert!("{project:{arch}"
+            .parse::<Template>()
+            .is_err());
+        assert!("{project}}"
+            .parse::<Template>()
+            .is_err());
+
+        // Uppercase repository name & no tag
+        assert!(Template::try_from("{codename}/{os}:x".to_owned()).is_ok());
+        assert!(Template::try_from("Debian:{arch}".to_owned()).is_err());
+        assert!(Template::try_from("{project}".to_owned()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn oci_reference_grammar() {
+        assert!(validate_reference("2cd/debian:potato-x86-base").is_ok());
+        assert!(validate_reference("debian/1.3:x86").is_ok());
+        assert!(validate_reference("debian:-x86").is_err());
+        assert!(validate_reference("debian//sid:x64").is_err());
+        assert!(validate_reference(&format!("debian:{}", "a".repeat(129))).is_err());
+    }
+}
@@ -0,0 +1,461 @@
+//! A blocking HTTP(S) client with retries, timeouts & resumable downloads.
+//!
+//! Proxies are read from the env, e.g., `ALL_PROXY`, `HTTPS_PROXY`, `http_proxy`.
+
+use crate::cli::PKG_VERSION;
+use anyhow::{bail, Context};
+use log::{debug, info, warn};
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    thread,
+    time::Duration,
+};
+use url::Url;
+
+/// `ureq::Error` is large (> 256 bytes).
+pub(crate) type Error = Box<ureq::Error>;
+
+/// Errors of [Client::download_part]
+#[derive(Debug)]
+enum DownloadError {
+    /// Request & response (including reading the body), may be retried.
+    Http(Error),
+    /// Writing the part file (e.g., disk full), never retried.
+    Local(io::Error),
+}
+
+impl From<Error> for DownloadError {
+    fn from(e: Error) -> Self {
+        Self::Http(e)
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Client {
+    agent: ureq::Agent,
+    retries: u32,
+    retry_delay: Duration,
+}
+
+/// The global client (connect timeout: 30s, read timeout: 60s, retries: 3, retry delay: 2s * n).
+pub(crate) fn client() -> &'static Client {
+    static C: OnceLock<Client> = OnceLock::new();
+    C.get_or_init(|| {
+        Client::new(Duration::from_secs(30), Duration::from_secs(60), 3)
+            .with_retry_delay(Duration::from_secs(2))
+    })
+}
+
+impl Client {
+    pub(crate) fn new(
+        connect_timeout: Duration,
+        read_timeout: Duration,
+        retries: u32,
+    ) -> Self {
+        let agent = ureq::AgentBuilder::new()
+            .timeout_connect(connect_timeout)
+            .timeout_read(read_timeout)
+            .redirects(8)
+            .user_agent(&format!("get-ctr/{PKG_VERSION}"))
+            .try_proxy_from_env(true)
+            .build();
+
+        Self {
+            agent,
+            retries,
+            retry_delay: Duration::from_secs(1),
+        }
+    }
+
+    pub(crate) fn agent(&self) -> &ureq::Agent {
+        &self.agent
+    }
+
+    pub(crate) fn with_retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+        self
+    }
+
+    /// Retries on transport errors (e.g., timeout, reset), 429 & 5xx.
+    fn call(
+        &self,
+        req: impl Fn() -> ureq::Request,
+    ) -> Result<ureq::Response, Error> {
+        let mut attempt = 0;
+        loop {
+            let r = req();
+            let url = r.url().to_owned();
+
+            match r.call().map_err(Box::new) {
+                Err(e) if attempt < self.retries && is_transient(&e) => {
+                    attempt += 1;
+                    warn!("{e}, retrying ({attempt}/{}): {url}", self.retries);
+                    thread::sleep(self.retry_delay * attempt);
+                }
+                x => return x,
+            }
+        }
+    }
+
+    /// Returns the status code (after redirects) of a HEAD request.
+    pub(crate) fn status(&self, url: &str) -> anyhow::Result<u16> {
+        match self.call(|| self.agent.head(url)) {
+            Ok(r) => Ok(r.status()),
+            Err(e) => match *e {
+                ureq::Error::Status(code, _) => Ok(code),
+                e => Err(e).with_context(|| format!("HEAD {url}")),
+            },
+        }
+    }
+
+    /// true if the status code is 200.
+    pub(crate) fn is_ok(&self, url: &str) -> bool {
+        match self.status(url) {
+            Ok(code) => code == 200,
+            Err(e) => {
+                debug!("{e:#}");
+                false
+            }
+        }
+    }
+
+    /// Runs `get` on `url`, then on the same resource of the next ranked mirrors while it fails, see [crate::url::fallback_urls].
+    fn with_fallback<T>(
+        url: &str,
+        get: impl Fn(&str) -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        let mut result = get(url);
+        if let (Err(_), Ok(u)) = (&result, Url::parse(url)) {
+            for next in crate::url::fallback_urls(&u) {
+                let Err(e) = &result else { break };
+                warn!("{e:#}, falling back to {next}");
+                result = get(next.as_str());
+            }
+        }
+        result
+    }
+
+    /// GET the body as a string. Returns an error if the status code is not 2xx.
+    pub(crate) fn get_string(&self, url: &str) -> anyhow::Result<String> {
+        Self::with_fallback(url, |url| {
+            let resp = self
+                .call(|| self.agent.get(url))
+                .with_context(|| format!("GET {url}"))?;
+            Ok(resp.into_string()?)
+        })
+    }
+
+    /// Same as [Self::get_string], but returns None if the status code is 404.
+    pub(crate) fn get_string_if_found(
+        &self,
+        url: &str,
+    ) -> anyhow::Result<Option<String>> {
+        Self::with_fallback(url, |url| match self.call(|| self.agent.get(url)) {
+            Err(e) if matches!(*e, ureq::Error::Status(404, _)) => Ok(None),
+            r => {
+                let resp = r.with_context(|| format!("GET {url}"))?;
+                Ok(Some(resp.into_string()?))
+            }
+        })
+    }
+
+    /// Downloads `url` to `dst`.
+    ///
+    /// The data is written to `dst.part` first; if it already exists (e.g., interrupted by a timeout), the download is resumed with a `Range` request.
+    ///
+    /// If the download fails, it falls back to the same file on the next ranked mirrors, see [crate::url::fallback_urls].
+    pub(crate) fn download(&self, url: &Url, dst: &Path) -> anyhow::Result<()> {
+        info!("Downloading {url} to {dst:?}");
+        let part = part_path(dst);
+
+        let mut result = self.download_resumable(url, &part);
+        let mut last = url.clone();
+        for next in crate::url::fallback_urls(url) {
+            let Err(DownloadError::Http(e)) = &result else {
+                break;
+            };
+            warn!("Failed to download {last}: {e}, falling back to {next}");
+            // Do not resume from the data of another mirror.
+            match fs::remove_file(&part) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                    result = Err(DownloadError::Local(e));
+                    break;
+                }
+                _ => result = self.download_resumable(&next, &part),
+            }
+            last = next;
+        }
+
+        match result {
+            Ok(()) => fs::rename(&part, dst)?,
+            Err(DownloadError::Http(e)) => bail!("Failed to download {last}: {e}"),
+            Err(DownloadError::Local(e)) => bail!("Failed to write {part:?}: {e}"),
+        }
+        Ok(())
+    }
+
+    /// Resumes the download on transient errors (up to `retries` times).
+    fn download_resumable(
+        &self,
+        url: &Url,
+        part: &Path,
+    ) -> Result<(), DownloadError> {
+        let mut attempt = 0;
+        loop {
+            match self.download_part(url, part) {
+                Err(DownloadError::Http(e))
+                    if attempt < self.retries && is_transient(&e) =>
+                {
+                    attempt += 1;
+                    warn!("{e}, resuming ({attempt}/{}): {url}", self.retries);
+                    thread::sleep(self.retry_delay * attempt);
+                }
+                r => return r,
+            }
+        }
+    }
+
+    fn download_part(&self, url: &Url, part: &Path) -> Result<(), DownloadError> {
+        let offset = part
+            .metadata()
+            .map_or(0, |m| m.len());
+
+        let req = self
+            .agent
+            .request_url("GET", url);
+        let resp = match offset {
+            0 => req.call(),
+            n => req
+                .set("Range", &format!("bytes={n}-"))
+                .call(),
+        };
+        let resp = match resp {
+            Err(ureq::Error::Status(416, resp)) if offset > 0 => {
+                match self.remote_size(url, &resp) {
+                    // The part file is already complete.
+                    Some(n) if n == offset => return Ok(()),
+                    n => {
+                        warn!(
+                            "{part:?} has {offset} bytes, but the remote size is {n:?}, restarting"
+                        );
+                        fs::remove_file(part).map_err(DownloadError::Local)?;
+                        return self.download_part(url, part);
+                    }
+                }
+            }
+            r => r.map_err(Box::new)?,
+        };
+
+        let append = resp.status() == 206;
+        if offset > 0 {
+            match append {
+                true => debug!("resuming from {offset} bytes"),
+                _ => debug!("The server does not support range requests"),
+            }
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(part)
+            .map_err(DownloadError::Local)?;
+
+        let mut writer = BufWriter::new(file);
+        let mut reader = resp.into_reader();
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            let n = match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(io_err(e).into()),
+            };
+            writer
+                .write_all(&buf[..n])
+                .map_err(DownloadError::Local)?;
+        }
+        writer
+            .flush()
+            .map_err(DownloadError::Local)
+    }
+
+    /// The total size from the `Content-Range: bytes */N` of a 416 response, or the `Content-Length` of a HEAD request.
+    fn remote_size(&self, url: &Url, resp: &ureq::Response) -> Option<u64> {
+        let parse = |s: &str| s.trim().parse::<u64>().ok();
+
+        if let Some(n) = resp
+            .header("Content-Range")
+            .and_then(|x| x.rsplit_once('/'))
+            .and_then(|(_, n)| parse(n))
+        {
+            return Some(n);
+        }
+        self.agent
+            .request_url("HEAD", url)
+            .call()
+            .ok()?
+            .header("Content-Length")
+            .and_then(parse)
+    }
+}
+
+fn part_path(dst: &Path) -> PathBuf {
+    let mut s = dst.as_os_str().to_owned();
+    s.push(".part");
+    PathBuf::from(s)
+}
+
+pub(crate) fn io_err(e: io::Error) -> Error {
+    Box::new(e.into())
+}
+
+fn is_transient(e: &ureq::Error) -> bool {
+    match e {
+        ureq::Error::Status(code, _) => *code == 429 || *code >= 500,
+        ureq::Error::Transport(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{response, serve};
+
+    fn test_client() -> Client {
+        Client::new(Duration::from_secs(5), Duration::from_secs(5), 2)
+            .with_retry_delay(Duration::ZERO)
+    }
+
+    #[test]
+    fn status_and_retry() -> anyhow::Result<()> {
+        let addr = serve(|req| match req.path() {
+            "/404" => response("404 Not Found", "", b""),
+            _ => response("200 OK", "", b""),
+        });
+        let url = format!("http://{addr}/");
+        let c = test_client();
+        assert!(c.is_ok(&url));
+        assert_eq!(c.status(&format!("{url}404"))?, 404);
+        assert_eq!(c.get_string_if_found(&format!("{url}404"))?, None);
+
+        // 503 => retry => 200
+        let addr = serve({
+            let n = std::sync::atomic::AtomicUsize::new(0);
+            move |_| match n.fetch_add(1, std::sync::atomic::Ordering::Relaxed) {
+                0 => response("503 Service Unavailable", "", b""),
+                _ => response("200 OK", "", b"noble"),
+            }
+        });
+        assert_eq!(c.get_string(&format!("http://{addr}/"))?, "noble");
+        Ok(())
+    }
+
+    #[test]
+    fn resume_download() -> anyhow::Result<()> {
+        const BODY: &[u8] = b"0123456789";
+        let addr = serve(|req| {
+            let range = req
+                .header("Range")
+                .and_then(|r| r.strip_prefix("bytes="))
+                .and_then(|r| {
+                    r.trim_end_matches('-')
+                        .parse::<usize>()
+                        .ok()
+                });
+            match range {
+                Some(n) => response(
+                    "206 Partial Content",
+                    &format!("Content-Range: bytes {n}-9/10\r\n"),
+                    &BODY[n..],
+                ),
+                _ => response("200 OK", "", BODY),
+            }
+        });
+
+        let tmp = tempfile::tempdir()?;
+        let dst = tmp.path().join("base2_0.tgz");
+        fs::write(part_path(&dst), &BODY[..4])?;
+
+        test_client().download(&Url::parse(&format!("http://{addr}/"))?, &dst)?;
+
+        let mut content = Vec::new();
+        fs::File::open(&dst)?.read_to_end(&mut content)?;
+        assert_eq!(content, BODY);
+        assert!(!part_path(&dst).exists());
+        Ok(())
+    }
+
+    #[test]
+    fn falls_back_to_the_next_mirror() -> anyhow::Result<()> {
+        let broken = serve(|_| response("500 Internal Server Error", "", b""));
+        let ok = serve(|req| match req.path() {
+            "/debian/dists/sid/Release" => response("200 OK", "", b"sid"),
+            _ => response("404 Not Found", "", b""),
+        });
+        crate::url::remember_ranking(
+            [&broken, &ok]
+                .iter()
+                .map(|addr| Url::parse(&format!("http://{addr}/debian/")))
+                .collect::<Result<_, _>>()?,
+        );
+
+        let url = Url::parse(&format!("http://{broken}/debian/dists/sid/Release"))?;
+        let tmp = tempfile::tempdir()?;
+        let dst = tmp.path().join("Release");
+        test_client().download(&url, &dst)?;
+        assert_eq!(fs::read(&dst)?, b"sid");
+        assert_eq!(test_client().get_string(url.as_str())?, "sid");
+        Ok(())
+    }
+
+    #[test]
+    fn range_not_satisfiable() -> anyhow::Result<()> {
+        const BODY: &[u8] = b"0123456789";
+        let addr = serve(|req| match req.header("Range") {
+            Some(_) => response(
+                "416 Range Not Satisfiable",
+                "Content-Range: bytes */10\r\n",
+                b"",
+            ),
+            _ => response("200 OK", "", BODY),
+        });
+        let url = Url::parse(&format!("http://{addr}/"))?;
+        let tmp = tempfile::tempdir()?;
+        let dst = tmp.path().join("base2_0.tgz");
+
+        // complete => no download
+        fs::write(part_path(&dst), BODY)?;
+        test_client().download(&url, &dst)?;
+        assert_eq!(fs::read(&dst)?, BODY);
+
+        // larger than the remote file => restart from zero
+        fs::write(part_path(&dst), b"0123456789abc")?;
+        test_client().download(&url, &dst)?;
+        assert_eq!(fs::read(&dst)?, BODY);
+        Ok(())
+    }
+
+    #[test]
+    fn local_io_error_is_not_retried() -> anyhow::Result<()> {
+        let addr = serve(|_| response("200 OK", "", b"potato"));
+        let tmp = tempfile::tempdir()?;
+        // The part file is a directory => cannot be opened for writing.
+        let dst = tmp.path().join("base2_2.tgz");
+        fs::create_dir(part_path(&dst))?;
+
+        let err = test_client()
+            .download(&Url::parse(&format!("http://{addr}/"))?, &dst)
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Failed to write"),
+            "{err}"
+        );
+        Ok(())
+    }
+}
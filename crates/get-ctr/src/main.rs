@@ -5,8 +5,11 @@ mod cli;
 mod command;
 mod dir;
 mod docker;
+mod http;
 mod logger;
 mod task;
+#[cfg(test)]
+mod test_util;
 mod url;
 
 fn main() -> anyhow::Result<()> {
@@ -35,22 +38,26 @@ mod tests {
             ("old", "old_old_debian/disk.v1.toml"),
             ("deb", "debootstrap/debian.toml"),
             ("uuu", "debootstrap/ubuntu.toml"),
+            ("devuan", "debootstrap/devuan.toml"),
+            ("kali", "debootstrap/kali.toml"),
+            ("raspbian", "debootstrap/raspbian.toml"),
+            ("trisquel", "debootstrap/trisquel.toml"),
         ] {
             let new_file = Path::new(file).with_extension("ron");
 
             let ron_str = match name {
-                "deb" | "uuu" => {
-                    let value = toml::from_str::<debootstrap::Cfg>(
-                        &fs::read_to_string(file)?,
-                    )?;
-                    ron::to_string(&value)
-                }
-                _ => {
+                "old" => {
                     let value =
                         toml::from_str::<DiskV1>(&fs::read_to_string(file)?)?;
 
                     ron::to_string(&value)
                 }
+                _ => {
+                    let value = toml::from_str::<debootstrap::Cfg>(
+                        &fs::read_to_string(file)?,
+                    )?;
+                    ron::to_string(&value)
+                }
             }?;
 
             fs::write(new_file, ron_str)?;
@@ -13,16 +13,21 @@ use url::Url;
 pub(crate) const DEB_ENV: &str = "DEBIAN_FRONTEND=noninteractive";
 
 use crate::{
-    cfg::debootstrap,
+    cfg::{
+        debootstrap,
+        project::Project,
+        registry::{self, Target},
+    },
     cli::Cli,
     command::{
         create_dir_all_as_root, force_remove_item_as_root, move_item_as_root, run,
         run_and_get_stdout, run_as_root, run_nspawn,
     },
     docker::repo::Repository,
+    http,
     task::{
-        compression::{extract_tar_as_root, pack_tar_as_root},
-        old_old_debian::{TarFile, BUILD_TIME_RON},
+        compression::extract_tar_as_root,
+        old_old_debian::{pack_rootfs, TarFile, BUILD_TIME_RON},
     },
 };
 
@@ -127,7 +132,7 @@ pub(crate) fn obtain<'a, I: IntoIterator<Item = &'a Repository<'a>>>(
                     if comp_mode {
                         get_rootfs_from_old_docker_image(
                             docker_dir,
-                            repo.ghcr_repos()
+                            repo.target_repos(&Target::ghcr())?
                                 .first()
                                 .expect("Empty GHCR REPO"),
                             &rootfs_dir,
@@ -155,7 +160,7 @@ pub(crate) fn obtain<'a, I: IntoIterator<Item = &'a Repository<'a>>>(
 
         patch_deb_rootfs(&rootfs_dir, repo);
 
-        pack_tar_as_root(&rootfs_dir, tar_path, true);
+        pack_rootfs(repo, &rootfs_dir, true)?;
     }
     Ok(())
 }
@@ -193,7 +198,7 @@ fn get_old_rootfs(
     series: &str,
 ) -> Result<(), anyhow::Error> {
     get_rootfs_from_docker(
-        &format!("{uri}/rootfs/{series}:{arch}", uri = Repository::REG_URI),
+        &format!("{uri}/rootfs/{series}:{arch}", uri = registry::REG_URI),
         docker_dir,
     );
     let base_tar = docker_dir.join("base.tar");
@@ -393,9 +398,6 @@ fn run_debootstrap(
     let ex_pkgs_comma_str = ex_packages_arr.join(",");
 
     let deb_src_url = deb_src.get_url();
-    if deb_src_url.scheme() == "https" {
-        args.push(osstr("--no-check-gpg"));
-    }
 
     args.extend(
         [
@@ -440,29 +442,48 @@ fn run_debootstrap(
         }
     }
 
-    fix_script_link(real_name, os_name)?;
+    fix_script_link(real_name, repo.get_project())?;
 
     args.push(osstr(real_name));
 
     args.push(rootfs_dir.as_ref());
-    args.push(osstr(deb_src_url.as_str()));
-
-    run_as_root("/usr/sbin/debootstrap", &args, true);
 
     let log_file = rootfs_dir.join("debootstrap/debootstrap.log");
 
-    if log_file.exists() {
-        log::debug!(
-            "log_file: {}, log_path: {log_file:?}",
-            fs::read_to_string(&log_file).unwrap_or_default()
-        );
-        panic!(
-            "Failed to build: {} (dir: {rootfs_dir:?}) with debootstrap",
-            repo.get_series()
-        )
-    };
+    // The selected mirror, then the next ranked ones (--mirror-select probe)
+    let mut urls = std::iter::once(deb_src_url.clone())
+        .chain(crate::url::fallback_urls(deb_src_url))
+        .peekable();
+    while let Some(url) = urls.next() {
+        let mut cmd_args = TinyVec::<[&OsStr; 16]>::new();
+        if url.scheme() == "https" {
+            cmd_args.push(osstr("--no-check-gpg"));
+        }
+        cmd_args.extend(args.iter().copied());
+        cmd_args.push(osstr(url.as_str()));
 
-    Ok(())
+        // Exits on failure, unless another mirror is left.
+        let status =
+            run_as_root("/usr/sbin/debootstrap", &cmd_args, urls.peek().is_none());
+
+        if status.success() && !log_file.exists() {
+            return Ok(());
+        }
+        if log_file.exists() {
+            log::debug!(
+                "log_file: {}, log_path: {log_file:?}",
+                fs::read_to_string(&log_file).unwrap_or_default()
+            );
+        }
+        if let Some(next) = urls.peek() {
+            log::warn!("debootstrap failed with {url}, falling back to {next}");
+            force_remove_item_as_root(rootfs_dir);
+        }
+    }
+    panic!(
+        "Failed to build: {} (dir: {rootfs_dir:?}) with debootstrap",
+        repo.get_series()
+    )
 }
 
 /// Release File Sample:
@@ -491,7 +512,8 @@ fn get_the_real_name_of_ubuntu_devel(url: &Url) -> &'static str {
             .join("dists/devel/main/source/Release")
             .expect("Failed to join release url");
 
-        let out = run_and_get_stdout("curl", &["-L", release_file_url.as_str()])
+        let out = http::client()
+            .get_string(release_file_url.as_str())
             .expect("Failed to get the real name of ubuntu devel suite");
 
         serde_yaml::from_str::<ReleaseUUU>(&out)
@@ -501,18 +523,15 @@ fn get_the_real_name_of_ubuntu_devel(url: &Url) -> &'static str {
 }
 
 /// If the script file does not exist in either "/usr/share/debootstrap/scripts/" or 'env::var_os("DEBOOTSTRAP_DIR")/scripts' then the corresponding symbolic link will be created.
-fn fix_script_link(suite: &str, os_name: &str) -> Result<(), io::Error> {
+fn fix_script_link(suite: &str, project: &str) -> Result<(), io::Error> {
     let env_script_exists =
         get_debootstrap_script_dir_env().is_some_and(|x| x.join(suite).exists());
 
     let script = Path::new(debootstrap::SCRIPT_DIR).join(suite);
 
     if !env_script_exists && !script.exists() {
-        let src = match os_name {
-            "Ubuntu" => "gutsy",
-            // &"Debian" => "sid",
-            _ => "sid",
-        };
+        // e.g., ubuntu => gutsy, devuan => ceres
+        let src = Project::from_name(project).map_or("sid", |p| p.get_script());
         log::info!("Creating the symlink:\t src: {src}, dst: {suite}");
         std::os::unix::fs::symlink(src, suite)?;
         move_item_as_root(suite, script);
@@ -1,15 +1,27 @@
 use crate::{
-    command::{force_remove_item_as_root, run, run_as_root},
+    cli::Cli,
+    command::{force_remove_item_as_root, is_root, run_as_root},
+    docker::repo::Repository,
     task::pool,
 };
+use byteunit::ByteUnit;
+use hash_digest::multi::{Algorithm, Digests, HashWriter};
 use log::{debug, info};
-use repack::compression::{Operation, Upack};
+use repack::{
+    archive::{Exclude, Packer},
+    compression::{Operation, Upack, ZstdParams},
+    dict::ZstdDict,
+    io_buffer::{buf_reader, buf_writer},
+    progress::ProgressCallback,
+};
 use std::{
-    ffi::OsStr,
+    ffi::{OsStr, OsString},
     fs, io,
+    os::unix::ffi::OsStrExt,
     path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
 };
-use tinyvec::TinyVec;
 
 pub(crate) fn decompress_gzip(gz_fname: &str, tar_path: &Path) -> io::Result<()> {
     let unpack_gz = Upack::new(gz_fname, tar_path);
@@ -22,26 +34,98 @@ pub(crate) fn decompress_gzip(gz_fname: &str, tar_path: &Path) -> io::Result<()>
     Ok(())
 }
 
-pub(crate) fn spawn_zstd_thread(
+/// blake3, sha256 & sha512 (same as the `SHA512` field of Debian `Release`)
+pub(crate) const ARCHIVE_DIGESTS: [Algorithm; 3] =
+    [Algorithm::Blake3, Algorithm::Sha256, Algorithm::Sha512];
+
+/// The codec (zstd/xz/lzip) is determined by the extension of `target`.
+///
+/// `zstd` & `dict` are ignored by xz & lzip.
+///
+/// `on_success` runs in the same job, after `target` has been fully written, with its [ARCHIVE_DIGESTS].
+pub(crate) fn spawn_compression_thread(
     tar_path: PathBuf,
-    zstd_target: PathBuf,
-    zstd_lv: Option<&u8>,
+    target: PathBuf,
+    lv: u8,
+    zstd: ZstdParams,
+    dict: Option<Arc<ZstdDict>>,
+    on_success: impl FnOnce(Digests) -> anyhow::Result<()> + Send + 'static,
 ) {
     let pool = pool::global_pool();
-    let lv = zstd_lv.map_or(Some(19), |x| Some(*x as _));
 
     pool.execute(move || {
-        let zstd = Upack::new(&tar_path, &zstd_target)
-            .with_operation(Operation::encode(lv));
+        let mut upack = Upack::new(&tar_path, &target)
+            .with_operation(Operation::encode(Some(lv as _)))
+            .with_zstd_params(zstd)
+            .with_progress(log_progress(&target));
+        upack.zstd_dict = dict;
         info!(
             "Compressing {:?} to {:?}",
-            zstd.source.path, zstd.target.path,
+            upack.source.path, upack.target.path,
         );
-        debug!("operation: {:?}", zstd.operation);
-        let _ = zstd.run();
+        debug!("operation: {:?}", upack.operation);
+
+        let digests = match encode_and_hash(&upack) {
+            Ok(d) => d,
+            Err(e) => {
+                log::error!("Failed to compress {tar_path:?}: {e}");
+                // Do not leave a truncated file behind.
+                let _ = fs::remove_file(&target);
+                pool::push_err(anyhow::Error::new(e).context(format!(
+                    "Failed to compress {tar_path:?} to {target:?}"
+                )));
+                return;
+            }
+        };
+        if let Err(e) = on_success(digests) {
+            log::error!("{e:#}");
+            pool::push_err(e.context(format!("Failed to finish {target:?}")));
+        }
     })
 }
 
+/// Compresses the source to the target of `upack`, and hashes the archive in the same pass.
+fn encode_and_hash(upack: &Upack<&PathBuf, &PathBuf>) -> repack::Result<Digests> {
+    let archive = HashWriter::new(buf_writer(upack.target.path)?, ARCHIVE_DIGESTS);
+    let (_, digests) = upack
+        .encode_stream(buf_reader(upack.source.path)?, archive)?
+        .finalize()?;
+    Ok(digests)
+}
+
+/// Logs the progress every 30s, so that long-running compressions give a sign of life in CI logs.
+///
+/// e.g., `2.0_hamm_x86.tar.zst: 512.00 MiB (42.3%), ratio: 18.52%, 35.61 MiB/s`
+fn log_progress(target: &Path) -> ProgressCallback {
+    let name = target
+        .file_name()
+        .unwrap_or(target.as_os_str())
+        .to_string_lossy()
+        .into_owned();
+
+    ProgressCallback::new(Duration::from_secs(30), move |p| {
+        let percent = p
+            .percent()
+            .map(|x| format!(" ({x:.1}%)"))
+            .unwrap_or_default();
+        let ratio = p
+            .ratio()
+            .map(|x| format!(", ratio: {:.2}%", x * 100.0))
+            .unwrap_or_default();
+        let msg = format!(
+            "{name}: {read}{percent}{ratio}, {speed:.2} MiB/s",
+            read = ByteUnit::new(p.read),
+            speed = p.mib_per_sec(),
+        );
+
+        match p.done {
+            true => info!("[done] {msg}"),
+            _ => info!("{msg}"),
+        }
+    })
+}
+
+/// Extracts a rootfs tarball, see [extract_tar].
 pub(crate) fn extract_tar_as_root<D: AsRef<Path>>(
     tar_path: &Path,
     dst_dir: D,
@@ -49,6 +133,11 @@ pub(crate) fn extract_tar_as_root<D: AsRef<Path>>(
     extract_tar(tar_path, dst_dir, true)
 }
 
+/// Extracts `tar_path` (tar, tar.gz, tar.zst, tar.xz, tar.bz2) to `dst_dir`.
+///
+/// - root: uses the native unpacker ([repack::archive::Unpacker]), which also restores the ownerships.
+/// - non-root & `as_root`: invokes the `tar` command as root (doas/sudo). A rootfs contains files owned by other users, setuid binaries and device nodes; chown & mknod require root, so the native unpacker cannot restore them in a non-root process.
+/// - non-root & `!as_root`: uses the native unpacker, the files are owned by the current user.
 pub(crate) fn extract_tar<D: AsRef<Path>>(
     tar_path: &Path,
     dst_dir: D,
@@ -59,7 +148,17 @@ pub(crate) fn extract_tar<D: AsRef<Path>>(
         fs::create_dir_all(dst)?;
     }
 
-    #[allow(unused_variables)]
+    if is_root() || !as_root {
+        let extract =
+            Upack::new(tar_path, dst).with_operation(Operation::decode_full());
+        info!(
+            "Extracting {:?} to {:?}",
+            extract.source.path, extract.target.path,
+        );
+        debug!("source format: {:?}", extract.source.get_format());
+        return Ok(extract.run()?);
+    }
+
     let osstr = OsStr::new;
 
     let args = [
@@ -69,20 +168,69 @@ pub(crate) fn extract_tar<D: AsRef<Path>>(
         tar_path.as_ref(),
     ];
 
-    match as_root {
-        true => run_as_root("tar", &args, true),
-        _ => run("tar", &args, true),
-    };
+    run_as_root("tar", &args, true);
 
     Ok(())
 }
 
-/// Invokes the `tar` command as root and packages the `src_dir` to `tar_path`.
+/// Patterns that are relative to the root of the rootfs.
+const ROOTFS_EXCLUDE: [&str; 14] = [
+    "proc/*",
+    "sys/*",
+    "tmp/*",
+    "var/tmp/*",
+    "run/*",
+    "mnt/*",
+    "media/*",
+    "boot/*",
+    "var/cache/apt/pkgcache.bin",
+    "var/cache/apt/srcpkgcache.bin",
+    "var/cache/apt/archives/*deb",
+    "var/cache/apt/archives/partial/*",
+    "var/cache/archive-copier/*",
+    "var/lib/apt/lists/*.*",
+    // "var/cache/apt/archives/lock",
+];
+
+pub(crate) fn rootfs_exclude(exclude_dev: bool) -> Exclude {
+    let mut exclude = Exclude::from_iter(ROOTFS_EXCLUDE);
+    if exclude_dev {
+        exclude.push("dev/*")
+    }
+    exclude
+}
+
+/// Returns the SOURCE_DATE_EPOCH of `repo` if `--reproducible` is enabled.
+pub(crate) fn reproducible_epoch(repo: &Repository) -> Option<i64> {
+    match Cli::static_reproducible(None) {
+        true => repo.source_date_epoch(),
+        _ => None,
+    }
+}
+
+/// The zstd archive that is written in the same pass as the tarball, see [`Packer::pack_to_file_and_zst`].
+#[derive(Debug)]
+pub(crate) struct ZstOutput<'a> {
+    pub(crate) path: &'a Path,
+    pub(crate) lv: u8,
+    pub(crate) params: ZstdParams,
+}
+
+/// Packages the `src_dir` to `tar_path`.
+///
+/// - root: uses the native tar writer (repack::archive).
+/// - non-root: invokes the `tar` command as root (doas/sudo), because the rootfs contains files that are only readable by root.
+///
+/// If `source_date_epoch` is Some, the tarball is reproducible (see [`Packer::with_source_date_epoch`]).
+///
+/// Returns the [ARCHIVE_DIGESTS] of `zst` if it has also been written (root only).
 pub(crate) fn pack_tar_as_root<S: AsRef<OsStr>>(
     src_dir: S,
     tar_path: &Path,
     exclude_dev: bool,
-) {
+    source_date_epoch: Option<i64>,
+    zst: Option<ZstOutput>,
+) -> io::Result<Option<Digests>> {
     let osstr = OsStr::new;
 
     if let Some(par) = tar_path.parent() {
@@ -92,41 +240,51 @@ pub(crate) fn pack_tar_as_root<S: AsRef<OsStr>>(
         }
     }
 
-    let mut args = TinyVec::<[&OsStr; 24]>::new();
-
     let src_osdir = src_dir.as_ref();
+    let exclude = rootfs_exclude(exclude_dev);
 
-    // doas tar --posix --directory src_dir --exclude=... -cf tar_path .
-    args.extend(["--posix", "--directory"].map(osstr));
-    args.push(src_osdir);
-    args.extend(
-        [
-            r"--exclude=proc/*",
-            r"--exclude=sys/*",
-            r"--exclude=tmp/*",
-            r"--exclude=var/tmp/*",
-            r"--exclude=run/*",
-            r"--exclude=mnt/*",
-            r"--exclude=media/*",
-            r"--exclude=boot/*",
-            r"--exclude=var/cache/apt/pkgcache.bin",
-            r"--exclude=var/cache/apt/srcpkgcache.bin",
-            r"--exclude=var/cache/apt/archives/*deb",
-            r"--exclude=var/cache/apt/archives/partial/*",
-            r"--exclude=var/cache/archive-copier/*",
-            r"--exclude=var/lib/apt/lists/*.*",
-            // r#"--exclude=var/cache/apt/archives/lock"#,
-        ]
-        .map(osstr),
-    );
-
-    if exclude_dev {
-        args.push(osstr(r#"--exclude=dev/*"#));
-    }
-
-    args.extend([osstr("-cf"), tar_path.as_ref(), osstr(".")]);
-
-    run_as_root("tar", &args, true);
+    let zst_digests = match is_root() {
+        true => {
+            info!("Packing {src_osdir:?} to {tar_path:?}");
+            debug!("exclude: {exclude:?}");
+            let packer = Packer::new(src_osdir)
+                .with_exclude(exclude)
+                .with_source_date_epoch(source_date_epoch);
+            match zst {
+                Some(ZstOutput { path, lv, params }) => {
+                    info!("Compressing to {path:?} in the same pass");
+                    let archive =
+                        HashWriter::new(buf_writer(path)?, ARCHIVE_DIGESTS);
+                    match packer
+                        .pack_to_file_and_zst(tar_path, archive, lv as _, &params)
+                        .and_then(HashWriter::finalize)
+                    {
+                        Ok((_, digests)) => Some(digests),
+                        Err(e) => {
+                            // Do not leave a truncated file behind.
+                            let _ = fs::remove_file(path);
+                            return Err(e);
+                        }
+                    }
+                }
+                _ => {
+                    packer.pack_to_file(tar_path)?;
+                    None
+                }
+            }
+        }
+        _ => {
+            if let Some(z) = zst {
+                info!(
+                    "Single-pass compression requires root, {:?} will be compressed from {tar_path:?}",
+                    z.path
+                )
+            }
+            let args = tar_args(src_osdir, tar_path, &exclude, source_date_epoch);
+            run_as_root("tar", &args, true);
+            None
+        }
+    };
 
     let internal_dir = |s| Path::new(src_osdir).join(s);
 
@@ -139,4 +297,178 @@ pub(crate) fn pack_tar_as_root<S: AsRef<OsStr>>(
     }
 
     force_remove_item_as_root(src_osdir);
+    Ok(zst_digests)
+}
+
+/// tar --posix --directory src_dir --anchored --wildcards --wildcards-match-slash --exclude=./PATTERN... -cf tar_path .
+///
+/// The excludes have the same semantics as [Exclude] (anchored at the root of `src_dir`, `*` matches `/`).
+fn tar_args(
+    src_dir: &OsStr,
+    tar_path: &Path,
+    exclude: &Exclude,
+    source_date_epoch: Option<i64>,
+) -> Vec<OsString> {
+    let osstr = OsStr::new;
+
+    // GNU tar matches the member names, e.g., ./proc/1
+    let excludes = exclude
+        .patterns()
+        .iter()
+        .map(|w| {
+            let mut arg = OsString::from("--exclude=./");
+            arg.push(OsStr::from_bytes(w.as_bytes()));
+            arg
+        })
+        .collect::<Vec<_>>();
+
+    // --sort=name --mtime=@EPOCH --clamp-mtime --numeric-owner --pax-option=...
+    let reproducible = source_date_epoch.map(|epoch| {
+        [
+            "--sort=name".into(),
+            format!("--mtime=@{epoch}"),
+            "--clamp-mtime".into(),
+            "--numeric-owner".into(),
+            "--pax-option=exthdr.name=%d/PaxHeaders/%f,delete=atime,delete=ctime"
+                .into(),
+        ]
+    });
+
+    let mut args = Vec::with_capacity(excludes.len() + 16);
+    args.extend(["--posix", "--directory"].map(OsString::from));
+    args.push(src_dir.to_owned());
+    // must precede the --exclude options
+    args.extend(
+        ["--anchored", "--wildcards", "--wildcards-match-slash"].map(OsString::from),
+    );
+    args.extend(excludes);
+    args.extend(
+        reproducible
+            .into_iter()
+            .flatten()
+            .map(OsString::from),
+    );
+    args.extend([osstr("-cf"), tar_path.as_ref(), osstr(".")].map(OsStr::to_owned));
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exclude_rootfs_items() {
+        let exclude = rootfs_exclude(true);
+        for p in [
+            "proc/1/status",
+            "dev/null",
+            "var/cache/apt/archives/bash_5.2.15-2_amd64.deb",
+            "var/lib/apt/lists/deb.debian.org_debian_dists_bookworm_InRelease",
+        ] {
+            assert!(exclude.is_excluded(p), "{p}")
+        }
+        for p in ["proc", "dev", "var/cache/apt/archives/lock", "etc/passwd"] {
+            assert!(!exclude.is_excluded(p), "{p}")
+        }
+        assert!(!rootfs_exclude(false).is_excluded("dev/null"))
+    }
+
+    /// The `tar` fallback excludes the same members as the native [Packer].
+    #[test]
+    fn tar_fallback_excludes_like_packer() -> io::Result<()> {
+        use std::{collections::BTreeSet, process::Command};
+
+        let tmp = tempfile::tempdir()?;
+        let root = tmp.path().join("rootfs");
+        for d in [
+            "proc/1",
+            "usr/share/proc",
+            "srv/www/tmp",
+            "var/tmp",
+            "var/cache/apt/archives",
+        ] {
+            fs::create_dir_all(root.join(d))?
+        }
+        for f in [
+            "proc/1/status",
+            "usr/share/proc/x",
+            "srv/www/tmp/z",
+            "var/tmp/y",
+            "var/cache/apt/archives/bash_5.2_amd64.deb",
+            "var/cache/apt/archives/lock",
+            "etc-tmp",
+        ] {
+            fs::write(root.join(f), f)?
+        }
+
+        // e.g., "./", "./proc/" (GNU) & ".", "proc" (native) => ".", "proc"
+        let names = |tar: &Path| -> io::Result<BTreeSet<String>> {
+            let out = Command::new("tar")
+                .arg("-tf")
+                .arg(tar)
+                .output()?;
+            Ok(String::from_utf8_lossy(&out.stdout)
+                .lines()
+                .map(|p| {
+                    match p
+                        .trim_start_matches("./")
+                        .trim_end_matches('/')
+                    {
+                        "" => ".".into(),
+                        p => p.into(),
+                    }
+                })
+                .collect())
+        };
+
+        let exclude = rootfs_exclude(true);
+        let gnu = tmp.path().join("gnu.tar");
+        let args = tar_args(root.as_os_str(), &gnu, &exclude, None);
+        let status = Command::new("tar")
+            .args(&args)
+            .status()?;
+        assert!(status.success(), "tar {args:?}: {status}");
+
+        // The previous fallback: unanchored, --exclude=tmp/*
+        let unanchored = tmp
+            .path()
+            .join("unanchored.tar");
+        let status = Command::new("tar")
+            .args(
+                tar_args(root.as_os_str(), &unanchored, &exclude, None)
+                    .into_iter()
+                    .filter(|a| a != "--anchored")
+                    .map(|a| match a.to_str() {
+                        Some(s) => s
+                            .replacen("--exclude=./", "--exclude=", 1)
+                            .into(),
+                        _ => a,
+                    }),
+            )
+            .status()?;
+        assert!(status.success(), "{status}");
+
+        let native = tmp.path().join("native.tar");
+        Packer::new(&root)
+            .with_exclude(exclude)
+            .pack_to_file(&native)?;
+
+        let gnu_names = names(&gnu)?;
+        assert!(gnu_names.contains("usr/share/proc/x"), "{gnu_names:?}");
+        assert!(gnu_names.contains("srv/www/tmp/z"), "{gnu_names:?}");
+        assert!(!gnu_names.contains("proc/1"), "{gnu_names:?}");
+        assert_eq!(gnu_names, names(&native)?);
+
+        // Unanchored, `tmp/*` & `proc/*` also dropped the nested srv/www/tmp/z & usr/share/proc/x.
+        let unanchored_names = names(&unanchored)?;
+        assert!(
+            !unanchored_names.contains("srv/www/tmp/z"),
+            "{unanchored_names:?}"
+        );
+        assert!(
+            !unanchored_names.contains("usr/share/proc/x"),
+            "{unanchored_names:?}"
+        );
+        Ok(())
+    }
 }
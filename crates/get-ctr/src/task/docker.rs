@@ -1,8 +1,13 @@
 use log_l10n::level::color::OwoColorize;
 
 use crate::{
+    cfg::registry,
     command::{self, run},
-    docker::{self, repo::Repository, spawn_docker_build},
+    docker::{
+        self,
+        repo::{NormalRepos, Repository},
+        spawn_docker_build,
+    },
     task::compression::{extract_tar, pack_tar_as_root},
 };
 use std::{
@@ -30,32 +35,12 @@ pub(crate) fn run_docker_build(
     docker_dir: &Path,
     tag_map: &mut docker::repo_map::RepoMap,
 ) -> anyhow::Result<()> {
-    let (ghcr_tags, reg_tags) = match repo.get_date_tagged() {
-        true => (repo.ghcr_date_tagged_repos(), repo.reg_date_tagged_repos()),
-        _ => (repo.ghcr_repos(), repo.reg_repos()),
-    };
-
-    // ghcr_tags => docker-dir/ghcr.ron
-    // tag => docker-dir/tag.ron
-    {
-        fs::write(docker_dir.join("ghcr.ron"), ron::to_string(&ghcr_tags)?)?;
-        fs::write(docker_dir.join("reg.ron"), ron::to_string(&reg_tags)?)?;
-
-        let tag = ghcr_tags[0]
-            .rsplit(':')
-            .next()
-            .unwrap_or("latest");
-        fs::write(docker_dir.join("tag.ron"), ron::to_string(tag)?)?;
-    }
+    let tags = save_tags(repo, docker_dir, tag_map)?;
 
-    let tags_iter = reg_tags
+    let tags_iter = tags
         .iter()
-        .map(Deref::deref)
-        .chain(
-            ghcr_tags
-                .iter()
-                .map(Deref::deref),
-        );
+        .flatten()
+        .map(Deref::deref);
     children.push((
         "docker-build-task",
         spawn_docker_build(
@@ -66,24 +51,46 @@ pub(crate) fn run_docker_build(
     ));
     // children
     //     .push(("sleep 0.009s", command::spawn_cmd("sleep", &["0.009"])));
+    Ok(())
+}
 
-    let (ghcr_main, reg_main) = match repo.get_date_tagged() {
-        true => (
-            repo.ghcr_main_date_tagged_repos(),
-            repo.reg_main_date_tagged_repos(),
-        ),
-        _ => (repo.ghcr_main_repos(), repo.reg_main_repos()),
-    };
+/// Writes {target}.ron (e.g., ghcr.ron, reg.ron) & tag.ron to `docker_dir`, pushes the tags to `tag_map`, and returns the tags of each [registry::targets].
+pub(crate) fn save_tags(
+    repo: &docker::repo::Repository<'_>,
+    docker_dir: &Path,
+    tag_map: &mut docker::repo_map::RepoMap,
+) -> anyhow::Result<Vec<NormalRepos>> {
+    let targets = registry::targets();
+    let mut all_tags = Vec::with_capacity(targets.len());
+
+    for target in targets {
+        let tags = repo.target_repos(target)?;
+
+        // ghcr_tags => docker-dir/ghcr.ron
+        fs::write(
+            docker_dir.join(target.ron_filename()),
+            ron::to_string(&tags)?,
+        )?;
+
+        // Map {key: MainRepo(reg, manifest-repo-0), value: TinyVec[x86-tag0, m68k-tag0, element0...]}
+        // Map {key: MainRepo(reg, manifest-repo-1), value: TinyVec[x86-tag1, m68k-tag1, element1...]}
+        for (key, element) in
+            iter::zip(repo.target_main_repos(target)?, tags.clone())
+        {
+            tag_map.push_to_value(key, element)
+        }
+        all_tags.push(tags)
+    }
 
-    let ghcr_iter = iter::zip(ghcr_main, ghcr_tags);
-    let reg_iter = iter::zip(reg_main, reg_tags);
+    // tag => docker-dir/tag.ron, e.g., potato-x86-base
+    let tag = all_tags
+        .first()
+        .and_then(|t| t.first())
+        .and_then(|t| t.rsplit(':').next())
+        .unwrap_or("latest");
+    fs::write(docker_dir.join("tag.ron"), ron::to_string(tag)?)?;
 
-    // Map {key: Reg(manifest-repo-0), value: TinyVec[x86-tag0, m68k-tag0, element0...]}
-    // Map {key: Reg(manifest-repo-1), value: TinyVec[x86-tag1, m68k-tag1, element1...]}
-    for (key, element) in reg_iter.chain(ghcr_iter) {
-        tag_map.push_to_value(key, element)
-    }
-    Ok(())
+    Ok(all_tags)
 }
 
 pub(crate) fn save_cache(first_repo: &Repository<'_>) -> io::Result<()> {
@@ -91,7 +98,7 @@ pub(crate) fn save_cache(first_repo: &Repository<'_>) -> io::Result<()> {
     let tmp_dir = temp_dir().join(&base_name);
     let tar_path = tmp_dir.join("cache.tar");
 
-    pack_tar_as_root(".", &tar_path, false);
+    pack_tar_as_root(".", &tar_path, false, None, None)?;
 
     const CONTENT: &str = r##"# syntax=docker/dockerfile:1
 FROM busybox:musl
@@ -122,10 +129,11 @@ COPY cache.tar /
     Ok(())
 }
 
+/// e.g., reg.tmoe.me:2096/debian/cache:2.2_potato_x86, see [registry::cache_target]
 fn get_cache_tag(first_repo: &Repository<'_>, base_name: &str) -> String {
     format!(
-        "{uri}/{owner}/cache:{base_name}",
-        uri = Repository::REG_URI,
+        "{prefix}/{owner}/cache:{base_name}",
+        prefix = registry::cache_target().prefix(),
         owner = first_repo.get_reg_date_tagged_owner(),
     )
 }
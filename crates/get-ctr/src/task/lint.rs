@@ -0,0 +1,541 @@
+//! Checks the distro catalogs before building, e.g., an arch that `get_oci_platform()` does not know.
+
+use crate::cfg::{
+    catalog::{self, Kind},
+    debootstrap::{self, Source},
+    disk::DiskV1,
+};
+use anyhow::bail;
+use derive_more::Display;
+use serde::{
+    de::{self, DeserializeOwned, Visitor},
+    Deserialize, Deserializer,
+};
+use std::{borrow::Cow, collections::BTreeSet, fmt, path::Path};
+
+#[derive(Debug, Display)]
+#[display("{file}:{line}:{col}: {msg}")]
+struct Problem {
+    file: String,
+    line: usize,
+    col: usize,
+    msg: String,
+}
+
+/// Where the linted values of an os entry are, i.e., the byte offsets of their opening quotes.
+///
+/// `S` is [`TomlAt`] or [`RonAt`] while parsing & `Option<usize>` after [`CatalogSrc::spans()`].
+/// Both [`debootstrap::OS`] & [`crate::cfg::disk::OS`] fit; unknown fields are ignored.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, bound = "S: Deserialize<'de> + Default")]
+struct OsSpans<S> {
+    version: S,
+    date: S,
+    tag: Vec<TagSpans<S>>,
+    disk: Vec<DiskSpans<S>>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, bound = "S: Deserialize<'de> + Default")]
+struct TagSpans<S> {
+    arch: S,
+    #[serde(rename = "deb-arch")]
+    deb_arch: S,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, bound = "S: Deserialize<'de> + Default")]
+struct DiskSpans<S> {
+    arch: S,
+    date: S,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, bound = "S: Deserialize<'de> + Default")]
+struct Spans<S> {
+    os: Vec<OsSpans<S>>,
+}
+
+type OsAt = OsSpans<Option<usize>>;
+
+trait Located {
+    /// Byte offset in `content` (`None` if the value is missing or not located)
+    fn offset(&self, content: &str) -> Option<usize>;
+}
+
+/// The span of a toml string.
+#[derive(Default)]
+struct TomlAt(Option<usize>);
+
+impl Located for TomlAt {
+    fn offset(&self, _: &str) -> Option<usize> {
+        self.0
+    }
+}
+
+impl<'de> Deserialize<'de> for TomlAt {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        toml::Spanned::<String>::deserialize(d).map(|s| Self(Some(s.span().start)))
+    }
+}
+
+/// A ron string borrowed from the content; an escaped one is copied & thus not located.
+#[derive(Default)]
+struct RonAt<'a>(Option<&'a str>);
+
+impl Located for RonAt<'_> {
+    fn offset(&self, content: &str) -> Option<usize> {
+        let start =
+            (self.0?.as_ptr() as usize).checked_sub(content.as_ptr() as usize)?;
+        // the quote (or `r#"`) precedes the borrowed body
+        start.checked_sub(1)
+    }
+}
+
+impl<'de> Deserialize<'de> for RonAt<'de> {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        struct V;
+        impl<'de> Visitor<'de> for V {
+            type Value = RonAt<'de>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a string")
+            }
+            fn visit_borrowed_str<E: de::Error>(
+                self,
+                v: &'de str,
+            ) -> Result<Self::Value, E> {
+                Ok(RonAt(Some(v)))
+            }
+            fn visit_str<E: de::Error>(self, _: &str) -> Result<Self::Value, E> {
+                Ok(RonAt(None))
+            }
+        }
+        d.deserialize_str(V)
+    }
+}
+
+impl<S: Located> OsSpans<S> {
+    fn resolve(self, content: &str) -> OsAt {
+        let at = |s: S| s.offset(content);
+        OsSpans {
+            version: at(self.version),
+            date: at(self.date),
+            tag: self
+                .tag
+                .into_iter()
+                .map(|t| TagSpans {
+                    arch: at(t.arch),
+                    deb_arch: at(t.deb_arch),
+                })
+                .collect(),
+            disk: self
+                .disk
+                .into_iter()
+                .map(|d| DiskSpans {
+                    arch: at(d.arch),
+                    date: at(d.date),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A catalog file (or the embedded one) & its content.
+struct CatalogSrc {
+    name: String,
+    content: Cow<'static, str>,
+    toml: bool,
+}
+
+impl CatalogSrc {
+    fn new(kind: Kind, file: Option<&Path>, content: Cow<'static, str>) -> Self {
+        let name = match file {
+            Some(f) => f.display().to_string(),
+            _ => format!("<embedded {kind}.ron>"),
+        };
+        let toml = file.is_some_and(|f| {
+            f.extension()
+                .is_some_and(|e| e == "toml")
+        });
+        Self {
+            name,
+            content,
+            toml,
+        }
+    }
+
+    /// 1-based line & column of the byte `offset`
+    fn position(&self, offset: usize) -> (usize, usize) {
+        let before = &self.content[..offset.min(self.content.len())];
+        let line = before.matches('\n').count() + 1;
+        let col = before
+            .rfind('\n')
+            .map_or(before.len(), |i| before.len() - i - 1)
+            + 1;
+        (line, col)
+    }
+
+    fn problem<S: Into<String>>(&self, offset: usize, msg: S) -> Problem {
+        let (line, col) = self.position(offset);
+        Problem {
+            file: self.name.clone(),
+            line,
+            col,
+            msg: msg.into(),
+        }
+    }
+
+    /// The value spans of every os entry, in the order of `deser()`.
+    fn spans(&self) -> Result<Vec<OsAt>, Problem> {
+        let content: &str = &self.content;
+        let os = match self.toml {
+            true => self
+                .deser::<Spans<TomlAt>>()?
+                .os
+                .into_iter()
+                .map(|os| os.resolve(content))
+                .collect(),
+            _ => ron::from_str::<Spans<RonAt>>(content)
+                .map_err(|e| self.ron_problem(e))?
+                .os
+                .into_iter()
+                .map(|os| os.resolve(content))
+                .collect(),
+        };
+        Ok(os)
+    }
+
+    fn deser<T: DeserializeOwned>(&self) -> Result<T, Problem> {
+        if self.toml {
+            return toml::from_str(&self.content).map_err(|e| {
+                let offset = e.span().map_or(0, |r| r.start);
+                self.problem(offset, e.message())
+            });
+        }
+        ron::from_str(&self.content).map_err(|e| self.ron_problem(e))
+    }
+
+    fn ron_problem(&self, e: ron::error::SpannedError) -> Problem {
+        Problem {
+            file: self.name.clone(),
+            line: e.position.line,
+            col: e.position.col,
+            msg: e.code.to_string(),
+        }
+    }
+}
+
+/// Loads all catalogs (from the search path or the embedded ones) & reports every problem.
+pub(crate) fn lint_catalogs() -> anyhow::Result<()> {
+    let mut problems = Vec::new();
+
+    for kind in Kind::ALL {
+        let (file, content) = catalog::read(kind)?;
+        let src = CatalogSrc::new(kind, file.as_deref(), content);
+        log::info!("Linting {}", src.name);
+
+        match kind {
+            Kind::OldOldDebian => match src
+                .deser::<DiskV1>()
+                .and_then(|cfg| Ok((cfg, src.spans()?)))
+            {
+                Ok((cfg, spans)) => lint_disk(&src, &cfg, &spans, &mut problems),
+                Err(p) => problems.push(p),
+            },
+            _ => match src
+                .deser::<debootstrap::Cfg>()
+                .and_then(|cfg| Ok((cfg, src.spans()?)))
+            {
+                Ok((cfg, spans)) => {
+                    lint_debootstrap(&src, &cfg, &spans, &mut problems)
+                }
+                Err(p) => problems.push(p),
+            },
+        }
+    }
+
+    for p in &problems {
+        log::error!("{p}")
+    }
+    match problems.len() {
+        0 => {
+            log::info!("OK");
+            Ok(())
+        }
+        n => bail!("{n} problem(s) in the catalogs"),
+    }
+}
+
+fn lint_debootstrap(
+    src: &CatalogSrc,
+    cfg: &debootstrap::Cfg,
+    spans: &[OsAt],
+    problems: &mut Vec<Problem>,
+) {
+    let mut versions = BTreeSet::new();
+
+    for (os, at) in cfg.iter().zip(spans) {
+        let version = os.get_version();
+        // An escaped (i.e., unlocated) ron value is reported at its os entry.
+        let os_offset = at.version.unwrap_or_default();
+
+        match version
+            .split_ascii_whitespace()
+            .next()
+        {
+            Some(v) if !versions.insert(v) => problems
+                .push(src.problem(os_offset, format!("duplicate version: {v}"))),
+            None => problems.push(src.problem(os_offset, "empty version")),
+            _ => {}
+        }
+
+        let date = os.get_date();
+        if !date.is_empty() && !is_valid_date(date) {
+            problems.push(src.problem(
+                at.date.unwrap_or(os_offset),
+                format!(
+                    "malformed date: {date:?} (expected: YYYY-MM or YYYY-MM-DD)"
+                ),
+            ))
+        }
+
+        for (tag, tag_at) in os.get_tag().iter().zip(&at.tag) {
+            let arch = tag.get_arch();
+            let tag_offset = tag_at.arch.unwrap_or(os_offset);
+
+            if !is_oci_arch(arch) {
+                problems.push(src.problem(
+                    tag_offset,
+                    format!("{version}: arch {arch:?} is not in archmap::linux_oci_platform"),
+                ))
+            }
+
+            // A tag with its own source (e.g., debian-amd64 for sarge) may use an unlisted arch.
+            let deb_arch = tag.get_deb_arch();
+            if !has_src(tag.get_source())
+                && !os
+                    .get_deb_architectures()
+                    .contains(deb_arch)
+            {
+                problems.push(src.problem(
+                    tag_at.deb_arch.unwrap_or(tag_offset),
+                    format!("{version}: deb-arch {deb_arch:?} is not listed in deb-architectures"),
+                ))
+            }
+
+            if !has_src(tag.get_source()) && !has_src(os.get_source()) {
+                problems.push(src.problem(
+                    tag_offset,
+                    format!("{version} ({arch}): neither the tag nor the os source has `src` or `enabled`"),
+                ))
+            }
+        }
+    }
+}
+
+fn lint_disk(
+    src: &CatalogSrc,
+    cfg: &DiskV1,
+    spans: &[OsAt],
+    problems: &mut Vec<Problem>,
+) {
+    let mut versions = BTreeSet::new();
+
+    for (os, at) in cfg.iter().zip(spans) {
+        let version = os.get_version();
+        let os_offset = at.version.unwrap_or_default();
+
+        if !versions.insert(version) {
+            problems.push(
+                src.problem(os_offset, format!("duplicate version: {version}")),
+            )
+        }
+
+        for (date, offset) in [(os.get_date(), at.date)]
+            .into_iter()
+            .chain(
+                os.get_disk()
+                    .iter()
+                    .zip(&at.disk)
+                    .map(|(d, d_at)| (d.get_date(), d_at.date)),
+            )
+        {
+            if !is_valid_date(date) {
+                problems.push(src.problem(
+                    offset.unwrap_or(os_offset),
+                    format!("{version}: malformed date: {date:?} (expected: YYYY-MM or YYYY-MM-DD)"),
+                ))
+            }
+        }
+
+        for (disk, disk_at) in os
+            .get_disk()
+            .iter()
+            .zip(&at.disk)
+        {
+            let arch = disk.get_arch();
+            if !is_oci_arch(arch) {
+                problems.push(src.problem(
+                    disk_at.arch.unwrap_or(os_offset),
+                    format!("{version}: arch {arch:?} is not in archmap::linux_oci_platform"),
+                ))
+            }
+        }
+    }
+}
+
+fn is_oci_arch(arch: &str) -> bool {
+    archmap::linux_oci_platform::map().contains_key(arch)
+}
+
+/// `get_src_format()` & `debootstrap_src()` need `src` or `enabled`.
+fn has_src(source: &Source) -> bool {
+    source.get_src().is_some() || source.get_enabled().is_some()
+}
+
+/// YYYY-MM or YYYY-MM-DD
+fn is_valid_date(date: &str) -> bool {
+    let mut parts = date.split('-');
+    let (Some(y), Some(m)) = (parts.next(), parts.next()) else {
+        return false;
+    };
+    let d = parts.next().unwrap_or("01");
+
+    if parts.next().is_some() || y.len() != 4 || m.len() != 2 || d.len() != 2 {
+        return false;
+    }
+
+    match (y.parse::<i32>(), m.parse::<u8>(), d.parse::<u8>()) {
+        (Ok(y), Ok(m), Ok(d)) => time::Month::try_from(m)
+            .and_then(|m| time::Date::from_calendar_date(y, m, d))
+            .is_ok(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lint_debootstrap_toml() {
+        let content = r#"
+[[os]]
+name = "Debian"
+version = "13"
+series = "trixie"
+date = "2025-13"
+deb-architectures = ["amd64"]
+
+[[os.tag]]
+arch = "x64"
+deb-arch = "amd64"
+source = { src = "debian" }
+
+[[os.tag]]
+arch = "pdp11"
+deb-arch = "pdp11"
+
+[[os]]
+name = "Debian"
+version = "13"
+"#;
+        let src = CatalogSrc {
+            name: "debian.toml".into(),
+            content: content.into(),
+            toml: true,
+        };
+        let cfg = src
+            .deser::<debootstrap::Cfg>()
+            .expect("Invalid toml");
+
+        let spans = src
+            .spans()
+            .expect("Invalid toml");
+        let mut problems = Vec::new();
+        lint_debootstrap(&src, &cfg, &spans, &mut problems);
+        let msgs = problems
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(msgs.len(), 5, "{msgs:#?}");
+        assert!(msgs[0].starts_with("debian.toml:6:8: malformed date"));
+        assert!(
+            msgs[1].starts_with("debian.toml:15:8: 13: arch \"pdp11\""),
+            "{msgs:#?}"
+        );
+        assert!(msgs[4].starts_with("debian.toml:20:11: duplicate version"));
+
+        // syntax error
+        let src = CatalogSrc {
+            content: "[[os]]\nversion = \n".into(),
+            ..src
+        };
+        let err = src
+            .deser::<debootstrap::Cfg>()
+            .expect_err("Invalid toml");
+        assert_eq!((err.line, err.col), (2, 11));
+    }
+
+    #[test]
+    fn lint_debootstrap_ron_spans() {
+        // "pdp11" comes first in deb-architectures, but the arch is reported.
+        let content = r#"(os:[(version:"13",r#deb-architectures:["pdp11"],tag:[(arch:"pdp11",r#deb-arch:"pdp11",source:(src:Some("debian")))])])"#;
+        let src = CatalogSrc {
+            name: "debian.ron".into(),
+            content: content.into(),
+            toml: false,
+        };
+        let cfg = src
+            .deser::<debootstrap::Cfg>()
+            .expect("Invalid ron");
+        let spans = src
+            .spans()
+            .expect("Invalid ron");
+
+        let mut problems = Vec::new();
+        lint_debootstrap(&src, &cfg, &spans, &mut problems);
+        assert_eq!(problems.len(), 1, "{problems:#?}");
+        assert_eq!(
+            (problems[0].line, problems[0].col),
+            (
+                1,
+                content
+                    .find(r#""pdp11",r#deb-arch"#)
+                    .unwrap()
+                    + 1
+            )
+        );
+    }
+
+    #[test]
+    fn lint_embedded_catalogs() {
+        for kind in Kind::ALL {
+            let src = CatalogSrc::new(kind, None, kind.embedded().into());
+            let spans = src
+                .spans()
+                .expect("Invalid ron");
+            assert!(!spans.is_empty());
+            let mut problems = Vec::new();
+            match kind {
+                Kind::OldOldDebian => lint_disk(
+                    &src,
+                    &src.deser()
+                        .expect("Invalid ron"),
+                    &spans,
+                    &mut problems,
+                ),
+                _ => lint_debootstrap(
+                    &src,
+                    &src.deser()
+                        .expect("Invalid ron"),
+                    &spans,
+                    &mut problems,
+                ),
+            }
+            assert!(problems.is_empty(), "{problems:#?}");
+        }
+    }
+}
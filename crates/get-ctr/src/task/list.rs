@@ -0,0 +1,293 @@
+//! Prints the OS/version/arch/tag matrix of the catalogs, e.g., `--list devuan --list-format json`.
+
+use crate::cfg::{
+    catalog::{self, Kind},
+    debootstrap,
+    disk::DiskV1,
+    project::{Project, PROJECTS},
+};
+use anyhow::bail;
+use clap::ValueEnum;
+use serde::Serialize;
+
+#[derive(ValueEnum, Debug, Clone, Copy, Default)]
+pub(crate) enum ListFormat {
+    #[default]
+    Table,
+    Json,
+    Ron,
+}
+
+/// One buildable combination, i.e., `--os {os} --ver {version} --arch {arch} --tag {tag}`
+#[derive(Serialize, Debug, Clone)]
+pub(crate) struct Entry {
+    os: &'static str,
+    version: String,
+    codename: String,
+    series: String,
+    arch: String,
+    deb_arch: String,
+    platform: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tag: Option<String>,
+    date: String,
+    /// e.g., disk.v1
+    catalog: String,
+}
+
+fn oci_platform(arch: &str) -> String {
+    archmap::linux_oci_platform::map()
+        .get(arch)
+        .map_or_else(String::new, |p| p.to_string())
+}
+
+/// The debian arch of `arch` (e.g., x64 => amd64), if the catalog does not have it.
+fn debian_arch(arch: &str) -> String {
+    archmap::debian_arch::map()
+        .get(arch)
+        .map_or_else(String::new, |a| a.to_string())
+}
+
+/// The entries of a debootstrap catalog, the version is the first word, e.g., "unstable".
+pub(crate) fn debootstrap_entries(
+    project: &Project,
+    cfg: &debootstrap::Cfg,
+) -> Vec<Entry> {
+    cfg.iter()
+        .flat_map(|os| {
+            let version = os
+                .get_version()
+                .split_ascii_whitespace()
+                .next()
+                .unwrap_or_default();
+
+            os.get_tag()
+                .iter()
+                .map(move |tag| Entry {
+                    os: project.get_os(),
+                    version: version.into(),
+                    codename: os.get_codename().into(),
+                    series: os.get_series().into(),
+                    arch: tag.get_arch().into(),
+                    deb_arch: tag.get_deb_arch().into(),
+                    platform: oci_platform(tag.get_arch()),
+                    tag: None,
+                    date: os.get_date().into(),
+                    catalog: project
+                        .get_catalog()
+                        .to_string(),
+                })
+        })
+        .collect()
+}
+
+pub(crate) fn disk_entries(cfg: &DiskV1) -> Vec<Entry> {
+    cfg.iter()
+        .flat_map(|os| {
+            os.get_disk()
+                .iter()
+                .map(|disk| Entry {
+                    os: "debian",
+                    version: os.get_version().into(),
+                    codename: os.get_codename().into(),
+                    series: os
+                        .get_codename()
+                        .to_ascii_lowercase(),
+                    arch: disk.get_arch().into(),
+                    deb_arch: disk
+                        .get_deb_arch()
+                        .clone()
+                        .unwrap_or_else(|| debian_arch(disk.get_arch())),
+                    platform: oci_platform(disk.get_arch()),
+                    tag: disk
+                        .get_tag()
+                        .clone()
+                        .filter(|t| !t.trim().is_empty()),
+                    date: disk.get_date().into(),
+                    catalog: Kind::OldOldDebian.to_string(),
+                })
+        })
+        .collect()
+}
+
+/// `os`: None => all projects
+fn entries(os: Option<&str>) -> anyhow::Result<Vec<Entry>> {
+    let projects = match os.map(str::trim) {
+        Some(os) if !os.is_empty() => match Project::find(os) {
+            Some(p) => vec![p],
+            _ => bail!(
+                "Unknown OS: {os}, valid: {}",
+                PROJECTS
+                    .iter()
+                    .map(|p| *p.get_os())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        },
+        _ => PROJECTS.iter().collect(),
+    };
+
+    let mut entries = Vec::with_capacity(256);
+    for project in projects {
+        if project.get_catalog() == &Kind::Debian {
+            entries.extend(disk_entries(&catalog::load(Kind::OldOldDebian)?))
+        }
+        let cfg = catalog::load(*project.get_catalog())?;
+        entries.extend(debootstrap_entries(project, &cfg))
+    }
+    Ok(entries)
+}
+
+pub(crate) fn print_list(
+    os: Option<&str>,
+    format: ListFormat,
+) -> anyhow::Result<()> {
+    let entries = entries(os)?;
+
+    let out = match format {
+        ListFormat::Table => table(&entries),
+        ListFormat::Json => serde_json::to_string_pretty(&entries)?,
+        ListFormat::Ron => ron::ser::to_string_pretty(&entries, Default::default())?,
+    };
+    println!("{out}");
+    Ok(())
+}
+
+fn table(entries: &[Entry]) -> String {
+    const HEADER: [&str; 8] = [
+        "OS", "VERSION", "SERIES", "ARCH", "DEB-ARCH", "PLATFORM", "TAG", "DATE",
+    ];
+    let rows = entries
+        .iter()
+        .map(|e| {
+            [
+                e.os,
+                &e.version,
+                &e.series,
+                &e.arch,
+                &e.deb_arch,
+                &e.platform,
+                e.tag.as_deref().unwrap_or("-"),
+                &e.date,
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    let mut widths = HEADER.map(str::len);
+    for row in &rows {
+        for (w, cell) in widths.iter_mut().zip(row) {
+            *w = (*w).max(cell.len())
+        }
+    }
+
+    let mut out = String::with_capacity((rows.len() + 1) * 80);
+    for row in [HEADER].iter().chain(&rows) {
+        let line = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, w)| format!("{cell:<w$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out.pop();
+    out
+}
+
+/// Explains why `--ver` & `--arch` select nothing & lists the closest valid choices.
+pub(crate) fn empty_selection(
+    entries: &[Entry],
+    os: &str,
+    ver: &str,
+    arch: Option<&str>,
+) -> anyhow::Error {
+    let versions = entries
+        .iter()
+        .map(|e| e.version.as_str())
+        .collect::<Vec<_>>();
+
+    if !versions.contains(&ver) {
+        return anyhow::anyhow!(
+            "{os} has no version {ver:?}, the closest: {} (see also: --list {os})",
+            closest(ver, &versions).join(", ")
+        );
+    }
+
+    let arches = entries
+        .iter()
+        .filter(|e| e.version == ver)
+        .flat_map(|e| [e.arch.as_str(), e.deb_arch.as_str()])
+        .collect::<Vec<_>>();
+    let arch = arch.unwrap_or_default();
+    anyhow::anyhow!(
+        "{os} {ver} has no arch {arch:?}, the closest: {} (see also: --list {os})",
+        closest(arch, &arches).join(", ")
+    )
+}
+
+/// Up to 5 candidates, sorted by edit distance
+fn closest<'a>(input: &str, candidates: &[&'a str]) -> Vec<&'a str> {
+    let mut sorted = Vec::with_capacity(candidates.len());
+    for c in candidates {
+        if !sorted.contains(c) {
+            sorted.push(*c)
+        }
+    }
+    sorted.sort_by_cached_key(|c| levenshtein(input, c));
+    sorted.truncate(5);
+    sorted
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut prev = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            cur[j + 1] = (prev[j] + cost)
+                .min(prev[j + 1] + 1)
+                .min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_and_suggest() -> anyhow::Result<()> {
+        let devuan = Project::find("devuan").expect("No devuan");
+        let cfg = ron::from_str(Kind::Devuan.embedded())?;
+        let entries = debootstrap_entries(devuan, &cfg);
+        let x64 = entries
+            .iter()
+            .find(|e| e.version == "5" && e.arch == "x64")
+            .expect("No devuan 5 x64");
+        assert_eq!(x64.platform, "linux/amd64");
+
+        let err = empty_selection(&entries, "devuan", "6", None).to_string();
+        assert!(err.contains("the closest: 4, 5, unstable"), "{err}");
+
+        let err = empty_selection(&entries, "devuan", "5", Some("amd65"));
+        assert!(
+            err.to_string()
+                .contains("the closest: amd64"),
+            "{err}"
+        );
+
+        let disk = disk_entries(&ron::from_str(Kind::OldOldDebian.embedded())?);
+        assert!(disk
+            .iter()
+            .any(|e| e.tag.as_deref() == Some("base") && e.deb_arch == "i386"));
+
+        let table = table(&entries[..1]);
+        assert!(table.starts_with("OS      VERSION"), "{table}");
+        Ok(())
+    }
+}
@@ -1,8 +1,11 @@
 pub(crate) mod build_rootfs;
 mod compression;
 pub(crate) mod docker;
+pub(crate) mod lint;
+pub(crate) mod list;
 pub(crate) mod old_old_debian;
 pub(crate) mod pool;
+pub(crate) mod verify;
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,201 @@
+//! Checks the downloaded base tgz against the indices of debian-archive,
+//! i.e., `md5sum.txt` in the disks dir & `dists/{series}/Release`.
+
+use crate::{cli::Cli, docker::repo::Repository, http};
+use anyhow::{bail, ensure};
+use hash_digest::multi::{self, Algorithm};
+use std::{collections::BTreeMap, path::Path};
+use url::Url;
+
+/// e.g., `{Md5: "0123..."}`
+type Expected = BTreeMap<Algorithm, String>;
+
+/// Returns an error if the hash of `tgz` does not match the upstream indices, or an index cannot be fetched or parsed.
+///
+/// If no index lists the file, it is an error unless `--allow-unlisted-tgz` (only logs a warning).
+pub(crate) fn verify_base_tgz(r: &Repository, tgz: &Path) -> anyhow::Result<()> {
+    let url = r
+        .get_url()
+        .as_ref()
+        .expect("Empty URL");
+
+    let mut expected = Expected::new();
+    if let Some(md5sum_url) = r.get_md5sum_url() {
+        let file_name = url
+            .path_segments()
+            .and_then(|mut x| x.next_back())
+            .unwrap_or_default();
+        if let Some(content) = fetch(md5sum_url)? {
+            if let Some(hex) = parse_md5sum_txt(&content, file_name)? {
+                expected.insert(Algorithm::Md5, hex);
+            }
+        }
+    }
+    if let Some((release_url, path)) = release_url(url, r.get_series()) {
+        if let Some(content) = fetch(&release_url)? {
+            expected.extend(parse_release(&content, &path)?);
+        }
+    }
+
+    if expected.is_empty() {
+        let msg = format!("{url} is not listed in md5sum.txt or Release");
+        ensure!(
+            Cli::static_allow_unlisted_tgz(None),
+            "{msg}, use --allow-unlisted-tgz to skip checking"
+        );
+        log::warn!("{msg}, skip checking");
+        return Ok(());
+    }
+
+    log::info!("Checking {tgz:?}: {expected:?}");
+    if let Err(e) = multi::check(tgz, &expected) {
+        bail!("Refusing to unpack {tgz:?} ({url}): {e}")
+    }
+    Ok(())
+}
+
+/// None: the index does not exist (404).
+fn fetch(url: &Url) -> anyhow::Result<Option<String>> {
+    log::debug!("fetching {url}");
+    let content = http::client().get_string_if_found(url.as_str())?;
+    if content.is_none() {
+        log::info!("{url} does not exist")
+    }
+    Ok(content)
+}
+
+/// e.g., (https://archive.debian.org/debian/dists/hamm/Release, "main/disks-i386/2.0.10_1998-07-21/base2_0.tgz")
+fn release_url(tgz_url: &Url, series: &str) -> Option<(Url, String)> {
+    let dists = format!("/dists/{series}/");
+    let s = tgz_url.as_str();
+    let idx = s.find(&dists)? + dists.len();
+
+    let release = Url::parse(&format!("{}Release", &s[..idx])).ok()?;
+    Some((release, s[idx..].to_owned()))
+}
+
+/// md5sum.txt: `hex  ./base2_0.tgz`
+///
+/// Returns an error if the entry of `file_name` has an invalid hash.
+fn parse_md5sum_txt(
+    content: &str,
+    file_name: &str,
+) -> anyhow::Result<Option<String>> {
+    let entry = content
+        .lines()
+        .find_map(|line| {
+            let (hex, path) = line
+                .trim()
+                .split_once(char::is_whitespace)?;
+            let path = path
+                .trim_start()
+                .trim_start_matches('*');
+
+            (path.trim_start_matches("./") == file_name).then_some(hex)
+        });
+
+    match entry {
+        Some(hex) if is_hex(hex) => Ok(Some(hex.to_owned())),
+        Some(hex) => bail!("Invalid md5sum of {file_name}: {hex:?}"),
+        _ => Ok(None),
+    }
+}
+
+/// Release:
+///
+/// ```txt
+/// MD5Sum:
+///  d41d8cd98f00b204e9800998ecf8427e 123 main/binary-i386/Packages
+/// SHA256:
+///  ...
+/// ```
+///
+/// Returns an error if an entry of `path` has an invalid hash.
+fn parse_release(content: &str, path: &str) -> anyhow::Result<Expected> {
+    let mut expected = Expected::new();
+    let mut algorithm = None;
+
+    for line in content.lines() {
+        if !line.starts_with(char::is_whitespace) {
+            algorithm = line
+                .strip_suffix(':')
+                .and_then(|x| x.parse::<Algorithm>().ok());
+            continue;
+        }
+        let Some(algo) = algorithm else { continue };
+
+        if let [hex, _size, p] = line
+            .split_whitespace()
+            .collect::<Vec<_>>()[..]
+        {
+            if p == path {
+                ensure!(
+                    is_hex(hex),
+                    "Invalid {algo:?} of {path} in Release: {hex:?}"
+                );
+                expected.insert(algo, hex.to_owned());
+            }
+        }
+    }
+    Ok(expected)
+}
+
+fn is_hex(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes()
+            .all(|b| b.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_indices() -> anyhow::Result<()> {
+        let md5sum = "\
+6a4b21a3a5b7c6d1e0f9a8b7c6d5e4f3  ./base2_0.tgz
+0123456789abcdef0123456789abcdef  ./rescue.bin
+";
+        assert_eq!(
+            parse_md5sum_txt(md5sum, "base2_0.tgz")?.as_deref(),
+            Some("6a4b21a3a5b7c6d1e0f9a8b7c6d5e4f3")
+        );
+        assert_eq!(parse_md5sum_txt(md5sum, "base2_1.tgz")?, None);
+        assert!(parse_md5sum_txt("xyz  ./base2_0.tgz", "base2_0.tgz").is_err());
+
+        let release = "\
+Origin: Debian
+MD5Sum:
+ 0123456789abcdef0123456789abcdef 1234 main/disks-i386/current/base2_2.tgz
+ fedcba9876543210fedcba9876543210 5678 main/binary-i386/Packages
+SHA1:
+ da39a3ee5e6b4b0d3255bfef95601890afd80709 1234 main/disks-i386/current/base2_2.tgz
+";
+        let expected =
+            parse_release(release, "main/disks-i386/current/base2_2.tgz")?;
+        assert_eq!(expected.len(), 2);
+        assert_eq!(
+            expected[&Algorithm::Md5],
+            "0123456789abcdef0123456789abcdef"
+        );
+        assert_eq!(
+            parse_release(release, "main/binary-i386/Packages")?.len(),
+            1
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn release_url_of_tgz() {
+        let tgz = Url::parse(
+            "https://archive.debian.org/debian/dists/hamm/main/disks-i386/2.0.10_1998-07-21/base2_0.tgz",
+        )
+        .expect("Invalid URL");
+        let (url, path) = release_url(&tgz, "hamm").expect("No dists");
+        assert_eq!(
+            url.as_str(),
+            "https://archive.debian.org/debian/dists/hamm/Release"
+        );
+        assert_eq!(path, "main/disks-i386/2.0.10_1998-07-21/base2_0.tgz");
+    }
+}
@@ -1,24 +1,29 @@
-use super::{deser_ron, TarFile, ZstdOp};
+use super::{deser_ron, ArchiveOp, TarFile, ARCHIVE_OP_RON};
 use crate::{
-    cfg::digest::{self, DockerMirror, FileMirror},
-    docker::{
-        get_oci_platform,
-        repo::Repository,
-        repo_map::{MainRepo, RepoMap},
+    cfg::{
+        digest::{self, DockerMirror, FileMirror},
+        project::Project,
+        registry,
     },
-    task::old_old_debian::{
-        docker_task::{
-            self, platforms_ron_name, repo_digests_filename, MainRepoDigests,
+    docker::{get_oci_platform, repo::Repository, repo_map::RepoMap},
+    task::{
+        compression::ARCHIVE_DIGESTS,
+        old_old_debian::{
+            docker_task::{
+                self, platforms_ron_name, repo_digests_filename, MainRepoDigests,
+            },
+            BUILD_TIME_RON,
         },
-        BUILD_TIME_RON,
     },
 };
 use byteunit::ByteUnit;
+use hash_digest::multi::{self, Algorithm};
+use repack::dict::ZstdDict;
 use ron::{extensions::Extensions, ser::PrettyConfig};
 use std::{
     env,
     ffi::OsStr,
-    fs, io,
+    fs, io, iter,
     path::{Path, PathBuf},
 };
 use url::Url;
@@ -153,28 +158,40 @@ fn archive_file_cfg(
     tar_size: u64,
     tag_name: &str,
 ) -> Result<digest::ArchiveFile, anyhow::Error> {
-    let ZstdOp {
-        path: zstd_path,
-        lv: zstd_lv,
-    } = deser_ron::<ZstdOp<PathBuf>, _>(&docker_dir.join("zstd.ron"))?;
-
-    let zstd_filename = zstd_path
+    let op = deser_ron::<ArchiveOp<PathBuf>, _>(&docker_dir.join(ARCHIVE_OP_RON))?;
+    let zstd = op.zstd_params();
+    let saved_digests = op.saved_digests();
+    let ArchiveOp {
+        path: archive_path,
+        codec,
+        lv,
+        dict: dict_path,
+        ..
+    } = op;
+
+    let archive_filename = archive_path
         .file_name()
-        .expect("Invalid ZSTD file");
-    let lossy_filename = zstd_filename.to_string_lossy();
-
-    let zstd_digests = ["blake3", "sha256"].map(|algo| {
-        log::info!("Getting {algo} checksum ..., zstd_path: {zstd_path:?}");
-        let hex = match algo {
-            "blake3" => hash_digest::blake3::get(&zstd_path),
-            _ => hash_digest::sha256::get(&zstd_path),
+        .expect("Invalid archive file");
+    let lossy_filename = archive_filename.to_string_lossy();
+
+    // Computed while the archive was written, older archive.ron files do not have them.
+    let hex_map = match saved_digests {
+        Some(d) => d,
+        _ => {
+            log::info!(
+                "Getting {ARCHIVE_DIGESTS:?} checksums ..., archive_path: {archive_path:?}"
+            );
+            multi::get(&archive_path, ARCHIVE_DIGESTS)?
         }
-        .expect("Failed to get hash digest");
+    };
 
-        let cmt = match algo {
-            "blake3" => {
-                format!(
-                    r##"Usage:
+    let archive_digests = hex_map
+        .iter()
+        .map(|(algo, hex)| {
+            let cmt = match algo {
+                Algorithm::Blake3 => {
+                    format!(
+                        r##"Usage:
     # run apt as root (i.e., +sudo/+doas)
     apt install b3sum
 
@@ -183,49 +200,49 @@ fn archive_file_cfg(
     b3sum --check blake3.txt
 
 "##
-                )
-            }
-            _ => {
-                format!(
-                    r##"Usage:
-    # check sha256 hash
-    echo '{hex}  {lossy_filename}' > sha256.txt
-    sha256sum --check sha256.txt
+                    )
+                }
+                _ => {
+                    format!(
+                        r##"Usage:
+    # check {algo} hash
+    echo '{hex}  {lossy_filename}' > {algo}.txt
+    {algo}sum --check {algo}.txt
 
 "##
-                )
-            }
-        };
-
-        digest::HashDigest::builder()
-            .algorithm(algo)
-            .cmt(cmt)
-            .hex(hex.to_string())
-            .build()
-    });
-    let zstd_meta = zstd_path.metadata()?;
-    let zstd_size = zstd_meta.len();
-    let readable_size = ByteUnit::new(zstd_size);
+                    )
+                }
+            };
+
+            digest::HashDigest::builder()
+                .algorithm(algo.as_str())
+                .cmt(cmt)
+                .hex(hex.to_string())
+                .build()
+        });
+    let archive_meta = archive_path.metadata()?;
+    let archive_size = archive_meta.len();
+    let readable_size = ByteUnit::new(archive_size);
 
-    let tar_and_zstd_size = ByteUnit::new(tar_size + zstd_size);
+    let tar_and_archive_size = ByteUnit::new(tar_size + archive_size);
     let tar_readable = ByteUnit::new(tar_size);
 
     let file_size_cmt = format!(
         r#"Ideally:
-    zstd size => download size (i.e., Consumes {readable_size} of traffic)
+    {codec} size => download size (i.e., Consumes {readable_size} of traffic)
     tar size => uncompressed size (Actually, the extracted content is >= {tar_readable})
-    zstd + tar size ~= space occupation for initial installation
-        (i.e., Requires at least {tar_and_zstd_size} of disk storage space, but actually needs more)
+    {codec} + tar size ~= space occupation for initial installation
+        (i.e., Requires at least {tar_and_archive_size} of disk storage space, but actually needs more)
 
 "#
     );
 
     let file_size = digest::FileSize::builder()
-        .bytes(zstd_size)
+        .bytes(archive_size)
         .cmt(file_size_cmt)
         .readable(readable_size)
-        .kib((!readable_size.is_kib()).then(|| ByteUnit::new_kib(zstd_size)))
-        .mib((!readable_size.is_mib()).then(|| ByteUnit::new_mib(zstd_size)))
+        .kib((!readable_size.is_kib()).then(|| ByteUnit::new_kib(archive_size)))
+        .mib((!readable_size.is_mib()).then(|| ByteUnit::new_mib(archive_size)))
         .tar_bytes(tar_size)
         .tar_readable(tar_readable)
         .build();
@@ -236,11 +253,8 @@ fn archive_file_cfg(
         _ => "",
     };
 
-    let gh_repo = match r.get_project() {
-        &"debian" | &"debian-sid" => "debian-museum",
-        // &"ubuntu"
-        _ => "ubuntu-museum",
-    };
+    let gh_repo = Project::from_name(r.get_project())
+        .map_or("debian-museum", |p| p.get_gh_repo());
 
     // github.com/2cd/debian-museum/releases/download
     let gh_url = format!(
@@ -248,17 +262,25 @@ fn archive_file_cfg(
         owner = r.get_owner()
     );
 
-    let zstd_mirror = [("github", gh_url)].map(|(name, u)| {
-        let (tag_prefix, tag) = match r.get_tag() {
-            Some(t) => ("-", *t),
-            None => ("", ""),
-        };
-
-        let url_str = format!(
+    let (tag_prefix, tag) = match r.get_tag() {
+        Some(t) => ("-", *t),
+        None => ("", ""),
+    };
+    let release_url = |u: &str, fname: &OsStr| {
+        format!(
             "https://{u}/{}{tag_prefix}{tag}/{}",
             r.get_version(),
-            zstd_filename.to_string_lossy()
-        );
+            fname.to_string_lossy()
+        )
+    };
+
+    let dict_file = dict_path
+        .as_deref()
+        .map(|p| dict_file_cfg(p, &lossy_filename, |f| release_url(&gh_url, f)))
+        .transpose()?;
+
+    let archive_mirror = [("github", &gh_url)].map(|(name, u)| {
+        let url_str = release_url(u, archive_filename);
 
         let cmt = format!(
             r##"Usage:
@@ -267,7 +289,7 @@ fn archive_file_cfg(
     curl -LO '{url_str}'
 
     # run gnutar or bsdtar (libarchive-tools) as root (e.g., doas tar -xvf file.tar.zst)
-    tar -C {tag_name} -xf {zstd_filename:?}
+    tar -C {tag_name} -xf {archive_filename:?}
 
     # run apt as root (i.e., +sudo/+doas)
     apt install systemd-container qemu-user-static
@@ -284,32 +306,85 @@ fn archive_file_cfg(
             .build()
     });
     let archive_file = digest::ArchiveFile::builder()
-        .name(zstd_filename)
-        .digest(zstd_digests)
-        .modified_time(zstd_meta.modified()?)
-        .zstd(
-            digest::Zstd::builder()
-                .level(zstd_lv)
-                .build(),
-        )
+        .name(archive_filename)
+        .digest(archive_digests.collect::<Vec<_>>())
+        .modified_time(archive_meta.modified()?)
+        .compression(codec.compression(lv, &zstd, dict_file.is_some()))
         .size(file_size)
-        .mirror(zstd_mirror)
+        .mirror(archive_mirror);
+
+    Ok(match dict_file {
+        Some(d) => archive_file.dict(d).build(),
+        _ => archive_file.build(),
+    })
+}
+
+/// The sidecar zstd dictionary of `archive_filename`.
+fn dict_file_cfg(
+    dict_path: &Path,
+    archive_filename: &str,
+    url: impl Fn(&OsStr) -> String,
+) -> anyhow::Result<digest::DictFile> {
+    let dict_filename = dict_path
+        .file_name()
+        .expect("Invalid dict file");
+    let lossy_dict = dict_filename.to_string_lossy();
+
+    let digests = multi::get(dict_path, Algorithm::DEFAULT)?
+        .into_iter()
+        .map(|(algo, hex)| {
+            digest::HashDigest::builder()
+                .algorithm(algo.as_str())
+                .hex(hex.to_string())
+                .build()
+        })
+        .collect::<Vec<_>>();
+
+    let url_str = url(dict_filename);
+    let cmt = format!(
+        r##"Usage:
+    curl -LO '{url_str}'
+
+    # decompress with the dictionary
+    zstd -d -D {lossy_dict:?} {archive_filename:?}
+
+    # or run gnutar as root
+    tar -I 'zstd -d -D {lossy_dict}' -xf {archive_filename:?}
+
+"##
+    );
+
+    let dict_file = digest::DictFile::builder()
+        .name(dict_filename)
+        .bytes(dict_path.metadata()?.len())
+        .id(ZstdDict::load(dict_path)?.id())
+        .cmt(cmt)
+        .digest(digests)
+        .mirror([FileMirror::builder()
+            .name("github")
+            .url(Url::parse(&url_str)?)
+            .build()])
         .build();
-    Ok(archive_file)
+    Ok(dict_file)
 }
 
 fn update_docker_cfg(docker_dir: &Path, r: &Repository<'_>) -> digest::Docker {
-    let docker_mirrors =
-        [("ghcr", "ghcr.ron"), ("reg", "reg.ron")].map(|(name, ron)| {
-            let repositories = deser_reg_ron(docker_dir.join(ron));
+    let targets = registry::targets();
+    let docker_mirrors = targets
+        .iter()
+        .map(|t| {
+            let repositories = deser_reg_ron(docker_dir.join(t.ron_filename()));
 
             DockerMirror::builder()
-                .name(name)
+                .name(t.get_name())
                 .repositories(repositories)
                 .build()
-        });
+        })
+        .collect::<Vec<_>>();
 
-    let repo_digest_file = docker_dir.join(repo_digests_filename("ghcr.ron"));
+    // The primary target, e.g., ghcr.repo-digests
+    let repo_digest_file =
+        docker_dir.join(repo_digests_filename(&targets[0].ron_filename()));
     let docker = digest::Docker::builder()
         .platform(get_oci_platform(r.get_arch()))
         .mirror(docker_mirrors)
@@ -334,18 +409,25 @@ pub(crate) fn init_root_cfg(
     digest_os_config: &mut [digest::OS; 1],
 ) -> Result<(), anyhow::Error> {
     let docker_ron = r.docker_ron_filename();
-    let mut ghcr_repos = MainRepoDigests::new();
-    let mut reg_repos = MainRepoDigests::new();
+    let targets = registry::targets();
+    // e.g., [ghcr_repos, reg_repos]
+    let mut target_repos = vec![MainRepoDigests::new(); targets.len()];
 
-    for k in deser_ron::<RepoMap, _>(&docker_ron)?
+    for k in RepoMap::deser(&docker_ron)?
         .clone()
         .into_keys()
     {
-        match k {
-            MainRepo::Ghcr(v) => ghcr_repos.push(v),
-            MainRepo::Reg(v) => reg_repos.push(v),
+        match targets
+            .iter()
+            .position(|t| t.get_name() == k.get_target())
+        {
+            Some(i) => target_repos[i].push(k.get_repo().to_owned()),
+            _ => log::warn!("Unknown registry target: {k:?}"),
         }
     }
+    for repos in &mut target_repos {
+        repos.sort_unstable()
+    }
 
     let repo_digest_map = deser_ron::<docker_task::MainRepoDigestMap, _>(
         docker_task::repo_digests_filename(&docker_ron),
@@ -354,10 +436,14 @@ pub(crate) fn init_root_cfg(
     let cmt = format!(
         r##"Usage:
     docker run --pull=always -it --rm {}"##,
-        ghcr_repos[0]
+        target_repos
+            .first()
+            .and_then(|r| r.first())
+            .map_or("", |r| r.as_str())
     );
 
-    let mirrors = [("ghcr", ghcr_repos), ("reg", reg_repos)].map(|(name, repos)| {
+    let mirrors = iter::zip(targets, target_repos).map(|(t, repos)| {
+        let name = t.get_name();
         DockerMirror::builder()
             .name(name)
             .repositories(repos)
@@ -379,7 +465,7 @@ pub(crate) fn init_root_cfg(
     let platforms = deser_ron::<Vec<String>, _>(platforms_ron_name(&docker_ron))?;
     let os_docker = digest::Docker::builder()
         .oci_platforms(platforms)
-        .mirror(mirrors)
+        .mirror(mirrors.collect::<Vec<_>>())
         .cmt(cmt)
         .build();
 
@@ -1,14 +1,18 @@
 use crate::{
+    cfg::{digest::DockerMirror, registry as registry_cfg},
     command::{self, run_and_get_stdout},
     docker::{
+        oci::{self, Annotations, Descriptor, Image, Index, LayerCodec, Layout},
+        registry::{self, Reference},
         repo::Repository,
-        repo_map::{MainRepo, RepoMap},
+        repo_map::RepoMap,
         DOCKER_FILE_FOR_NEW_DISTROS, DOCKER_FILE_OLD_CONTENT, DOCKER_IGNORE_CONTENT,
     },
     task::{
-        docker::{run_docker_build, run_docker_push},
+        docker::{run_docker_build, run_docker_push, save_tags},
         old_old_debian::{
-            self, deser_ron, digest_cfg::DISTROS_THAT_REQUIRE_XTERM, TarFile,
+            deser_ron, digest_cfg::DISTROS_THAT_REQUIRE_XTERM, TarFile,
+            BUILD_TIME_RON,
         },
         pool::wait_process,
     },
@@ -16,7 +20,11 @@ use crate::{
 use ahash::{HashMapExt, HashSetExt};
 use anyhow::{bail, Context};
 use log_l10n::level::color::OwoColorize;
-use std::{collections::BTreeSet, fs, io, path::Path};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+    path::{Path, PathBuf},
+};
 use tinyvec::TinyVec;
 
 pub(crate) type MainRepoDigests = TinyVec<[String; 4]>;
@@ -36,12 +44,7 @@ where
             continue;
         }
 
-        use MainRepo::*;
-        let repo = match k {
-            Reg(s) => s,
-            Ghcr(s) => s,
-        };
-        repo_set.insert(rsplit_colon(repo, &mut arr));
+        repo_set.insert(rsplit_colon(k.get_repo(), &mut arr));
     }
 
     for i in repo_set {
@@ -60,7 +63,7 @@ fn get_repo_map_from_ron(
         Some(v) if Path::new(&v).exists() => v,
         _ => bail!("The docker(tags).ron file does not exist and you may need to rebuild it using `--build`."),
     };
-    let map = old_old_debian::deser_ron::<RepoMap, _>(&docker_ron)?;
+    let map = RepoMap::deser(&docker_ron)?;
     Ok((map, docker_ron))
 }
 
@@ -112,10 +115,7 @@ where
         let mut args =
             TinyVec::<[&str; 24]>::from_iter(["manifest", "create", "--amend"]);
 
-        let (repo, digest_map_key) = match k {
-            MainRepo::Reg(s) => (s, "reg"),
-            MainRepo::Ghcr(s) => (s, "ghcr"),
-        };
+        let (repo, digest_map_key) = (k.get_repo(), k.get_target());
 
         args.push(repo);
 
@@ -203,8 +203,9 @@ where
         } = r.base_tar_name()?;
 
         let is_new = !matches!(r.get_series().as_str(), s if DISTROS_THAT_REQUIRE_XTERM.contains(&s));
+        let labels = r.oci_labels(build_time(docker_dir)?.as_deref());
 
-        create_docker_file(docker_dir, tar_fname, is_new)?;
+        create_docker_file(docker_dir, tar_fname, is_new, &labels)?;
 
         run_docker_build(r, &mut children, docker_dir, &mut tag_map)?;
         treeset.insert(r.oci_platform());
@@ -226,11 +227,239 @@ where
     Ok(())
 }
 
-/// Replaces "base.tar" in the default DOCKER_FILE_CONTENT with tar_fname(e.g., 2.2_potato_x86_base_2001-06-14.tar), and finally write.
+/// Same as [docker_build], but writes an OCI image layout (e.g., oci/2.2-potato-base/) instead of running `docker build`.
+///
+/// index.json refers to each arch image by its tags, and each main repo (e.g., reg.tmoe.me:2096/debian/potato:base) by a nested index.
+pub(crate) fn oci_build<'a, I>(repos: I, codec: LayerCodec) -> anyhow::Result<()>
+where
+    I: IntoIterator<Item = &'a Repository<'a>>,
+{
+    let mut tag_map = RepoMap::default();
+    let mut docker_ron_name = String::with_capacity(64);
+    let mut treeset = BTreeSet::new();
+    let mut layout = None;
+
+    // e.g., {"reg.tmoe.me:2096/debian/potato:x86-base": Descriptor{platform: linux/386}}
+    let mut images = BTreeMap::new();
+    // {manifest digest: annotations}
+    let mut labels = BTreeMap::new();
+    let mut manifests = Vec::with_capacity(32);
+
+    for r in repos {
+        if layout.is_none() {
+            docker_ron_name = r.docker_ron_filename();
+            layout = Some(Layout::create(oci_dir(&docker_ron_name))?);
+        }
+        let Some(layout) = &layout else {
+            unreachable!()
+        };
+
+        let TarFile {
+            ref tar_path,
+            ref docker_dir,
+            ..
+        } = r.base_tar_name()?;
+
+        let is_new = !matches!(r.get_series().as_str(), s if DISTROS_THAT_REQUIRE_XTERM.contains(&s));
+        let created = build_time(docker_dir)?;
+
+        let image = Image {
+            platform: r.oci_platform(),
+            env: oci::dockerfile_env(!is_new),
+            cmd: oci::DOCKERFILE_CMD,
+            labels: Some(r.oci_labels(created.as_deref())),
+            created,
+            codec,
+        };
+        let desc = layout.add_image(tar_path, &image)?;
+        labels.insert(desc.digest.clone(), image.labels.unwrap_or_default());
+
+        for tag in save_tags(r, docker_dir, &mut tag_map)?
+            .iter()
+            .flatten()
+        {
+            push_ref(&mut manifests, &desc, tag);
+            images.insert(tag.to_owned(), desc.clone());
+        }
+        treeset.insert(r.oci_platform());
+    }
+
+    let Some(layout) = layout else {
+        bail!("No images to write")
+    };
+
+    let mut main_repos = tag_map
+        .iter()
+        .collect::<Vec<_>>();
+    main_repos.sort_by_key(|(k, _)| {
+        (registry_cfg::position(k.get_target()), k.get_repo())
+    });
+
+    for (k, tags) in main_repos {
+        let repo = k.get_repo();
+        let arch_manifests = tags
+            .iter()
+            .filter_map(|t| images.get(t))
+            .map(|d| Descriptor {
+                annotations: None,
+                ..d.clone()
+            })
+            .collect::<Vec<_>>();
+        // e.g., created & version, but not the title of each arch
+        let annotations = oci::common_annotations(
+            &arch_manifests
+                .iter()
+                .filter_map(|d| labels.get(&d.digest).cloned())
+                .collect::<Vec<Annotations>>(),
+        );
+        let index = Index {
+            annotations,
+            ..Index::new(arch_manifests)
+        };
+        let desc = layout.write_json_blob(oci::MEDIA_TYPE_INDEX, &index)?;
+        push_ref(&mut manifests, &desc, repo);
+    }
+
+    log::info!(
+        "writing to: {:?}",
+        layout
+            .get_dir()
+            .join("index.json")
+    );
+    layout.write_index(&Index::new(manifests))?;
+
+    fs::write(&docker_ron_name, ron::to_string(&tag_map)?)?;
+    fs::write(
+        platforms_ron_name(&docker_ron_name),
+        ron::to_string(&treeset)?,
+    )?;
+    Ok(())
+}
+
+/// docker_dir/build-time.ron => RFC 3339, e.g., 2024-06-10T08:00:00Z
+fn build_time(docker_dir: &Path) -> anyhow::Result<Option<String>> {
+    let p = docker_dir.join(BUILD_TIME_RON);
+    if !p.exists() {
+        return Ok(None);
+    }
+    let time = deser_ron::<time::OffsetDateTime, _>(p)?
+        .format(&time::format_description::well_known::Rfc3339)?;
+    Ok(Some(time))
+}
+
+/// e.g., 2.2-potato-base.ron => oci/2.2-potato-base
+fn oci_dir(docker_ron_name: &str) -> PathBuf {
+    Path::new("oci").join(docker_ron_name.trim_end_matches(".ron"))
+}
+
+/// Pushes the OCI layout (see [oci_build]) to the registry targets (e.g., ghcr & reg) without docker, i.e., [docker_push] + [create_manifest] + [pull_image_and_create_repo_digests].
+///
+/// The digests are returned by the registry. The credentials are read from the env, e.g., GET_CTR_REG_USER & GET_CTR_REG_TOKEN.
+pub(crate) fn oci_push<'a, I>(repos: I) -> anyhow::Result<()>
+where
+    I: IntoIterator<Item = &'a Repository<'a>>,
+{
+    let repos = repos
+        .into_iter()
+        .collect::<Vec<_>>();
+    let (map, ron_filename) = get_repo_map_from_ron(repos.first().copied())?;
+    let layout = Layout::open(oci_dir(&ron_filename))?;
+    let index = layout.read_index()?;
+
+    let mut repo_digest_map = ahash::HashMap::with_capacity(4);
+    // e.g., {"reg.tmoe.me:2096/debian/potato:x86-base": "reg.tmoe.me:2096/debian/potato@sha256:123456abcdef"}
+    let mut tag_digests = ahash::HashMap::with_capacity(32);
+
+    for (k, tags) in map.iter() {
+        #[cfg(debug_assertions)]
+        if k.is_ghcr() {
+            continue;
+        }
+
+        let (repo, digest_map_key) = (k.get_repo(), k.get_target());
+        let plain_http =
+            registry_cfg::find(digest_map_key).is_some_and(|t| *t.get_plain_http());
+        let client = registry::Client::new(
+            &DockerMirror::builder()
+                .name(digest_map_key)
+                .repositories(MainRepoDigests::new())
+                .build()
+                .with_env_credentials(),
+        );
+
+        for tag in tags {
+            let desc = Layout::find_ref(&index, tag)
+                .with_context(|| format!("{tag} is not in the OCI layout"))?;
+            let reference = Reference::parse(tag).with_plain_http(plain_http);
+            let digest = client.push_image(&layout, desc, &reference)?;
+            tag_digests.insert(tag.as_str(), reference.repo_digest(&digest));
+        }
+
+        log::info!("pushing the image index: {repo}");
+        let desc = Layout::find_ref(&index, repo)
+            .with_context(|| format!("{repo} is not in the OCI layout"))?;
+        let reference = Reference::parse(repo).with_plain_http(plain_http);
+        let digest = client.push_image(&layout, desc, &reference)?;
+        update_repo_digest_map(
+            &mut repo_digest_map,
+            digest_map_key,
+            reference.repo_digest(&digest),
+        )
+    }
+
+    fs::write(
+        repo_digests_filename(&ron_filename),
+        ron::to_string(&repo_digest_map)?,
+    )?;
+
+    // Same as `docker inspect --format {{json .RepoDigests}}`
+    for r in repos {
+        let TarFile { docker_dir, .. } = r.base_tar_name()?;
+
+        for target in registry_cfg::targets() {
+            let fname = &target.ron_filename();
+            let cfg = deser_ron::<MainRepoDigests, _>(docker_dir.join(fname))?;
+            let Some(digest) = cfg
+                .first()
+                .and_then(|tag| tag_digests.get(tag.as_str()))
+            else {
+                continue;
+            };
+
+            let new_fname = repo_digests_filename(fname);
+            log::info!("writing to: {new_fname}");
+            let repo_digests = MainRepoDigests::from_iter([digest.to_owned()]);
+            fs::write(docker_dir.join(new_fname), ron::to_string(&repo_digests)?)?
+        }
+    }
+    Ok(())
+}
+
+/// Adds `desc` to index.json with the ref.name of `image_name`, unless the ref.name already exists (e.g., the same tag on reg & ghcr).
+fn push_ref(manifests: &mut Vec<Descriptor>, desc: &Descriptor, image_name: &str) {
+    let annotations = oci::ref_annotations(image_name);
+    let ref_name = &annotations[oci::ANNOTATION_REF_NAME];
+
+    if manifests.iter().any(|d| {
+        d.annotations
+            .as_ref()
+            .is_some_and(|a| &a[oci::ANNOTATION_REF_NAME] == ref_name)
+    }) {
+        log::debug!("skipping the duplicate ref.name: {image_name}");
+        return;
+    }
+    manifests.push(Descriptor {
+        annotations: Some(annotations),
+        ..desc.clone()
+    })
+}
+
+/// Replaces "base.tar" in the default DOCKER_FILE_CONTENT with tar_fname(e.g., 2.2_potato_x86_base_2001-06-14.tar), appends the `LABEL`s, and finally write.
 fn create_docker_file(
     docker_dir: &Path,
     tar_fname: &str,
     new: bool,
+    labels: &Annotations,
 ) -> Result<(), io::Error> {
     let docker_file = docker_dir.join("Dockerfile");
     log::debug!("docker_file: {:?}", docker_file);
@@ -240,10 +469,9 @@ fn create_docker_file(
     let docker_file_content =
         if new { DOCKER_FILE_FOR_NEW_DISTROS } else { DOCKER_FILE_OLD_CONTENT };
 
-    fs::write(
-        &docker_file,
-        docker_file_content.replace("base.tar", tar_fname),
-    )?;
+    let mut content = docker_file_content.replace("base.tar", tar_fname);
+    content.push_str(&dockerfile_labels(labels));
+    fs::write(&docker_file, content)?;
 
     let docker_ignore = docker_dir.join(".dockerignore");
     log::debug!("creating the .dockerignore");
@@ -252,6 +480,27 @@ fn create_docker_file(
     Ok(())
 }
 
+/// `LABEL key="value" ...`, e.g., org.opencontainers.image.version="2.2"
+///
+/// `\`, `"` & `$` (variable expansion) are escaped.
+fn dockerfile_labels(labels: &Annotations) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+    let pairs = labels
+        .iter()
+        .map(|(k, v)| {
+            let v = v
+                .replace('\\', r"\\")
+                .replace('"', r#"\""#)
+                .replace('$', r"\$");
+            format!(r#"{k}="{v}""#)
+        })
+        .collect::<Vec<_>>()
+        .join(" \\\n    ");
+    format!("\nLABEL {pairs}\n")
+}
+
 pub(crate) fn platforms_ron_name(docker_ron_name: &str) -> &str {
     docker_ron_name.trim_end_matches("on")
 }
@@ -265,12 +514,13 @@ where
     for r in repos {
         let TarFile { docker_dir, .. } = r.base_tar_name()?;
 
-        for fname in ["ghcr.ron", "reg.ron"] {
+        for target in registry_cfg::targets() {
             #[cfg(debug_assertions)]
-            if fname == "ghcr.ron" {
+            if target.get_name() == registry_cfg::GHCR {
                 continue;
             }
 
+            let fname = &target.ron_filename();
             let cfg = deser_ron::<MainRepoDigests, _>(docker_dir.join(fname))?;
             let repo = match cfg.first() {
                 Some(x) => x,
@@ -1,10 +1,14 @@
+mod checksum;
 pub(crate) mod digest_cfg;
 pub(crate) mod docker_task;
 
 use crate::{
-    cfg::{components::OLD_DEBIAN, mirror::debian_archive},
-    command::run_curl,
-    docker::repo::{create_src_list_link, Repository},
+    cfg::{components::OLD_DEBIAN, digest::Codec, mirror::MirrorVariant},
+    cli::Cli,
+    docker::repo::{
+        convert_to_url_str, create_src_list_link, list_stem, Repository,
+    },
+    http,
     task::{
         build_rootfs::{
             create_build_time_ron, get_mirror_dir_based_on,
@@ -12,16 +16,25 @@ use crate::{
         },
         compression::{
             decompress_gzip, extract_tar_as_root, pack_tar_as_root,
-            spawn_zstd_thread,
+            reproducible_epoch, spawn_compression_thread, ZstOutput,
+            ARCHIVE_DIGESTS,
         },
     },
 };
+use ahash::HashMapExt;
+use hash_digest::{multi::Digests, HexStr};
+use repack::{
+    compression::ZstdParams,
+    dict::{ZstdDict, DEFAULT_DICT_SIZE},
+};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::{
+    collections::BTreeMap,
     env,
     fs::{self, File},
     io,
     path::{Path, PathBuf},
+    sync::Arc,
 };
 pub(crate) const VERS: [&str; 4] = ["1.3", "2.0", "2.1", "2.2"];
 pub(crate) const BUILD_TIME_RON: &str = "build-time.ron";
@@ -82,20 +95,16 @@ pub(crate) fn obtain<'a, I: IntoIterator<Item = &'a Repository<'a>>>(
 
         let gz_fname = tar_fname.replace("tar", "tgz");
 
-        // curl
-
-        // #[cfg(debug_assertions)]
-        // log::info!("skip running curl");
-        // #[cfg(not(debug_assertions))]
-        {
-            log::debug!("running curl ...");
-            run_curl(
-                r.get_url()
-                    .as_ref()
-                    .expect("Empty URL"),
-                &gz_fname,
-            );
-        }
+        // download (resumable)
+        http::client().download(
+            r.get_url()
+                .as_ref()
+                .expect("Empty URL"),
+            Path::new(&gz_fname),
+        )?;
+        // md5sum.txt & Release
+        checksum::verify_base_tgz(r, Path::new(&gz_fname))?;
+
         // gz
         decompress_gzip(&gz_fname, tar_path)?;
 
@@ -117,7 +126,7 @@ pub(crate) fn obtain<'a, I: IntoIterator<Item = &'a Repository<'a>>>(
             //     debian 2.1 (slink) => false
             //     _ => true
             let exclude_dev = !matches!(r.get_series().as_ref(), "slink");
-            pack_tar_as_root(&extracted_dir, tar_path, exclude_dev);
+            pack_rootfs(r, &extracted_dir, exclude_dev)?;
         }
     }
     Ok(())
@@ -135,22 +144,17 @@ fn add_archive_src_mirrors(
     let components = OLD_DEBIAN;
     let mirror_dir = get_mirror_dir_based_on(docker_dir)?;
 
-    for m in debian_archive::deb_mirrors() {
-        let (region_prefix, region) = match m.get_region() {
-            Some(region) => (".", region),
-            _ => ("", &""),
-        };
-
+    let mirrors = MirrorVariant::DebianArchive.mirrors();
+    for m in &mirrors {
         // e.g., Official.list, NJU.CN.list
-        let fname =
-            format!("{name}{region_prefix}{region}.list", name = m.get_name());
+        let fname = format!("{}.list", list_stem(m));
         log::debug!("src list file name: {fname}");
 
         // DO NOT ADD `[trusted=yes]` for old-old debian
         let content = format!(
             "deb {url} {series} {components}\n\
             # deb-src {url} {series} {components}\n",
-            url = crate::docker::repo::convert_to_url_str(&m, true),
+            url = convert_to_url_str(m, true),
         );
         log::debug!("content: {content}");
 
@@ -160,52 +164,292 @@ fn add_archive_src_mirrors(
         fs::write(mirror_list_fpath, content)?;
     }
 
-    create_src_list_link(&mirror_dir)?;
+    create_src_list_link(&mirror_dir, &list_stem(&mirrors[0]))?;
 
     move_mirror_list_to_rootfs(&mirror_dir, rootfs_dir, false)?;
     Ok(())
 }
 
-#[derive(Debug, Default, Serialize, Deserialize)]
-struct ZstdOp<P: AsRef<Path>> {
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+struct ArchiveOp<P: AsRef<Path>> {
     path: P,
+    #[serde(default)]
+    codec: Codec,
     lv: u8,
+    /// zstd only: `--long=N` (0: disabled). None: the default of [ZstdParams].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    zstd_long: Option<u32>,
+    /// zstd only: the trained dictionary (sidecar file)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    dict: Option<P>,
+    /// [ARCHIVE_DIGESTS] of the archive (algorithm => hex), computed while it was written.
+    /// Empty: saved by an older version.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    digests: BTreeMap<String, String>,
+}
+
+impl<P: AsRef<Path>> ArchiveOp<P> {
+    fn zstd_params(&self) -> ZstdParams {
+        self.zstd_long
+            .map_or_else(ZstdParams::default, ZstdParams::from_long)
+    }
+
+    fn with_digests(mut self, digests: &Digests) -> Self {
+        self.digests = digests
+            .iter()
+            .map(|(algo, hex)| (algo.to_string(), hex.to_string()))
+            .collect();
+        self
+    }
+
+    /// None if an algorithm of [ARCHIVE_DIGESTS] is missing.
+    fn saved_digests(&self) -> Option<Digests> {
+        let digests = self
+            .digests
+            .iter()
+            .filter_map(|(algo, hex)| {
+                Some((algo.parse().ok()?, HexStr::from(hex).ok()?))
+            })
+            .collect::<Digests>();
+        ARCHIVE_DIGESTS
+            .iter()
+            .all(|a| digests.contains_key(a))
+            .then_some(digests)
+    }
+}
+
+impl ArchiveOp<PathBuf> {
+    fn new(
+        r: &Repository,
+        tar_fname: &str,
+        codec: Codec,
+        lv: u8,
+        zstd_long: Option<u32>,
+    ) -> Self {
+        // e.g., zstd/2.0_hamm_x86_1998-07-21.tar.zst
+        let path = Path::new(&codec.to_string())
+            .join(tar_fname.replace("tar", codec.tar_extension()));
+        log::debug!("archive_file: {path:?}");
+
+        Self {
+            path,
+            codec,
+            lv,
+            zstd_long: match codec {
+                // CLI > disk.v1.ron
+                Codec::Zstd => zstd_long.or(*r.get_zstd_long()),
+                _ => None,
+            },
+            dict: None,
+            digests: Default::default(),
+        }
+    }
+
+    fn create_parent_dir(&self) -> io::Result<()> {
+        log::debug!("creating the archive_file.parent() dir");
+        fs::create_dir_all(
+            self.path
+                .parent()
+                .expect("Invalid archive-file path"),
+        )
+    }
+
+    /// Writes [ARCHIVE_OP_RON] once the archive is complete.
+    ///
+    /// Atomic (tmp file + rename), so that an interrupted run never leaves a partial one.
+    fn save(&self, docker_dir: &Path) -> anyhow::Result<()> {
+        let path = docker_dir.join(ARCHIVE_OP_RON);
+        let tmp = path.with_extension("ron.tmp");
+        fs::write(&tmp, ron::to_string(self)?)?;
+        fs::rename(tmp, path)?;
+        Ok(())
+    }
+
+    /// Removes the [ARCHIVE_OP_RON] of the previous run before the archive is rewritten,
+    /// so that it does not vouch for a truncated archive if this run is interrupted.
+    fn discard_saved(docker_dir: &Path) -> io::Result<()> {
+        match fs::remove_file(docker_dir.join(ARCHIVE_OP_RON)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// The archive has been written by [pack_rootfs] with the same op (the digests aside), and is not older than the tarball.
+    fn is_up_to_date(&self, docker_dir: &Path, tar_path: &Path) -> bool {
+        let mtime = |p: &Path| {
+            fs::metadata(p)
+                .and_then(|m| m.modified())
+                .ok()
+        };
+        deser_ron::<Self, _>(docker_dir.join(ARCHIVE_OP_RON)).is_ok_and(|saved| {
+            Self {
+                digests: self.digests.clone(),
+                ..saved
+            } == *self
+        }) && matches!(
+            (mtime(&self.path), mtime(tar_path)),
+            (Some(archive), Some(tar)) if archive >= tar
+        )
+    }
+}
+
+/// `--obtain --repack --format zstd` (without `--zstd-dict`): the rootfs is compressed while it is packed, see [pack_rootfs].
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SinglePassZstd {
+    pub(crate) lv: u8,
+    pub(crate) zstd_long: Option<u32>,
+}
+
+/// Packs the rootfs to the tarball of `r`.
+///
+/// With [SinglePassZstd] (root only), the zstd archive is written in the same pass, and [repack] skips it.
+pub(crate) fn pack_rootfs(
+    r: &Repository,
+    rootfs_dir: &Path,
+    exclude_dev: bool,
+) -> anyhow::Result<()> {
+    let TarFile {
+        tar_fname,
+        tar_path,
+        docker_dir,
+    } = r.base_tar_name()?;
+
+    let op = Cli::static_single_pass_zstd(None)
+        .map(|z| ArchiveOp::new(r, &tar_fname, Codec::Zstd, z.lv, z.zstd_long));
+    if let Some(op) = &op {
+        op.create_parent_dir()?;
+        ArchiveOp::discard_saved(&docker_dir)?
+    }
+    let zst = op.as_ref().map(|op| ZstOutput {
+        path: &op.path,
+        lv: op.lv,
+        params: op.zstd_params(),
+    });
+
+    let zst_digests = pack_tar_as_root(
+        rootfs_dir,
+        &tar_path,
+        exclude_dev,
+        reproducible_epoch(r),
+        zst,
+    )?;
+    if let (Some(digests), Some(op)) = (zst_digests, op) {
+        op.with_digests(&digests)
+            .save(&docker_dir)?
+    }
+    Ok(())
+}
+
+/// Clamps `level` to the max level of `codec`.
+pub(crate) fn archive_level(codec: Codec, level: Option<&u8>) -> u8 {
+    match level {
+        Some(&l) if l > codec.max_level() => {
+            log::warn!("The max level of {codec} is {}", codec.max_level());
+            codec.max_level()
+        }
+        Some(&l) => l,
+        _ => codec.default_level(),
+    }
+}
+
+/// Saves the [ArchiveOp] (i.e., codec, level, zstd params & path of the compressed tarball) for `--digest`.
+pub(crate) const ARCHIVE_OP_RON: &str = "archive.ron";
+
+/// e.g., zstd/2.0-hamm.dict
+///
+/// All architectures of the same release (& tag) share a dictionary.
+fn zstd_dict_path(r: &Repository) -> PathBuf {
+    Path::new("zstd").join(format!(
+        "{}-{}{}.dict",
+        r.get_version(),
+        r.get_series(),
+        r.opt_tag_suffix()
+    ))
+}
+
+/// Trains a zstd dictionary for each family of tarballs, and saves it to [zstd_dict_path].
+fn train_zstd_dicts(
+    repos: &[&Repository],
+) -> anyhow::Result<ahash::HashMap<PathBuf, Arc<ZstdDict>>> {
+    let mut families = ahash::HashMap::<_, Vec<_>>::new();
+    for r in repos {
+        families
+            .entry(zstd_dict_path(r))
+            .or_default()
+            .push(r.base_tar_name()?.tar_path);
+    }
+
+    let mut dicts = ahash::HashMap::with_capacity(families.len());
+    for (dict_path, tars) in families {
+        log::info!("Training the zstd dictionary {dict_path:?} from {tars:?}");
+        let dict = ZstdDict::train(&tars, DEFAULT_DICT_SIZE)?;
+
+        if let Some(dir) = dict_path.parent() {
+            fs::create_dir_all(dir)?
+        }
+        dict.save(&dict_path)?;
+        dicts.insert(dict_path, Arc::new(dict));
+    }
+    Ok(dicts)
 }
 
 pub(crate) fn repack<'a, I: IntoIterator<Item = &'a Repository<'a>>>(
     repos: I,
-    zstd_lv: Option<&u8>,
+    codec: Codec,
+    level: Option<&u8>,
+    zstd_long: Option<u32>,
+    zstd_dict: bool,
 ) -> anyhow::Result<()> {
+    let lv = archive_level(codec, level);
+
+    let repos = repos
+        .into_iter()
+        .collect::<Vec<_>>();
+    let dicts = match (zstd_dict, codec) {
+        (true, Codec::Zstd) => train_zstd_dicts(&repos)?,
+        (true, _) => {
+            log::warn!("The zstd dictionary is not supported by {codec}");
+            Default::default()
+        }
+        _ => Default::default(),
+    };
+
     for r in repos {
         log::trace!("{r:#?}");
 
         let TarFile {
             tar_fname,
             tar_path,
-            ref docker_dir,
+            docker_dir,
             ..
         } = r.base_tar_name()?;
 
-        let zstd_file = Path::new("zstd").join(tar_fname.replace("tar", "tar.zst"));
-        log::debug!("zstd_file: {zstd_file:?}");
+        let dict = dicts.get_key_value(&zstd_dict_path(r));
 
-        // create dir
-        let op = ZstdOp {
-            path: &zstd_file,
-            lv: *zstd_lv.unwrap_or(&19),
-        };
-        {
-            log::debug!("creating the zstd_file.parent() dir");
-            fs::create_dir_all(
-                zstd_file
-                    .parent()
-                    .expect("Invalid ZSTD-file path"),
-            )?;
-            fs::write(docker_dir.join("zstd.ron"), ron::to_string(&op)?)?;
+        let mut op = ArchiveOp::new(r, &tar_fname, codec, lv, zstd_long);
+        op.dict = dict.map(|(p, _)| p.clone());
+
+        if op.is_up_to_date(&docker_dir, &tar_path) {
+            log::info!("{:?} has been compressed while packing, skip", op.path);
+            continue;
         }
+        op.create_parent_dir()?;
+        ArchiveOp::discard_saved(&docker_dir)?;
 
-        // compress to zstd
-        spawn_zstd_thread(tar_path, zstd_file, zstd_lv);
+        // compress to zstd/xz/lzip, then save the op
+        let zstd = op.zstd_params();
+        let dict = dict.map(|(_, d)| d.clone());
+        spawn_compression_thread(
+            tar_path,
+            op.path.clone(),
+            lv,
+            zstd,
+            dict,
+            move |digests| {
+                op.with_digests(&digests)
+                    .save(&docker_dir)
+            },
+        );
     }
     Ok(())
 }
@@ -232,6 +476,7 @@ mod tests {
     use super::*;
     use crate::dir::set_static_workdir;
     use byteunit::ByteUnit;
+    use hash_digest::multi;
     use std::path::Path;
 
     #[test]
@@ -243,13 +488,50 @@ mod tests {
         }
 
         dbg!(deser_ron::<String, _>(&docker_dir.join("tag.ron"))?);
-        dbg!(deser_ron::<ZstdOp<PathBuf>, _>(
-            &docker_dir.join("zstd.ron")
+        dbg!(deser_ron::<ArchiveOp<PathBuf>, _>(
+            &docker_dir.join(ARCHIVE_OP_RON)
         )?);
 
         Ok(())
     }
 
+    /// archive.ron is only present while the archive is complete.
+    #[test]
+    fn interrupted_archive_is_not_up_to_date() -> anyhow::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let (docker_dir, tar_path) = (tmp.path(), tmp.path().join("base.tar"));
+        let op = ArchiveOp {
+            path: tmp.path().join("base.tar.zst"),
+            codec: Codec::Zstd,
+            lv: 19,
+            zstd_long: None,
+            dict: None,
+            digests: Default::default(),
+        };
+        fs::write(&tar_path, "tar")?;
+        fs::write(&op.path, "zst")?;
+        op.save(docker_dir)?;
+        assert!(op.is_up_to_date(docker_dir, &tar_path));
+
+        // The digests of the archive do not change the op.
+        let load =
+            || deser_ron::<ArchiveOp<PathBuf>, _>(docker_dir.join(ARCHIVE_OP_RON));
+        assert_eq!(load()?.saved_digests(), None);
+        let digests = multi::get(&op.path, ARCHIVE_DIGESTS)?;
+        load()?
+            .with_digests(&digests)
+            .save(docker_dir)?;
+        assert!(op.is_up_to_date(docker_dir, &tar_path));
+        assert_eq!(load()?.saved_digests(), Some(digests));
+
+        // A new compression starts, then the run is killed => a truncated archive.
+        ArchiveOp::discard_saved(docker_dir)?;
+        fs::write(&op.path, "z")?;
+        assert!(!op.is_up_to_date(docker_dir, &tar_path));
+        assert!(ArchiveOp::discard_saved(docker_dir).is_ok());
+        Ok(())
+    }
+
     #[test]
     fn get_file_size() -> anyhow::Result<()> {
         let file = Path::new("tmp/zstd/2.0_hamm_x86_1998-07-21.tar.zst");
@@ -1,13 +1,49 @@
+use anyhow::bail;
 use log::info;
-use std::{self, sync::OnceLock};
+use std::{
+    self,
+    sync::{Mutex, OnceLock},
+};
 use threadpool::ThreadPool;
 
 pub(crate) static POOL: OnceLock<ThreadPool> = OnceLock::new();
 
+/// Errors of the jobs executed in the global pool.
+static ERRS: Mutex<Vec<anyhow::Error>> = Mutex::new(Vec::new());
+
 pub(crate) fn global_pool() -> &'static ThreadPool {
     POOL.get_or_init(ThreadPool::default)
 }
 
+/// A failed job reports its error here, so that it can be returned from [join_global_pool].
+pub(crate) fn push_err(e: anyhow::Error) {
+    ERRS.lock()
+        .unwrap_or_else(|x| x.into_inner())
+        .push(e)
+}
+
+/// Waits for all jobs in the global pool, then returns the first error (if any).
+pub(crate) fn join_global_pool() -> anyhow::Result<()> {
+    let pool = global_pool();
+    pool.join();
+
+    let mut errs = ERRS
+        .lock()
+        .unwrap_or_else(|x| x.into_inner());
+
+    for e in errs.iter().skip(1) {
+        log::error!("{e:#}");
+    }
+    if !errs.is_empty() {
+        return Err(errs.swap_remove(0));
+    }
+
+    match pool.panic_count() {
+        0 => Ok(()),
+        n => bail!("{n} job(s) panicked in the thread pool"),
+    }
+}
+
 pub(crate) fn wait_process(children: Vec<(&str, std::process::Child)>) {
     for (name, mut task) in children {
         if task
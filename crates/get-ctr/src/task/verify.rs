@@ -0,0 +1,216 @@
+use crate::cfg::digest::{self, Codec, Compression, HashDigest};
+use anyhow::bail;
+use derive_more::Display;
+use hash_digest::multi::{self, Algorithm};
+use std::{
+    collections::BTreeMap,
+    ffi::OsStr,
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Display)]
+enum Mismatch {
+    /// The paths that have been looked up
+    #[display("missing file, tried: {_0:?}")]
+    Missing(Vec<PathBuf>),
+
+    #[display("size mismatch: {path:?}, expected: {expected}, actual: {actual}")]
+    Size {
+        path: PathBuf,
+        expected: u64,
+        actual: u64,
+    },
+
+    #[display(
+        "{algorithm} mismatch: {path:?}, expected: {expected}, actual: {actual}"
+    )]
+    Digest {
+        path: PathBuf,
+        algorithm: Algorithm,
+        expected: String,
+        actual: String,
+    },
+
+    #[display("unsupported algorithm: {algorithm} ({path:?})")]
+    Unsupported { path: PathBuf, algorithm: String },
+}
+
+/// Loads a digest file (yaml or ron), recomputes every [HashDigest] & size of the referenced files under `asset_dir`, then reports the mismatches.
+///
+/// Files are looked up in `asset_dir/{codec}/` (e.g., zstd/) first, then `asset_dir/`. Older digests without the codec only contain zstd archives.
+pub(crate) fn verify_digest_file(
+    digest_file: &Path,
+    asset_dir: &Path,
+) -> anyhow::Result<()> {
+    let digests = load_digest_file(digest_file)?;
+    log::info!("Verifying {digest_file:?}, asset dir: {asset_dir:?}");
+
+    let mut mismatches = Vec::new();
+    let mut checked = 0_usize;
+
+    for tag in digests
+        .get_os()
+        .iter()
+        .flat_map(|os| os.get_tag())
+    {
+        let file = tag.get_file();
+        let codec_dir = file
+            .get_compression()
+            .as_ref()
+            .map_or(Codec::Zstd, Compression::codec)
+            .to_string();
+        let dirs = [asset_dir.join(codec_dir), asset_dir.into()];
+
+        let mut check = |name: &Path, bytes: u64, hex: &[HashDigest]| {
+            checked += 1;
+            let paths = dirs
+                .iter()
+                .map(|d| d.join(name))
+                .collect::<Vec<_>>();
+            match paths
+                .iter()
+                .find(|p| p.is_file())
+            {
+                Some(path) => verify_file(path, bytes, hex, &mut mismatches),
+                _ => {
+                    mismatches.push(Mismatch::Missing(paths));
+                    Ok(())
+                }
+            }
+        };
+
+        check(
+            file.get_name(),
+            *file.get_size().get_bytes(),
+            file.get_digest(),
+        )?;
+
+        if let Some(d) = file.get_dict() {
+            check(d.get_name(), *d.get_bytes(), d.get_digest())?
+        }
+    }
+
+    for m in &mismatches {
+        log::error!("{m}")
+    }
+
+    match mismatches.len() {
+        0 => {
+            log::info!("OK: {checked} files");
+            Ok(())
+        }
+        n => bail!("{n} mismatch(es) in {checked} files ({digest_file:?})"),
+    }
+}
+
+fn verify_file(
+    path: &Path,
+    expected_bytes: u64,
+    expected: &[HashDigest],
+    mismatches: &mut Vec<Mismatch>,
+) -> anyhow::Result<()> {
+    log::info!("Checking {path:?}");
+
+    let actual_bytes = path.metadata()?.len();
+    if actual_bytes != expected_bytes {
+        mismatches.push(Mismatch::Size {
+            path: path.to_owned(),
+            expected: expected_bytes,
+            actual: actual_bytes,
+        });
+        // Skip the hash digests of a truncated file
+        return Ok(());
+    }
+
+    let mut algorithms = BTreeMap::new();
+    for d in expected {
+        match d
+            .get_algorithm()
+            .parse::<Algorithm>()
+        {
+            Ok(a) => {
+                algorithms.insert(a, d.get_hex());
+            }
+            _ => mismatches.push(Mismatch::Unsupported {
+                path: path.to_owned(),
+                algorithm: d.get_algorithm().to_owned(),
+            }),
+        }
+    }
+
+    let actual = multi::get(path, algorithms.keys().copied())?;
+    for (algorithm, hex) in algorithms {
+        let got = &actual[&algorithm];
+        if !got.eq_ignore_ascii_case(hex.trim()) {
+            mismatches.push(Mismatch::Digest {
+                path: path.to_owned(),
+                algorithm,
+                expected: hex.to_owned(),
+                actual: got.to_string(),
+            })
+        }
+    }
+    Ok(())
+}
+
+fn load_digest_file(path: &Path) -> anyhow::Result<digest::Digests> {
+    let content = fs::read_to_string(path)?;
+
+    let digests = match path.extension() {
+        Some(ext) if ext == OsStr::new("ron") => ron::from_str(&content)?,
+        _ => serde_yaml::from_str(&content)?,
+    };
+    Ok(digests)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_yaml_digest() -> anyhow::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let zstd_dir = tmp.path().join("zstd");
+        fs::create_dir_all(&zstd_dir)?;
+        fs::write(zstd_dir.join("a.tar.zst"), "zstd")?;
+
+        let hex = multi::get(zstd_dir.join("a.tar.zst"), [Algorithm::Sha256])?;
+        let yaml = format!(
+            r#"os:
+- name: Debian
+  tag:
+  - name: "2.0"
+    file:
+      name: a.tar.zst
+      size:
+        bytes: 4
+      compression:
+        codec: zstd
+      digest:
+      - algorithm: sha256
+        hex: {}
+"#,
+            hex[&Algorithm::Sha256]
+        );
+        let digest_file = tmp.path().join("digest.yml");
+        fs::write(&digest_file, &yaml)?;
+        verify_digest_file(&digest_file, tmp.path())?;
+
+        // legacy: `zstd` instead of `compression`, or neither
+        for legacy in ["zstd:\n        level: 19", ""] {
+            let yaml = yaml.replace("compression:\n        codec: zstd", legacy);
+            fs::write(&digest_file, &yaml)?;
+            verify_digest_file(&digest_file, tmp.path())?;
+        }
+
+        // modified
+        fs::write(zstd_dir.join("a.tar.zst"), "ZSTD")?;
+        assert!(verify_digest_file(&digest_file, tmp.path()).is_err());
+
+        // missing
+        fs::remove_file(zstd_dir.join("a.tar.zst"))?;
+        assert!(verify_digest_file(&digest_file, tmp.path()).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,104 @@
+//! Test helpers, e.g., a local stand-in HTTP server.
+
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpListener,
+    thread,
+};
+
+/// A request received by [serve].
+#[derive(Debug, Default)]
+pub(crate) struct Request {
+    /// The request line & the headers, e.g., `GET /debian/ HTTP/1.1\r\nRange: bytes=4-\r\n...`
+    pub(crate) head: String,
+    pub(crate) body: Vec<u8>,
+}
+
+impl Request {
+    /// e.g., GET
+    pub(crate) fn method(&self) -> &str {
+        self.head
+            .split(' ')
+            .next()
+            .unwrap_or_default()
+    }
+
+    /// e.g., /v2/2cd/debian/manifests/potato
+    pub(crate) fn path(&self) -> &str {
+        self.head
+            .split(' ')
+            .nth(1)
+            .unwrap_or_default()
+    }
+
+    /// The value of the first header named `name` (case-insensitive).
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        self.head
+            .lines()
+            .skip(1)
+            .find_map(|l| {
+                let (k, v) = l.split_once(':')?;
+                k.eq_ignore_ascii_case(name)
+                    .then(|| v.trim())
+            })
+    }
+}
+
+/// A local stand-in HTTP server. `handler` gets each request and returns the raw response, see [response].
+///
+/// Returns the address (host:port), e.g., 127.0.0.1:41234
+pub(crate) fn serve<F>(handler: F) -> String
+where
+    F: Fn(&Request) -> Vec<u8> + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind");
+    let addr = listener
+        .local_addr()
+        .expect("No addr")
+        .to_string();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            let mut reader = BufReader::new(
+                stream
+                    .try_clone()
+                    .expect("clone"),
+            );
+            let mut req = Request::default();
+            while reader
+                .read_line(&mut req.head)
+                .is_ok_and(|n| n > 2)
+            {}
+            let len = req
+                .header("Content-Length")
+                .and_then(|n| n.parse().ok())
+                .unwrap_or(0);
+            req.body.resize(len, 0);
+            let _ = reader.read_exact(&mut req.body);
+
+            let _ = stream.write_all(&handler(&req));
+        }
+    });
+    addr
+}
+
+/// e.g., `response("206 Partial Content", "Content-Range: bytes 4-9/10\r\n", b"456789")`
+pub(crate) fn response(status: &str, extra: &str, body: &[u8]) -> Vec<u8> {
+    let mut r = format!(
+        "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n{extra}\r\n",
+        body.len()
+    )
+    .into_bytes();
+    r.extend_from_slice(body);
+    r
+}
+
+/// An address that nothing listens on.
+pub(crate) fn dead_addr() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind");
+    listener
+        .local_addr()
+        .expect("No addr")
+        .to_string()
+}
@@ -0,0 +1,218 @@
+use crate::cfg::{
+    self,
+    mirror::{Mirror, MirrorVariant},
+};
+use clap::ValueEnum;
+use std::{
+    env,
+    sync::{Mutex, OnceLock},
+};
+use url::{ParseError, Url};
+
+mod probe;
+
+type UrlResult = Result<Url, ParseError>;
+
+/// How [find_mirror_url] selects a mirror.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum MirrorSelect {
+    /// The first mirror that matches the region (LANG contains "CN" => CN)
+    #[default]
+    Lang,
+
+    /// Probes all mirrors & selects the fastest available one (falls back to lang if none is available)
+    ///
+    /// If a request to the selected mirror fails later (e.g., a download), it is retried on the next ranked one, see [fallback_urls].
+    Probe,
+}
+
+static MIRROR_SELECT: OnceLock<MirrorSelect> = OnceLock::new();
+
+/// Only the first call takes effect.
+pub(crate) fn set_mirror_select(select: MirrorSelect) {
+    if MIRROR_SELECT
+        .set(select)
+        .is_err()
+    {
+        log::debug!("mirror select has already been set")
+    }
+}
+
+fn mirror_select() -> MirrorSelect {
+    *MIRROR_SELECT.get_or_init(MirrorSelect::default)
+}
+
+fn is_cn() -> bool {
+    static B: OnceLock<bool> = OnceLock::new();
+    *B.get_or_init(|| env::var("LANG").is_ok_and(|x| x.contains("CN")))
+}
+
+/// The ranked mirrors of each [find_mirror_url] call (probe only), e.g., `[[https://a.org/debian/, https://b.org/debian/]]`
+fn static_ranked() -> &'static Mutex<Vec<Vec<Url>>> {
+    static R: OnceLock<Mutex<Vec<Vec<Url>>>> = OnceLock::new();
+    R.get_or_init(Default::default)
+}
+
+/// The same resource on the mirrors ranked after the one of `url`, e.g., https://a.org/debian/dists/hamm/Release => [https://b.org/debian/dists/hamm/Release]
+///
+/// Empty if `url` is not on a mirror that [find_mirror_url] has selected with [MirrorSelect::Probe].
+pub(crate) fn fallback_urls(url: &Url) -> Vec<Url> {
+    let ranked = static_ranked()
+        .lock()
+        .expect("Poisoned mirror ranking");
+    fallbacks_in(&ranked, url)
+}
+
+/// `bases`: the mirror URLs, fastest first, see [fallback_urls]
+pub(crate) fn remember_ranking(bases: Vec<Url>) {
+    let mut ranked = static_ranked()
+        .lock()
+        .expect("Poisoned mirror ranking");
+    if !ranked.contains(&bases) {
+        ranked.push(bases)
+    }
+}
+
+fn fallbacks_in(ranked: &[Vec<Url>], url: &Url) -> Vec<Url> {
+    let url_str = url.as_str();
+    for bases in ranked {
+        let Some((i, rest)) = bases
+            .iter()
+            .enumerate()
+            .find_map(|(i, b)| Some((i, url_str.strip_prefix(b.as_str())?)))
+        else {
+            continue;
+        };
+        return bases[i + 1..]
+            .iter()
+            .filter_map(|b| Url::parse(&format!("{b}{rest}")).ok())
+            .collect();
+    }
+    Vec::new()
+}
+
+/// `url` on the official mirror of its variant, e.g., https://mirrors.nju.edu.cn/debian/dists/sid/ => https://deb.debian.org/debian/dists/sid/
+///
+/// Unlike the one [find_mirror_url] has selected (probe, LANG or the user catalog), it does not depend on the host.
+/// `url` is returned as is if it is not on a known mirror, e.g., snapshot.debian.org.
+pub(crate) fn official_url(url: &Url) -> Url {
+    let url_str = url.as_str();
+    MirrorVariant::ALL
+        .into_iter()
+        .flat_map(|v| {
+            v.mirrors()
+                .into_iter()
+                .map(move |m| (v, m))
+        })
+        .filter_map(|(v, m)| Some((v, url_str.strip_prefix(*m.get_url())?)))
+        // the longest mirror URL, i.e., the shortest rest
+        .min_by_key(|(_, rest)| rest.len())
+        .and_then(|(v, rest)| {
+            let official = v.official()?;
+            Url::parse(&format!("{}{rest}", official.get_url())).ok()
+        })
+        .unwrap_or_else(|| url.clone())
+}
+
+pub(crate) fn find_mirror_url(
+    mirrors: &[Mirror],
+    // variant: MirrorVariant,
+) -> UrlResult {
+    if mirror_select() == MirrorSelect::Probe {
+        let ranked = probe::rank(mirrors);
+        if let Some((m, latency)) = ranked.first() {
+            log::debug!("mirror: {m:?}, latency: {latency:?}");
+            remember_ranking(
+                ranked
+                    .iter()
+                    .filter_map(|(m, _)| Url::parse(m.get_url()).ok())
+                    .collect(),
+            );
+            return Url::parse(m.get_url());
+        }
+        log::warn!("No mirror is available, fall back to LANG")
+    }
+
+    let m = mirrors
+        .iter()
+        // .filter(|x| x.get_variant() == &variant)
+        .find(|x| match x.get_region() {
+            None if is_cn() => false,
+            Some("CN") => is_cn(),
+            _ => true,
+        })
+        .ok_or(ParseError::EmptyHost)?;
+
+    log::debug!("mirror: {m:?}");
+
+    Url::parse(m.get_url())
+}
+
+pub(crate) fn debian_archive() -> UrlResult {
+    log::debug!("finding the mirror url from static debian archive mirrors.");
+    find_mirror_url(&MirrorVariant::DebianArchive.mirrors())
+    // , MirrorVariant::DebianArchive
+}
+
+pub(crate) fn add_slash(x: &str) -> &str {
+    if x.ends_with('/') {
+        return "";
+    }
+
+    "/"
+}
+
+/// e.g., dists/hamm/main/disks-i386/2.0.10_1998-07-21/{file_name}
+pub(crate) fn concat_url_path(
+    url: &mut String,
+    os: &cfg::disk::OS,
+    disk: &cfg::disk::Disk,
+    file_name: &str,
+) {
+    let (op, dp) = (os.get_path(), disk.get_path());
+    url.clear();
+    *url = format!(
+        "{op}{o_sep}{dp}{d_sep}{file_name}",
+        o_sep = add_slash(op),
+        d_sep = add_slash(dp)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fallback_to_the_next_ranked_mirrors() -> anyhow::Result<()> {
+        let ranked = [
+            [
+                "https://a.org/debian/",
+                "http://b.org/debian-archive/debian/",
+            ],
+            ["https://a.org/ubuntu/", "https://c.org/ubuntu/"],
+        ]
+        .map(|bases| {
+            bases
+                .iter()
+                .map(|b| Url::parse(b))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+
+        let release = Url::parse("https://a.org/debian/dists/hamm/Release")?;
+        assert_eq!(
+            fallbacks_in(&ranked, &release),
+            [Url::parse(
+                "http://b.org/debian-archive/debian/dists/hamm/Release"
+            )?]
+        );
+        // the last one
+        let release = Url::parse("https://c.org/ubuntu/dists/warty/Release")?;
+        assert!(fallbacks_in(&ranked, &release).is_empty());
+        // not a ranked mirror
+        let release = Url::parse("https://d.org/debian/dists/hamm/Release")?;
+        assert!(fallbacks_in(&ranked, &release).is_empty());
+        Ok(())
+    }
+}
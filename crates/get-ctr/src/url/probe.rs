@@ -0,0 +1,143 @@
+//! Probes the mirrors (HEAD request) & sorts them by latency.
+//!
+//! A request that fails on the selected mirror falls back to the next one in this order, see [super::fallback_urls].
+
+use crate::{cfg::mirror::Mirror, http};
+use ahash::HashMap;
+use std::{
+    sync::{Mutex, OnceLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// url => latency (None: unavailable)
+type Cache = HashMap<String, Option<Duration>>;
+
+/// The results are cached per run, so each mirror is probed at most once.
+fn static_cache() -> &'static Mutex<Cache> {
+    static C: OnceLock<Mutex<Cache>> = OnceLock::new();
+    C.get_or_init(Default::default)
+}
+
+/// A short timeout & no retries: a slow mirror is as bad as a dead one.
+fn static_client() -> &'static http::Client {
+    static C: OnceLock<http::Client> = OnceLock::new();
+    C.get_or_init(|| {
+        let timeout = Duration::from_secs(5);
+        http::Client::new(timeout, timeout, 0)
+    })
+}
+
+/// Returns the latency of a HEAD request, or None if the mirror is unavailable (e.g., timeout, 404).
+fn latency(client: &http::Client, url: &str) -> Option<Duration> {
+    let now = Instant::now();
+    match client.status(url) {
+        Ok(code) if (200..400).contains(&code) => Some(now.elapsed()),
+        Ok(code) => {
+            log::warn!("mirror: {url}, status: {code}");
+            None
+        }
+        Err(e) => {
+            log::warn!("{e:#}");
+            None
+        }
+    }
+}
+
+/// Sorts the available mirrors by latency (fastest first). Unavailable mirrors are excluded.
+///
+/// Uncached mirrors are probed concurrently.
+pub(crate) fn rank<'m>(mirrors: &[Mirror<'m>]) -> Vec<(Mirror<'m>, Duration)> {
+    rank_with(static_client(), mirrors)
+}
+
+fn rank_with<'m>(
+    client: &http::Client,
+    mirrors: &[Mirror<'m>],
+) -> Vec<(Mirror<'m>, Duration)> {
+    let uncached = {
+        let cache = static_cache()
+            .lock()
+            .expect("Poisoned mirror cache");
+        let mut urls = mirrors
+            .iter()
+            .map(|m| *m.get_url())
+            .filter(|url| !cache.contains_key(*url))
+            .collect::<Vec<_>>();
+        urls.sort_unstable();
+        urls.dedup();
+        urls
+    };
+
+    let probed = thread::scope(|s| {
+        uncached
+            .iter()
+            .map(|url| s.spawn(move || (url.to_string(), latency(client, url))))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .filter_map(|h| h.join().ok())
+            .collect::<Vec<_>>()
+    });
+
+    let cache = &mut static_cache()
+        .lock()
+        .expect("Poisoned mirror cache");
+    for (url, t) in probed {
+        log::info!("probe: {url}, latency: {t:?}");
+        cache.insert(url, t);
+    }
+
+    let mut ranked = mirrors
+        .iter()
+        .filter_map(|m| {
+            cache
+                .get(*m.get_url())
+                .copied()
+                .flatten()
+                .map(|t| (*m, t))
+        })
+        .collect::<Vec<_>>();
+    ranked.sort_by_key(|(_, t)| *t);
+    ranked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{dead_addr, response, serve};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    #[test]
+    fn rank_mirrors_by_latency() {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let alive = {
+            let hits = hits.clone();
+            serve(move |_| {
+                hits.fetch_add(1, Ordering::Relaxed);
+                response("200 OK", "", b"")
+            })
+        };
+        let (dead, alive) = (
+            format!("http://{}/debian/", dead_addr()),
+            format!("http://{alive}/debian/"),
+        );
+        let ron_str = format!(
+            r#"[(name: "Dead", url: "{dead}"), (name: "Alive", url: "{alive}", region: Some("CN"))]"#
+        );
+        let mirrors = ron::from_str::<Vec<Mirror>>(&ron_str).expect("Invalid ron");
+        let client =
+            http::Client::new(Duration::from_secs(2), Duration::from_secs(2), 0);
+
+        let ranked = rank_with(&client, &mirrors);
+        assert_eq!(ranked.len(), 1);
+        assert_eq!(ranked[0].0.get_name(), &"Alive");
+
+        // cached: probed only once
+        let ranked = rank_with(&client, &mirrors);
+        assert_eq!(ranked[0].0.get_url(), &alive.as_str());
+        assert_eq!(hits.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,68 @@
+use crate::{multi::Algorithm, HexStr};
+use core::fmt::Write as _;
+use sha2::Digest;
+
+/// A streaming hash function with a hex output.
+///
+/// It is object safe, so that [crate::multi::MultiHasher] can hold any of them.
+pub trait Hasher: Send {
+    fn algorithm(&self) -> Algorithm;
+
+    fn update(&mut self, data: &[u8]);
+
+    fn finalize_hex(self: Box<Self>) -> HexStr;
+}
+
+impl Hasher for blake3::Hasher {
+    fn algorithm(&self) -> Algorithm {
+        Algorithm::Blake3
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match data.len() {
+            // > 128 KiB
+            n if n > 128 << 10 => self.update_rayon(data),
+            _ => blake3::Hasher::update(self, data),
+        };
+    }
+
+    fn finalize_hex(self: Box<Self>) -> HexStr {
+        to_hex(self.finalize().as_bytes())
+    }
+}
+
+macro_rules! impl_digest_hasher {
+    ($($t:ty => $algo:ident),+ $(,)?) => {
+        $(
+            impl Hasher for $t {
+                fn algorithm(&self) -> Algorithm {
+                    Algorithm::$algo
+                }
+
+                fn update(&mut self, data: &[u8]) {
+                    Digest::update(self, data)
+                }
+
+                fn finalize_hex(self: Box<Self>) -> HexStr {
+                    to_hex(&self.finalize())
+                }
+            }
+        )+
+    };
+}
+
+impl_digest_hasher!(
+    sha2::Sha256 => Sha256,
+    sha2::Sha512 => Sha512,
+    sha1::Sha1 => Sha1,
+    md5::Md5 => Md5,
+);
+
+/// Lowercase hex (up to 64 bytes, i.e., SHA-512)
+pub fn to_hex(bytes: &[u8]) -> HexStr {
+    let mut hex = HexStr::new();
+    for b in bytes {
+        write!(hex, "{b:02x}").expect("The digest is too long");
+    }
+    hex
+}
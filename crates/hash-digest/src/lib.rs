@@ -1,4 +1,9 @@
 pub mod blake3;
+pub mod hasher;
+pub mod multi;
 pub mod sha256;
 
 pub type HexStr64 = arrayvec::ArrayString<64>;
+
+/// Variable-length hex string (up to SHA-512).
+pub type HexStr = arrayvec::ArrayString<128>;
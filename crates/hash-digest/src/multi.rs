@@ -0,0 +1,367 @@
+use crate::{hasher::Hasher, HexStr};
+use sha2::Digest;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fs::File,
+    io::{self, Read, Write},
+    path::Path,
+    str::FromStr,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Algorithm {
+    Blake3,
+    Sha256,
+    Sha512,
+    /// Debian `Release`: SHA1
+    Sha1,
+    /// Debian `Release`: MD5Sum
+    Md5,
+}
+
+impl Algorithm {
+    pub const ALL: [Self; 5] = [
+        Self::Blake3,
+        Self::Sha256,
+        Self::Sha512,
+        Self::Sha1,
+        Self::Md5,
+    ];
+
+    /// blake3 & sha256
+    pub const DEFAULT: [Self; 2] = [Self::Blake3, Self::Sha256];
+
+    /// e.g., "blake3"
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Blake3 => "blake3",
+            Self::Sha256 => "sha256",
+            Self::Sha512 => "sha512",
+            Self::Sha1 => "sha1",
+            Self::Md5 => "md5",
+        }
+    }
+
+    /// Length of the hex string, e.g., sha512 => 128
+    pub const fn hex_len(&self) -> usize {
+        match self {
+            Self::Blake3 | Self::Sha256 => 64,
+            Self::Sha512 => 128,
+            Self::Sha1 => 40,
+            Self::Md5 => 32,
+        }
+    }
+
+    pub fn new_hasher(&self) -> Box<dyn Hasher> {
+        match self {
+            Self::Blake3 => Box::new(blake3::Hasher::new()),
+            Self::Sha256 => Box::new(sha2::Sha256::new()),
+            Self::Sha512 => Box::new(sha2::Sha512::new()),
+            Self::Sha1 => Box::new(sha1::Sha1::new()),
+            Self::Md5 => Box::new(md5::Md5::new()),
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "blake3" | "b3" => Ok(Self::Blake3),
+            "sha256" => Ok(Self::Sha256),
+            "sha512" => Ok(Self::Sha512),
+            "sha1" => Ok(Self::Sha1),
+            "md5" | "md5sum" => Ok(Self::Md5),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Unsupported hash algorithm: {s}"),
+            )),
+        }
+    }
+}
+
+/// Algorithm => hex string
+pub type Digests = BTreeMap<Algorithm, HexStr>;
+
+/// Feeds the same data into several hashers.
+#[derive(Default)]
+pub struct MultiHasher {
+    hashers: Vec<Box<dyn Hasher>>,
+}
+
+impl fmt::Debug for MultiHasher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(
+                self.hashers
+                    .iter()
+                    .map(|h| h.algorithm()),
+            )
+            .finish()
+    }
+}
+
+impl MultiHasher {
+    /// Duplicate algorithms are ignored.
+    pub fn new<I: IntoIterator<Item = Algorithm>>(algorithms: I) -> Self {
+        let mut algorithms = algorithms
+            .into_iter()
+            .collect::<Vec<_>>();
+        algorithms.sort_unstable();
+        algorithms.dedup();
+
+        Self {
+            hashers: algorithms
+                .iter()
+                .map(Algorithm::new_hasher)
+                .collect(),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for h in &mut self.hashers {
+            h.update(data)
+        }
+    }
+
+    pub fn finalize(self) -> Digests {
+        self.hashers
+            .into_iter()
+            .map(|h| (h.algorithm(), h.finalize_hex()))
+            .collect()
+    }
+}
+
+impl Write for MultiHasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Computes all `algorithms` of the file in a single read pass.
+///
+/// # Example
+///
+/// ```no_run
+/// use hash_digest::multi::{self, Algorithm};
+///
+/// let digests = multi::get("a.tar.zst", Algorithm::ALL)?;
+/// println!("{}", digests[&Algorithm::Sha256]);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn get<P, I>(path: P, algorithms: I) -> io::Result<Digests>
+where
+    P: AsRef<Path>,
+    I: IntoIterator<Item = Algorithm>,
+{
+    let mut hasher = MultiHasher::new(algorithms);
+    let mut file = File::open(path)?;
+
+    let mut buf = vec![0; 1 << 20];
+    loop {
+        match file.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => hasher.update(&buf[..n]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(hasher.finalize())
+}
+
+/// Compares the file with the `expected` hex strings (case-insensitive).
+///
+/// Returns an [io::ErrorKind::InvalidData] error on mismatch.
+pub fn check<P, S>(path: P, expected: &BTreeMap<Algorithm, S>) -> io::Result<()>
+where
+    P: AsRef<Path>,
+    S: AsRef<str>,
+{
+    let path = path.as_ref();
+    let actual = get(path, expected.keys().copied())?;
+
+    for (algo, hex) in expected {
+        let (hex, got) = (hex.as_ref().trim(), &actual[algo]);
+        if !hex.eq_ignore_ascii_case(got) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{algo} mismatch: {path:?}, expected: {hex}, got: {got}"),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Hashes everything that is written into `inner`,
+/// e.g., `HashWriter::new(File::create("a.tar.zst")?, Algorithm::ALL)` as the output of an encoder.
+#[derive(Debug)]
+pub struct HashWriter<W> {
+    inner: W,
+    hasher: MultiHasher,
+}
+
+impl<W: Write> HashWriter<W> {
+    pub fn new<I: IntoIterator<Item = Algorithm>>(inner: W, algorithms: I) -> Self {
+        Self {
+            inner,
+            hasher: MultiHasher::new(algorithms),
+        }
+    }
+
+    /// Flushes `inner`, then returns it with the digests.
+    pub fn finalize(mut self) -> io::Result<(W, Digests)> {
+        self.inner.flush()?;
+        Ok((self.inner, self.hasher.finalize()))
+    }
+}
+
+impl<W: Write> Write for HashWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Hashes everything that is read from `inner`, e.g., the input of an encoder.
+#[derive(Debug)]
+pub struct HashReader<R> {
+    inner: R,
+    hasher: MultiHasher,
+}
+
+impl<R: Read> HashReader<R> {
+    pub fn new<I: IntoIterator<Item = Algorithm>>(inner: R, algorithms: I) -> Self {
+        Self {
+            inner,
+            hasher: MultiHasher::new(algorithms),
+        }
+    }
+
+    /// The digests of the data that has been read so far.
+    pub fn finalize(self) -> Digests {
+        self.hasher.finalize()
+    }
+}
+
+impl<R: Read> Read for HashReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{blake3, sha256};
+    use std::{env, fs, process};
+
+    /// ("", "abc") of each algorithm, e.g., FIPS 180-2 & RFC 1321
+    const KNOWN_ANSWERS: [(Algorithm, &str, &str); 5] = [
+        (
+            Algorithm::Blake3,
+            "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262",
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85",
+        ),
+        (
+            Algorithm::Sha256,
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+        ),
+        (
+            Algorithm::Sha512,
+            "cf83e1357eefb8bdf1542850d66d8007d620e4050b5715dc83f4a921d36ce9ce47d0d13c5d85f2b0ff8318d2877eec2f63b931bd47417a81a538327af927da3e",
+            "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f",
+        ),
+        (
+            Algorithm::Sha1,
+            "da39a3ee5e6b4b0d3255bfef95601890afd80709",
+            "a9993e364706816aba3e25717850c26c9cd0d89d",
+        ),
+        (
+            Algorithm::Md5,
+            "d41d8cd98f00b204e9800998ecf8427e",
+            "900150983cd24fb0d6963f7d28e17f72",
+        ),
+    ];
+
+    #[test]
+    fn known_answers() {
+        assert_eq!(KNOWN_ANSWERS.map(|(algo, ..)| algo), Algorithm::ALL);
+
+        for (algo, empty, abc) in KNOWN_ANSWERS {
+            for (input, expected) in [("", empty), ("abc", abc)] {
+                let mut hasher = MultiHasher::new([algo]);
+                hasher.update(input.as_bytes());
+                assert_eq!(
+                    hasher.finalize()[&algo].as_str(),
+                    expected,
+                    "{algo}({input:?})"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn single_pass_matches_each_algorithm() -> io::Result<()> {
+        let path = env::temp_dir().join(format!("multi-{}.bin", process::id()));
+        let data = (0..(3 << 20))
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+        fs::write(&path, &data)?;
+
+        let digests = get(&path, Algorithm::ALL)?;
+        assert_eq!(
+            digests[&Algorithm::Blake3].as_str(),
+            blake3::get(&path)?.as_str()
+        );
+        assert_eq!(
+            digests[&Algorithm::Sha256].as_str(),
+            sha256::get(&path)?.as_str()
+        );
+        for (algo, hex) in &digests {
+            assert_eq!(hex.len(), algo.hex_len(), "{algo}")
+        }
+
+        let expected = BTreeMap::from([(Algorithm::Md5, digests[&Algorithm::Md5])]);
+        check(&path, &expected)?;
+        let wrong = BTreeMap::from([(
+            Algorithm::Sha1,
+            "da39a3ee5e6b4b0d3255bfef95601890afd80709",
+        )]);
+        assert!(check(&path, &wrong).is_err());
+
+        let mut writer = HashWriter::new(Vec::new(), [Algorithm::Sha256]);
+        writer.write_all(&data)?;
+        let (out, w_digests) = writer.finalize()?;
+        assert_eq!(out, data);
+        assert_eq!(w_digests.len(), 1);
+        assert_eq!(w_digests[&Algorithm::Sha256], digests[&Algorithm::Sha256]);
+
+        let mut reader = HashReader::new(data.as_slice(), [Algorithm::Sha256]);
+        io::copy(&mut reader, &mut io::sink())?;
+        assert_eq!(reader.finalize(), w_digests);
+
+        fs::remove_file(path)
+    }
+}
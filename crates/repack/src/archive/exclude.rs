@@ -0,0 +1,147 @@
+use std::{os::unix::ffi::OsStrExt, path::Path};
+
+/// A shell-like wildcard, relative to the root of the packed directory.
+///
+/// - `*` matches any sequence of bytes, including `/`
+/// - All other bytes are matched literally.
+///
+/// Unlike GNU tar, a pattern is always anchored to the root:
+/// `proc/*` excludes `proc/1/status`, but keeps `proc` and `usr/proc/x`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Wildcard(Box<[u8]>);
+
+impl Wildcard {
+    /// Leading `./` and `/` are ignored, i.e. `./proc/*` == `/proc/*` == `proc/*`.
+    pub fn new(pattern: &str) -> Self {
+        let mut p = pattern.as_bytes();
+        while let [b'.', b'/', rest @ ..] | [b'/', rest @ ..] = p {
+            p = rest
+        }
+        Self(p.into())
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Matches the whole `path` (not just a prefix) against the pattern.
+    pub fn is_match(&self, path: &[u8]) -> bool {
+        let pat = self.as_bytes();
+        let (mut p, mut s) = (0, 0);
+        // (position of the last `*` in pat, position in path it was tried at)
+        let mut star: Option<(usize, usize)> = None;
+
+        while s < path.len() {
+            match pat.get(p) {
+                Some(b'*') => {
+                    star = Some((p, s));
+                    p += 1;
+                }
+                Some(c) if *c == path[s] => {
+                    p += 1;
+                    s += 1;
+                }
+                _ => match star {
+                    // backtrack: let the last `*` consume one more byte
+                    Some((sp, ss)) => {
+                        p = sp + 1;
+                        s = ss + 1;
+                        star = Some((sp, ss + 1));
+                    }
+                    None => return false,
+                },
+            }
+        }
+
+        pat[p..]
+            .iter()
+            .all(|c| *c == b'*')
+    }
+}
+
+/// A typed replacement for a list of `tar --exclude=PATTERN` arguments.
+///
+/// # Example
+///
+/// ```
+/// use repack::archive::Exclude;
+///
+/// let exclude = Exclude::from_iter(["proc/*", "var/cache/apt/archives/*deb"]);
+///
+/// assert!(exclude.is_excluded("proc/1/status"));
+/// assert!(!exclude.is_excluded("proc"));
+/// assert!(exclude.is_excluded("var/cache/apt/archives/bash_5.2_amd64.deb"));
+/// assert!(!exclude.is_excluded("var/cache/apt/archives/lock"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Exclude(Vec<Wildcard>);
+
+impl Exclude {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, pattern: &str) {
+        self.0
+            .push(Wildcard::new(pattern))
+    }
+
+    pub fn with(mut self, pattern: &str) -> Self {
+        self.push(pattern);
+        self
+    }
+
+    pub fn patterns(&self) -> &[Wildcard] {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// `rel_path` is relative to the root of the packed directory, e.g. `var/tmp/x`.
+    pub fn is_excluded<P: AsRef<Path>>(&self, rel_path: P) -> bool {
+        let bytes = rel_path
+            .as_ref()
+            .as_os_str()
+            .as_bytes();
+
+        self.0
+            .iter()
+            .any(|w| w.is_match(bytes))
+    }
+}
+
+impl<S: AsRef<str>> FromIterator<S> for Exclude {
+    fn from_iter<I: IntoIterator<Item = S>>(iter: I) -> Self {
+        Self(
+            iter.into_iter()
+                .map(|s| Wildcard::new(s.as_ref()))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wildcard_match() {
+        let w = Wildcard::new("./var/lib/apt/lists/*.*");
+        assert_eq!(w.as_bytes(), b"var/lib/apt/lists/*.*");
+
+        assert!(w.is_match(b"var/lib/apt/lists/deb.debian.org_Release"));
+        assert!(w.is_match(b"var/lib/apt/lists/partial/a.b"));
+        assert!(!w.is_match(b"var/lib/apt/lists/lock"));
+        assert!(!w.is_match(b"var/lib/apt/lists"));
+
+        let exact = Wildcard::new("var/cache/apt/pkgcache.bin");
+        assert!(exact.is_match(b"var/cache/apt/pkgcache.bin"));
+        assert!(!exact.is_match(b"var/cache/apt/pkgcache.bin.1"));
+
+        assert!(Wildcard::new("*").is_match(b""));
+        assert!(Wildcard::new("a*b*c").is_match(b"a/x/b/y/c"));
+        assert!(!Wildcard::new("a*b*c").is_match(b"a/x/b/y/cd"));
+    }
+}
@@ -0,0 +1,8 @@
+//! Native tar (pax) support, i.e. no dependency on the `tar` binary.
+pub mod exclude;
+mod pack;
+mod unpack;
+
+pub use exclude::Exclude;
+pub use pack::Packer;
+pub use unpack::Unpacker;
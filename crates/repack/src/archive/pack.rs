@@ -0,0 +1,465 @@
+use crate::{
+    archive::exclude::Exclude,
+    compression::ZstdParams,
+    io_buffer::{buf_writer, create_file},
+};
+use std::{
+    collections::HashMap,
+    fs::{self, File, Metadata},
+    io::{self, Write},
+    os::unix::{ffi::OsStrExt, fs::MetadataExt},
+    path::{Path, PathBuf},
+};
+use tar::{Builder, EntryType, Header, HeaderMode};
+
+/// Packs a directory (e.g., rootfs) into a POSIX (pax) tar stream.
+///
+/// Equivalent to `tar --posix --directory src_dir --exclude=... -cf - .`,
+/// but without the `tar` binary.
+///
+/// Keeps uid/gid/mode, hard links, symlinks, device nodes and fifos.
+/// Extended attributes are stored as `SCHILY.xattr.*` pax records.
+/// Sockets are skipped (same as GNU tar).
+///
+/// See also: [`Packer::with_source_date_epoch`] for bit-for-bit reproducible archives.
+///
+/// # Example
+///
+/// ```no_run
+/// # use repack::{archive::{Exclude, Packer}, compression::ZstdParams};
+/// # fn main() -> std::io::Result<()> {
+/// Packer::new("rootfs")
+///     .with_exclude(Exclude::from_iter(["proc/*", "sys/*"]))
+///     .pack_to_zst("rootfs.tar.zst", 19, &ZstdParams::default())?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct Packer {
+    src_dir: PathBuf,
+    exclude: Exclude,
+    source_date_epoch: Option<i64>,
+}
+
+/// (st_dev, st_ino) of the first path of a hard link group -> its name in the archive.
+type HardLinks = HashMap<(u64, u64), PathBuf>;
+
+impl Packer {
+    pub fn new<P: AsRef<Path>>(src_dir: P) -> Self {
+        Self {
+            src_dir: src_dir.as_ref().to_owned(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_exclude(mut self, exclude: Exclude) -> Self {
+        self.exclude = exclude;
+        self
+    }
+
+    pub fn get_exclude(&self) -> &Exclude {
+        &self.exclude
+    }
+
+    /// Enables the reproducible mode, similar to
+    /// `tar --sort=name --mtime=@EPOCH --clamp-mtime --numeric-owner --pax-option=delete=atime,delete=ctime`.
+    ///
+    /// - Entries are sorted by name.
+    /// - mtime is clamped to `source_date_epoch` (seconds since 1970-01-01 UTC), and nanoseconds are dropped.
+    /// - atime & ctime are not stored.
+    /// - Owner & group names are left empty, only uid/gid are kept.
+    pub fn with_source_date_epoch(mut self, source_date_epoch: Option<i64>) -> Self {
+        self.source_date_epoch = source_date_epoch;
+        self
+    }
+
+    pub fn get_source_date_epoch(&self) -> Option<i64> {
+        self.source_date_epoch
+    }
+
+    /// Packs `src_dir` to `tar_path` (uncompressed).
+    pub fn pack_to_file<P: AsRef<Path>>(&self, tar_path: P) -> io::Result<()> {
+        let mut file = self.pack(buf_writer(tar_path)?)?;
+        file.flush()
+    }
+
+    /// Packs `src_dir` and compresses the tar stream to `zstd_path` in a single pass.
+    pub fn pack_to_zst<P: AsRef<Path>>(
+        &self,
+        zstd_path: P,
+        level: i32,
+        params: &ZstdParams,
+    ) -> io::Result<()> {
+        let encoder =
+            crate::zstd::new_encoder(create_file(zstd_path)?, level, params, None)?;
+        self.pack(encoder)?.finish()?;
+        Ok(())
+    }
+
+    /// Packs `src_dir` to `tar_path` and compresses the same tar stream into `zstd_out`, i.e., the rootfs is only read once.
+    ///
+    /// Returns `zstd_out` (not flushed), e.g., a file wrapped by a hasher.
+    pub fn pack_to_file_and_zst<T: AsRef<Path>, W: Write>(
+        &self,
+        tar_path: T,
+        zstd_out: W,
+        level: i32,
+        params: &ZstdParams,
+    ) -> io::Result<W> {
+        let encoder = crate::zstd::new_encoder(zstd_out, level, params, None)?;
+        let Tee(mut file, encoder) =
+            self.pack(Tee(buf_writer(tar_path)?, encoder))?;
+        file.flush()?;
+        encoder.finish()
+    }
+
+    /// Writes the whole tar stream (including the end-of-archive blocks) into `writer` and returns it.
+    pub fn pack<W: Write>(&self, writer: W) -> io::Result<W> {
+        let mut builder = Builder::new(writer);
+        let mut hard_links = HardLinks::new();
+
+        let root_meta = fs::symlink_metadata(&self.src_dir)?;
+        if !root_meta.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{:?} is not a directory", self.src_dir),
+            ));
+        }
+        self.append_entry(
+            &mut builder,
+            &self.src_dir,
+            Path::new("."),
+            &root_meta,
+            &mut hard_links,
+        )?;
+        self.walk(&mut builder, &self.src_dir, Path::new(""), &mut hard_links)?;
+
+        builder.into_inner()
+    }
+
+    fn walk<W: Write>(
+        &self,
+        builder: &mut Builder<W>,
+        dir: &Path,
+        rel_dir: &Path,
+        hard_links: &mut HardLinks,
+    ) -> io::Result<()> {
+        let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+        if self.source_date_epoch.is_some() {
+            entries.sort_unstable_by_key(|x| x.file_name());
+        }
+
+        for entry in entries {
+            let rel_path = rel_dir.join(entry.file_name());
+
+            if self
+                .exclude
+                .is_excluded(&rel_path)
+            {
+                continue;
+            }
+
+            let path = entry.path();
+            let meta = fs::symlink_metadata(&path)?;
+            self.append_entry(builder, &path, &rel_path, &meta, hard_links)?;
+
+            if meta.is_dir() {
+                self.walk(builder, &path, &rel_path, hard_links)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn append_entry<W: Write>(
+        &self,
+        builder: &mut Builder<W>,
+        path: &Path,
+        rel_path: &Path,
+        meta: &Metadata,
+        hard_links: &mut HardLinks,
+    ) -> io::Result<()> {
+        let file_type = meta.file_type();
+        {
+            use std::os::unix::fs::FileTypeExt;
+            if file_type.is_socket() {
+                return Ok(());
+            }
+        }
+
+        let mut header = Header::new_ustar();
+        header.set_metadata_in_mode(meta, HeaderMode::Complete);
+
+        let mut pax = PaxRecords::default();
+        match self.source_date_epoch {
+            Some(epoch) => header.set_mtime(meta.mtime().min(epoch).max(0) as _),
+            _ => pax.push_times(meta),
+        }
+        pax.push_xattrs(path);
+
+        if header
+            .set_path(rel_path)
+            .is_err()
+        {
+            pax.push("path", rel_path.as_os_str().as_bytes());
+            header.set_path(truncated_name(rel_path))?;
+        }
+
+        let mut link_name = None;
+
+        if file_type.is_symlink() {
+            link_name = Some(fs::read_link(path)?);
+        } else if !meta.is_dir() && meta.nlink() > 1 {
+            let key = (meta.dev(), meta.ino());
+            match hard_links.get(&key) {
+                Some(first) => {
+                    header.set_entry_type(EntryType::Link);
+                    header.set_size(0);
+                    link_name = Some(first.to_owned());
+                }
+                _ => {
+                    hard_links.insert(key, rel_path.to_owned());
+                }
+            }
+        }
+
+        if let Some(target) = link_name {
+            if header
+                .set_link_name(&target)
+                .is_err()
+            {
+                pax.push("linkpath", target.as_os_str().as_bytes());
+                header.set_link_name(truncated_name(&target))?;
+            }
+        }
+
+        let entry_type = header.entry_type();
+        if entry_type.is_character_special() || entry_type.is_block_special() {
+            let rdev = meta.rdev();
+            header.set_device_major(dev_major(rdev))?;
+            header.set_device_minor(dev_minor(rdev))?;
+        }
+
+        pax.append_to(builder)?;
+        header.set_cksum();
+
+        match entry_type.is_file() {
+            true => builder.append(&header, File::open(path)?),
+            _ => builder.append(&header, io::empty()),
+        }
+    }
+}
+
+/// Writes the same bytes to both writers.
+struct Tee<A, B>(A, B);
+
+impl<A: Write, B: Write> Write for Tee<A, B> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write_all(buf)?;
+        self.1.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()?;
+        self.1.flush()
+    }
+}
+
+/// The ustar `name` field is only a fallback when the full path is stored in a pax record.
+fn truncated_name(p: &Path) -> PathBuf {
+    let name = p
+        .file_name()
+        .unwrap_or(p.as_os_str());
+    let bytes = name.as_bytes();
+    let end = bytes.len().min(99);
+    PathBuf::from(String::from_utf8_lossy(&bytes[..end]).as_ref())
+}
+
+#[derive(Debug, Default)]
+struct PaxRecords(Vec<(String, Vec<u8>)>);
+
+impl PaxRecords {
+    fn push<K: Into<String>>(&mut self, key: K, value: &[u8]) {
+        self.0
+            .push((key.into(), value.to_vec()))
+    }
+
+    /// Same as `tar --posix`: mtime, atime & ctime with nanoseconds.
+    fn push_times(&mut self, meta: &Metadata) {
+        for (key, sec, nsec) in [
+            ("mtime", meta.mtime(), meta.mtime_nsec()),
+            ("atime", meta.atime(), meta.atime_nsec()),
+            ("ctime", meta.ctime(), meta.ctime_nsec()),
+        ] {
+            self.push(key, pax_time(sec, nsec).as_bytes())
+        }
+    }
+
+    fn push_xattrs(&mut self, path: &Path) {
+        // Unsupported filesystems (e.g., tmpfs without user xattr) return an error, which can be ignored.
+        let Ok(names) = xattr::list(path) else {
+            return;
+        };
+        let mut names = names.collect::<Vec<_>>();
+        names.sort_unstable();
+
+        for name in names {
+            if let Ok(Some(value)) = xattr::get(path, &name) {
+                let key = format!("SCHILY.xattr.{}", name.to_string_lossy());
+                self.push(key, &value)
+            }
+        }
+    }
+
+    fn append_to<W: Write>(&self, builder: &mut Builder<W>) -> io::Result<()> {
+        builder.append_pax_extensions(
+            self.0
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.as_slice())),
+        )
+    }
+}
+
+fn pax_time(sec: i64, nsec: i64) -> String {
+    match nsec {
+        0 => sec.to_string(),
+        n => format!("{sec}.{n:09}"),
+    }
+}
+
+/// glibc: gnu_dev_major
+const fn dev_major(dev: u64) -> u32 {
+    (((dev >> 32) & 0xffff_f000) | ((dev >> 8) & 0x0000_0fff)) as u32
+}
+
+/// glibc: gnu_dev_minor
+const fn dev_minor(dev: u64) -> u32 {
+    (((dev >> 12) & 0xffff_ff00) | (dev & 0x0000_00ff)) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn pack_dir_with_exclude() -> io::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let root = tmp.path();
+        for dir in ["proc/1", "var/tmp", "usr/bin"] {
+            fs::create_dir_all(root.join(dir))?;
+        }
+        fs::write(root.join("proc/1/status"), "excluded")?;
+        fs::write(root.join("var/tmp/x"), "excluded")?;
+        fs::write(root.join("usr/bin/bash"), "#!")?;
+        fs::hard_link(root.join("usr/bin/bash"), root.join("usr/bin/sh"))?;
+        symlink("usr/bin", root.join("bin"))?;
+
+        let tar = Packer::new(root)
+            .with_exclude(Exclude::from_iter(["proc/*", "var/tmp/*"]))
+            .pack(Vec::new())?;
+
+        let mut archive = tar::Archive::new(tar.as_slice());
+        let mut names = Vec::new();
+        for entry in archive.entries()? {
+            let entry = entry?;
+            let header = entry.header();
+            let name = entry.path()?.into_owned();
+
+            match name.to_str() {
+                Some("bin") => assert!(header.entry_type().is_symlink()),
+                Some(n @ ("usr/bin/bash" | "usr/bin/sh")) => {
+                    // whichever comes first is the regular file
+                    let is_link = header.entry_type() == EntryType::Link;
+                    let first = names
+                        .iter()
+                        .any(|x: &PathBuf| x.ends_with("bash") || x.ends_with("sh"));
+                    assert_eq!(is_link, first, "{n}");
+                }
+                _ => {}
+            }
+            names.push(name);
+        }
+
+        let has = |s: &str| names.contains(&PathBuf::from(s));
+        assert!(has("."));
+        assert!(has("proc"));
+        assert!(has("var/tmp"));
+        assert!(has("usr/bin/bash"));
+        assert!(!has("proc/1"));
+        assert!(!has("var/tmp/x"));
+        Ok(())
+    }
+
+    #[test]
+    fn reproducible_tar() -> io::Result<()> {
+        use std::time::{Duration, SystemTime};
+
+        let tmp = tempfile::tempdir()?;
+        let root = tmp.path();
+        for f in ["b", "a", "c/d"] {
+            let path = root.join(f);
+            fs::create_dir_all(path.parent().unwrap())?;
+            fs::write(path, f)?;
+        }
+        const EPOCH: i64 = 900_000_000;
+        let packer = Packer::new(root).with_source_date_epoch(Some(EPOCH));
+        let first = packer.pack(Vec::new())?;
+
+        // newer than SOURCE_DATE_EPOCH => clamped
+        File::options()
+            .write(true)
+            .open(root.join("a"))?
+            .set_modified(SystemTime::now() + Duration::from_secs(60))?;
+        assert_eq!(first, packer.pack(Vec::new())?);
+
+        let mut archive = tar::Archive::new(first.as_slice());
+        let mut names = Vec::new();
+        for entry in archive.entries()? {
+            let entry = entry?;
+            assert_eq!(entry.header().mtime()?, EPOCH as u64);
+            names.push(entry.path()?.into_owned());
+        }
+        assert_eq!(names, [".", "a", "b", "c", "c/d"].map(PathBuf::from));
+        Ok(())
+    }
+
+    #[test]
+    fn negative_source_date_epoch() -> io::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        fs::write(tmp.path().join("a"), "a")?;
+
+        let tar = Packer::new(tmp.path())
+            .with_source_date_epoch(Some(-1))
+            .pack(Vec::new())?;
+        for entry in tar::Archive::new(tar.as_slice()).entries()? {
+            assert_eq!(entry?.header().mtime()?, 0);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn pack_tar_and_zst_in_one_pass() -> io::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let root = tmp.path().join("rootfs");
+        fs::create_dir_all(root.join("etc"))?;
+        fs::write(root.join("etc/hostname"), "potato")?;
+
+        let tar = tmp.path().join("a.tar");
+        let zst = Packer::new(&root)
+            .with_source_date_epoch(Some(0))
+            .pack_to_file_and_zst(&tar, Vec::new(), 3, &ZstdParams::default())?;
+
+        let decoded = zstd::decode_all(zst.as_slice())?;
+        assert_eq!(decoded, fs::read(&tar)?);
+        Ok(())
+    }
+
+    #[test]
+    fn device_numbers() {
+        // makedev(4, 64) => /dev/ttyS0
+        let dev = (4 << 8) | 64;
+        assert_eq!(dev_major(dev), 4);
+        assert_eq!(dev_minor(dev), 64);
+    }
+}
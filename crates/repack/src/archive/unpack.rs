@@ -0,0 +1,286 @@
+use std::{
+    ffi::CString,
+    fs,
+    io::{self, Read},
+    os::unix::{
+        ffi::OsStrExt,
+        fs::{lchown, PermissionsExt},
+    },
+    path::{Component, Path, PathBuf},
+};
+use tar::{Archive, Entry, EntryType};
+
+/// Extracts a tar stream into a directory (e.g., rootfs).
+///
+/// Equivalent to `tar --directory dst_dir -xf -`,
+/// but without the `tar` binary.
+///
+/// Keeps mode, mtime, hard links, symlinks and xattrs (`SCHILY.xattr.*`).
+/// Device nodes & fifos are created with mknod(2).
+///
+/// Same as GNU tar, the uid/gid are only restored when running as root.
+///
+/// # Example
+///
+/// ```no_run
+/// # use repack::archive::Unpacker;
+/// # fn main() -> std::io::Result<()> {
+/// let tar = std::fs::File::open("rootfs.tar")?;
+/// Unpacker::new("rootfs").unpack(tar)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Unpacker {
+    dst_dir: PathBuf,
+    preserve_ownerships: bool,
+}
+
+impl Unpacker {
+    pub fn new<P: AsRef<Path>>(dst_dir: P) -> Self {
+        Self {
+            dst_dir: dst_dir.as_ref().to_owned(),
+            preserve_ownerships: unsafe { libc::geteuid() } == 0,
+        }
+    }
+
+    pub fn with_preserve_ownerships(mut self, preserve: bool) -> Self {
+        self.preserve_ownerships = preserve;
+        self
+    }
+
+    pub fn get_dst_dir(&self) -> &Path {
+        &self.dst_dir
+    }
+
+    pub fn unpack<R: Read>(&self, reader: R) -> io::Result<()> {
+        let dst = &self.dst_dir;
+        if dst.symlink_metadata().is_err() {
+            fs::create_dir_all(dst)?;
+        }
+
+        // The nodes must not be created through a symlink that points outside (e.g., `a -> /etc`, `a/passwd`).
+        let canon_dst = dst.canonicalize()?;
+
+        let mut archive = Archive::new(reader);
+        archive.set_preserve_permissions(true);
+        archive.set_preserve_mtime(true);
+        archive.set_preserve_ownerships(self.preserve_ownerships);
+        archive.set_unpack_xattrs(true);
+        archive.set_overwrite(true);
+
+        // Same as tar::Archive::unpack: directories are applied at the end,
+        // so that their permissions & mtime are not changed by descendants.
+        let mut directories = Vec::new();
+
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let entry_type = entry.header().entry_type();
+
+            match entry_type {
+                EntryType::Directory => directories.push(entry),
+                EntryType::Char | EntryType::Block | EntryType::Fifo => {
+                    self.make_node(&canon_dst, &entry, entry_type)?
+                }
+                _ => {
+                    entry.unpack_in(dst)?;
+                }
+            }
+        }
+
+        directories.sort_by(|a, b| {
+            b.path_bytes()
+                .cmp(&a.path_bytes())
+        });
+        for mut dir in directories {
+            dir.unpack_in(dst)?;
+        }
+
+        Ok(())
+    }
+
+    fn make_node<R: Read>(
+        &self,
+        canon_dst: &Path,
+        entry: &Entry<'_, R>,
+        entry_type: EntryType,
+    ) -> io::Result<()> {
+        let Some(path) = join_inside(&self.dst_dir, &entry.path()?) else {
+            // e.g., `../dev/null`
+            return Ok(());
+        };
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            return Ok(());
+        };
+        let header = entry.header();
+        let mode = header.mode()?;
+
+        let (kind, dev) = match entry_type {
+            EntryType::Char | EntryType::Block => {
+                let major = header
+                    .device_major()?
+                    .unwrap_or(0);
+                let minor = header
+                    .device_minor()?
+                    .unwrap_or(0);
+                let kind = match entry_type {
+                    EntryType::Char => libc::S_IFCHR,
+                    _ => libc::S_IFBLK,
+                };
+                (kind, libc::makedev(major, minor))
+            }
+            _ => (libc::S_IFIFO, 0),
+        };
+
+        create_dir_inside(canon_dst, parent)?;
+        let path = canonicalize_inside(canon_dst, parent)?.join(name);
+        if path.symlink_metadata().is_ok() {
+            fs::remove_file(&path)?;
+        }
+
+        let c_path = CString::new(path.as_os_str().as_bytes())?;
+        if unsafe { libc::mknod(c_path.as_ptr(), kind | (mode & 0o7777), dev) } != 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+
+        if self.preserve_ownerships {
+            lchown(&path, Some(header.uid()? as _), Some(header.gid()? as _))?;
+        }
+        // mknod(2) is affected by umask
+        fs::set_permissions(&path, fs::Permissions::from_mode(mode))
+    }
+}
+
+/// Same as `tar::Entry::unpack_in`: creates the missing ancestors of `dir` one by one,
+/// and checks that the parent of each one is still inside `canon_dst`.
+fn create_dir_inside(canon_dst: &Path, dir: &Path) -> io::Result<()> {
+    let mut missing = Vec::new();
+    let mut ancestor = dir;
+    while ancestor
+        .symlink_metadata()
+        .is_err()
+    {
+        missing.push(ancestor);
+        match ancestor.parent() {
+            Some(p) => ancestor = p,
+            _ => break,
+        }
+    }
+    for dir in missing.into_iter().rev() {
+        if let Some(parent) = dir.parent() {
+            canonicalize_inside(canon_dst, parent)?;
+        }
+        fs::create_dir_all(dir)?;
+    }
+    Ok(())
+}
+
+/// Resolves the symlinks of `dir`, returns an error if it points outside `canon_dst`.
+fn canonicalize_inside(canon_dst: &Path, dir: &Path) -> io::Result<PathBuf> {
+    let canon = dir.canonicalize()?;
+    match canon.starts_with(canon_dst) {
+        true => Ok(canon),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{dir:?} points outside of {canon_dst:?} (resolved to {canon:?})"
+            ),
+        )),
+    }
+}
+
+/// Returns None if `path` would escape from `dst`.
+fn join_inside(dst: &Path, path: &Path) -> Option<PathBuf> {
+    let mut out = dst.to_owned();
+    for c in path.components() {
+        match c {
+            Component::Normal(x) => out.push(x),
+            Component::CurDir | Component::RootDir => {}
+            Component::ParentDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::Packer;
+    use std::os::unix::fs::{symlink, MetadataExt};
+
+    #[test]
+    fn pack_and_unpack() -> io::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let src = tmp.path().join("src");
+        fs::create_dir_all(src.join("usr/bin"))?;
+        fs::write(src.join("usr/bin/bash"), "#!")?;
+        fs::set_permissions(
+            src.join("usr/bin/bash"),
+            fs::Permissions::from_mode(0o755),
+        )?;
+        fs::hard_link(src.join("usr/bin/bash"), src.join("usr/bin/sh"))?;
+        symlink("usr/bin", src.join("bin"))?;
+
+        let tar = Packer::new(&src).pack(Vec::new())?;
+
+        let dst = tmp.path().join("dst");
+        Unpacker::new(&dst)
+            .with_preserve_ownerships(false)
+            .unpack(tar.as_slice())?;
+
+        let bash = fs::metadata(dst.join("usr/bin/bash"))?;
+        assert_eq!(bash.mode() & 0o7777, 0o755);
+        assert_eq!(bash.ino(), fs::metadata(dst.join("usr/bin/sh"))?.ino());
+        assert_eq!(fs::read_link(dst.join("bin"))?, Path::new("usr/bin"));
+        assert_eq!(fs::read(dst.join("bin/bash"))?, b"#!");
+        Ok(())
+    }
+
+    #[test]
+    fn join_path_inside_dst() {
+        let dst = Path::new("/rootfs");
+        assert_eq!(
+            join_inside(dst, Path::new("./dev/null")),
+            Some(PathBuf::from("/rootfs/dev/null"))
+        );
+        assert_eq!(join_inside(dst, Path::new("../dev/null")), None);
+    }
+
+    /// A symlink `a -> /etc`, followed by a node `a/passwd`.
+    ///
+    /// The symlink points to a stand-in "etc" outside the destination, instead of the host /etc.
+    #[test]
+    fn node_through_symlink_stays_inside() -> io::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let etc = tmp.path().join("etc");
+        fs::create_dir(&etc)?;
+        fs::write(etc.join("passwd"), "root:x:0:0::/root:/bin/sh")?;
+
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut link = tar::Header::new_ustar();
+        link.set_entry_type(EntryType::Symlink);
+        link.set_size(0);
+        link.set_mode(0o777);
+        builder.append_link(&mut link, "a", &etc)?;
+
+        let mut fifo = tar::Header::new_ustar();
+        fifo.set_entry_type(EntryType::Fifo);
+        fifo.set_size(0);
+        fifo.set_mode(0o644);
+        builder.append_data(&mut fifo, "a/passwd", io::empty())?;
+        let tar = builder.into_inner()?;
+
+        let dst = tmp.path().join("dst");
+        let err = Unpacker::new(&dst)
+            .with_preserve_ownerships(false)
+            .unpack(tar.as_slice())
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{err}");
+
+        let passwd = etc.join("passwd");
+        assert!(fs::symlink_metadata(&passwd)?.is_file());
+        assert_eq!(fs::read_to_string(passwd)?, "root:x:0:0::/root:/bin/sh");
+        Ok(())
+    }
+}
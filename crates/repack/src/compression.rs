@@ -1,7 +1,18 @@
 use core::fmt::Debug;
-use std::{io, path::Path};
+use std::{
+    fs,
+    io::{Read, Write},
+    path::Path,
+    sync::Arc,
+};
 
-use crate::format::Format;
+use crate::{
+    dict::ZstdDict,
+    error::{Error, FileIo},
+    format::Format,
+    io_buffer::{buf_reader, buf_writer, create_file},
+    progress::ProgressCallback,
+};
 
 /// - Decode
 ///     - Full: a-dir.tar.zst => a-dir
@@ -12,6 +23,7 @@ pub enum Layer {
     OuterMost,
 }
 
+#[allow(clippy::derivable_impls)]
 impl Default for Layer {
     fn default() -> Self {
         Self::Full
@@ -48,6 +60,49 @@ impl Default for Operation {
     }
 }
 
+/// Same as `zstd --long=N`: long distance matching & window log.
+///
+/// The default is `--long=27`.
+/// Note: `zstd -d` requires `--long=N` (or `--memory=`) if `window_log > 27`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ZstdParams {
+    pub long_distance: bool,
+    /// None: determined by the level
+    pub window_log: Option<u32>,
+}
+
+impl Default for ZstdParams {
+    fn default() -> Self {
+        Self::long(27)
+    }
+}
+
+impl ZstdParams {
+    /// Enables long distance matching with the window log `n` (10 ~ 31).
+    pub const fn long(n: u32) -> Self {
+        Self {
+            long_distance: true,
+            window_log: Some(n),
+        }
+    }
+
+    /// Disables long distance matching. The window log is determined by the level.
+    pub const fn disabled() -> Self {
+        Self {
+            long_distance: false,
+            window_log: None,
+        }
+    }
+
+    /// Same as `zstd --long=N`, but `0` disables long distance matching.
+    pub const fn from_long(n: u32) -> Self {
+        match n {
+            0 => Self::disabled(),
+            n => Self::long(n),
+        }
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct UpackFile<P>
 where
@@ -61,14 +116,31 @@ impl<P> UpackFile<P>
 where
     P: AsRef<Path>,
 {
+    /// Determines the format by the file name.
     pub fn new(path: P) -> Self {
         let format = Format::from(to_lowercase_file_name(&path));
 
         Self { path, format }
     }
 
-    // TODO: Using Magic Bytes instead of FileName
-    // pub fn new_src() {}
+    /// If the file exists, the format is determined by its magic bytes.
+    /// Otherwise, it falls back to [`UpackFile::new`].
+    pub fn new_src(path: P) -> Self {
+        let by_name = Self::new(path);
+        let p = by_name.path.as_ref();
+        if !p.is_file() {
+            return by_name;
+        }
+
+        let format = match Format::sniff(p) {
+            Ok(Format::Unknown) | Err(_) => by_name.format,
+            // e.g., a.tar.xz: the tar layer can only be known from the file name.
+            Ok(f) if f == by_name.format.outer() => by_name.format,
+            Ok(f) => f,
+        };
+
+        Self { format, ..by_name }
+    }
 
     pub fn get_format(&self) -> &Format {
         &self.format
@@ -91,6 +163,10 @@ where
     pub source: UpackFile<S>,
     pub target: UpackFile<D>,
     pub operation: Operation,
+    pub zstd_params: ZstdParams,
+    /// Required for both encoding & decoding if the zstd file was compressed with it.
+    pub zstd_dict: Option<Arc<ZstdDict>>,
+    pub progress: Option<ProgressCallback>,
 }
 
 impl<S, D> Upack<S, D>
@@ -107,25 +183,36 @@ where
     /// ## Decompress **base.tgz** to **base.tar**
     ///
     /// ```no_run
+    /// # use repack::compression::Upack;
+    /// # fn main() -> repack::Result<()> {
     /// let de_gz = Upack::new("base.tgz", "base.tar");
     ///
     /// de_gz.run()?;
+    /// # Ok(())
+    /// # }
     /// ```
     ///
     /// ## Compress **file.tar** to **file.tar.zst**
     ///
     /// ```no_run
+    /// # use repack::compression::Upack;
+    /// # fn main() -> repack::Result<()> {
     /// let compress_to_zstd = Upack::new("file.tar", "file.tar.zst")
     ///     // .with_operation(Operation::Encode { level: 22 });
     ///     .encode_with_max_lv();
     ///
     /// compress_to_zstd.run()?;
+    /// # Ok(())
+    /// # }
     /// ```
     pub fn new(source: S, target: D) -> Self {
         Self {
-            source: UpackFile::new(source),
+            source: UpackFile::new_src(source),
             target: UpackFile::new(target),
             operation: Default::default(),
+            zstd_params: Default::default(),
+            zstd_dict: None,
+            progress: None,
         }
     }
 
@@ -145,29 +232,105 @@ where
         self
     }
 
-    /// Decode gz or Encode zst
-    pub fn run(&self) -> io::Result<()> {
+    pub fn with_zstd_params(mut self, params: ZstdParams) -> Self {
+        self.zstd_params = params;
+        self
+    }
+
+    pub fn with_zstd_dict<T: Into<Arc<ZstdDict>>>(mut self, dict: T) -> Self {
+        self.zstd_dict = Some(dict.into());
+        self
+    }
+
+    pub fn with_progress(mut self, callback: ProgressCallback) -> Self {
+        self.progress = Some(callback);
+        self
+    }
+
+    /// Compresses `src` into `out` (Encode only), then returns `out` (not flushed).
+    ///
+    /// The codec is determined by the target format, and the target path is not written, e.g., `out` can hash the archive in the same pass.
+    ///
+    /// The errors of `src` & `out` are reported as [Error::Io].
+    pub fn encode_stream<R: Read, W: Write>(
+        &self,
+        src: R,
+        out: W,
+    ) -> crate::Result<W> {
+        use Format::*;
+        let (src, out) = (FileIo(src), FileIo(out));
+
+        let out = match (&self.operation, self.target.get_format()) {
+            (Operation::Encode { level }, TarZstd | Zstd) => {
+                self.compress_to_zst(*level as _, src, out)?
+            }
+            (Operation::Encode { level }, TarXz | Xz) => {
+                self.compress_to_xz(*level, src, out)?
+            }
+            (Operation::Encode { level }, TarLz | Lz) => {
+                self.compress_to_lz(*level, src, out)?
+            }
+            (Operation::Encode { level }, TarGz | Gz) => {
+                self.compress_to_gz(*level, src, out)?
+            }
+            (op, dst) => {
+                return Err(Error::UnsupportedOperation {
+                    operation: *op,
+                    src: *self.source.get_format(),
+                    dst: *dst,
+                })
+            }
+        };
+        Ok(out.0)
+    }
+
+    /// - Encode: zst, xz, lz, gz
+    /// - Decode(OuterMost): gz, zst, xz, bz2, lz
+    /// - Decode(Full): tar, tar.gz, tar.zst, tar.xz, tar.bz2, tar.lz => directory
+    pub fn run(&self) -> crate::Result<()> {
         let src_fmt = self.source.get_format();
         let dst_fmt = self.target.get_format();
         {
             use Format::*;
-            use Layer::OuterMost;
+            use Layer::{Full, OuterMost};
             use Operation::*;
 
             match (&self.operation, src_fmt, dst_fmt) {
                 // (Encode{level}, s, TarZstd) if !s.is_tar() => self.compress_to_tar_zst(*level as _)?,
-                (Encode { level }, _, TarZstd | Zstd) => {
-                    self.compress_to_zst(*level as _)?
+                (Encode { .. }, _, TarZstd | Zstd) => {
+                    self.encode_stream(
+                        buf_reader(&self.source.path)?,
+                        create_file(&self.target.path)?,
+                    )?;
+                }
+                (Encode { .. }, _, TarXz | Xz | TarLz | Lz | TarGz | Gz) => {
+                    self.encode_stream(
+                        buf_reader(&self.source.path)?,
+                        buf_writer(&self.target.path)?,
+                    )?
+                    .flush()?;
+                }
+                (
+                    Decode(OuterMost),
+                    Gz | TarGz | Zstd | TarZstd | Xz | TarXz | Bz2 | TarBz2 | Lz
+                    | TarLz,
+                    _,
+                ) => self.decompress()?,
+                (
+                    Decode(Full),
+                    Tar | TarGz | TarZstd | TarXz | TarBz2 | TarLz,
+                    _,
+                ) => self.extract()?,
+                // e.g., misnamed base.tgz (uncompressed)
+                (Decode(OuterMost), Tar, Tar | Unknown) => {
+                    fs::copy(&self.source.path, &self.target.path)?;
                 }
-                (Decode(OuterMost), Gz | TarGz, _) => self.decompress_gz()?,
                 (op, src, dst) => {
-                    panic!(
-                        "[FATAL] Unsupported operation: {op:?}\n\
-                        {self:?}\n\t\
-                        src-fmt:{src:?}\n\t\
-                        dst-fmt: {dst:?}
-                        "
-                    )
+                    return Err(Error::UnsupportedOperation {
+                        operation: *op,
+                        src: *src,
+                        dst: *dst,
+                    })
                 }
             }
         }
@@ -0,0 +1,211 @@
+use crate::{
+    archive::Unpacker,
+    compression::Upack,
+    dict::ZstdDict,
+    error::{DecoderIo, Error, FileIo},
+    format::Format,
+    io_buffer::{buf_reader, buf_writer},
+};
+use std::{
+    io::{self, BufRead, Read, Write},
+    path::Path,
+};
+
+/// Wraps `reader` with the decoder of the outermost layer of `format`.
+///
+/// Tar & Unknown are passed through as is.
+pub(crate) fn new_decoder<'r, R: BufRead + 'r>(
+    format: &Format,
+    reader: R,
+    zstd_dict: Option<&ZstdDict>,
+) -> crate::Result<Box<dyn Read + 'r>> {
+    use Format::*;
+    let decoder: Box<dyn Read> = match format.outer() {
+        Gz => Box::new(flate2::bufread::MultiGzDecoder::new(reader)),
+        Zstd => {
+            let codec_err = Error::codec(*format);
+            let dict = zstd_dict.map_or(&[][..], ZstdDict::as_bytes);
+            let mut decoder =
+                zstd::Decoder::with_dictionary(reader, dict).map_err(codec_err)?;
+            // Same as `zstd -d --long=31`: accepts frames encoded with window_log > 27.
+            decoder
+                .window_log_max(31)
+                .map_err(Error::codec(*format))?;
+            Box::new(decoder)
+        }
+        Xz => Box::new(liblzma::bufread::XzDecoder::new_multi_decoder(reader)),
+        Bz2 => Box::new(bzip2::bufread::MultiBzDecoder::new(reader)),
+        Lz => Box::new(lzma_rust2::LzipReader::new(reader)),
+        _ => Box::new(reader),
+    };
+    Ok(decoder)
+}
+
+impl<S, D> Upack<S, D>
+where
+    S: AsRef<Path>,
+    D: AsRef<Path>,
+{
+    /// Decode(OuterMost), e.g., a.tar.zst => a.tar
+    pub(crate) fn decompress(&self) -> crate::Result<()> {
+        let src_fmt = self.source.get_format();
+        let mut src_file =
+            self.progress_reader(FileIo(buf_reader(&self.source.path)?));
+        let mut dst_file =
+            src_file.counting_writer(FileIo(buf_writer(&self.target.path)?));
+
+        let mut decoder =
+            new_decoder(src_fmt, &mut src_file, self.zstd_dict.as_deref())?;
+        io::copy(&mut decoder, &mut dst_file).map_err(Error::codec(*src_fmt))?;
+        dst_file.flush()?;
+        drop(decoder);
+
+        src_file.finish();
+        Ok(())
+    }
+
+    /// Decode(Full), e.g., a.tar.zst => a/
+    pub(crate) fn extract(&self) -> crate::Result<()> {
+        let src_fmt = self.source.get_format();
+        let mut src_file =
+            self.progress_reader(FileIo(buf_reader(&self.source.path)?));
+        let decoder =
+            new_decoder(src_fmt, &mut src_file, self.zstd_dict.as_deref())?;
+
+        Unpacker::new(&self.target.path)
+            .unpack(DecoderIo(decoder))
+            .map_err(Error::decoded(*src_fmt))?;
+
+        src_file.finish();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        archive::Packer,
+        compression::{Operation, Upack, ZstdParams},
+        Error,
+    };
+    use std::{fs, io};
+
+    #[test]
+    fn decode_xz_and_bz2() -> crate::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let src = tmp.path().join("src");
+        fs::create_dir_all(src.join("etc"))?;
+        fs::write(src.join("etc/hostname"), "debian")?;
+
+        let tar = Packer::new(&src).pack(Vec::new())?;
+
+        let xz = liblzma::write::XzEncoder::new(Vec::new(), 6);
+        let tar_xz = tmp.path().join("a.tar.xz");
+        fs::write(
+            &tar_xz,
+            Packer::new(&src)
+                .pack(xz)?
+                .finish()?,
+        )?;
+
+        let dst = tmp.path().join("a");
+        Upack::new(&tar_xz, &dst)
+            .with_operation(Operation::decode_full())
+            .run()?;
+        assert_eq!(fs::read(dst.join("etc/hostname"))?, b"debian");
+
+        let mut bz2 = bzip2::write::BzEncoder::new(Vec::new(), Default::default());
+        io::Write::write_all(&mut bz2, &tar)?;
+        // misnamed
+        let tar_gz = tmp.path().join("b.tar.gz");
+        fs::write(&tar_gz, bz2.finish()?)?;
+
+        let b_tar = tmp.path().join("b.tar");
+        Upack::new(&tar_gz, &b_tar).run()?;
+        assert_eq!(fs::read(b_tar)?, tar);
+        Ok(())
+    }
+
+    #[test]
+    fn io_errors_are_not_codec_errors() -> crate::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let src = tmp.path().join("src");
+        fs::create_dir_all(&src)?;
+        fs::write(src.join("hostname"), "debian")?;
+        let tar_xz = tmp.path().join("a.tar.xz");
+        let xz = liblzma::write::XzEncoder::new(Vec::new(), 6);
+        fs::write(
+            &tar_xz,
+            Packer::new(&src)
+                .pack(xz)?
+                .finish()?,
+        )?;
+
+        // corrupted
+        let bad = tmp.path().join("bad.tar.xz");
+        fs::write(&bad, b"not xz")?;
+        let err = Upack::new(&bad, tmp.path().join("bad.tar"))
+            .run()
+            .unwrap_err();
+        assert!(matches!(err, Error::Codec { .. }), "{err:?}");
+
+        // the source cannot be read
+        let dir = tmp.path().join("dir.tar.xz");
+        fs::create_dir(&dir)?;
+        let err = Upack::new(&dir, tmp.path().join("dir.tar"))
+            .run()
+            .unwrap_err();
+        assert!(matches!(err, Error::Io(_)), "{err:?}");
+
+        // the files cannot be created
+        let file = tmp.path().join("file");
+        fs::write(&file, "")?;
+        let err = Upack::new(&tar_xz, file.join("a"))
+            .with_operation(Operation::decode_full())
+            .run()
+            .unwrap_err();
+        assert!(matches!(err, Error::Io(_)), "{err:?}");
+        Ok(())
+    }
+
+    #[test]
+    fn encode_xz_lzip_and_gz() -> crate::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let tar = tmp.path().join("a.tar");
+        fs::write(&tar, "debian ".repeat(4096))?;
+
+        for ext in ["tar.xz", "tar.lz", "tar.gz"] {
+            let compressed = tmp
+                .path()
+                .join(format!("a.{ext}"));
+            Upack::new(&tar, &compressed)
+                .with_operation(Operation::encode(Some(6)))
+                .run()?;
+
+            let decompressed = tmp.path().join("b.tar");
+            Upack::new(&compressed, &decompressed).run()?;
+            assert_eq!(fs::read(&decompressed)?, fs::read(&tar)?, "{ext}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn decode_zstd_long_window() -> crate::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let tar = tmp.path().join("a.tar");
+        fs::write(&tar, "debian ".repeat(4096))?;
+
+        for params in [ZstdParams::long(30), ZstdParams::disabled()] {
+            let zst = tmp.path().join("a.tar.zst");
+            Upack::new(&tar, &zst)
+                .with_operation(Operation::encode(Some(3)))
+                .with_zstd_params(params)
+                .run()?;
+
+            let decompressed = tmp.path().join("b.tar");
+            Upack::new(&zst, &decompressed).run()?;
+            assert_eq!(fs::read(&decompressed)?, fs::read(&tar)?, "{params:?}");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,147 @@
+use crate::io_buffer::create_file;
+use std::{
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+/// Same as `zstd --train --maxdict` (default: 110 KiB).
+pub const DEFAULT_DICT_SIZE: usize = 112_640;
+
+/// Each tarball contributes (at most) `MAX_SAMPLES` samples of `SAMPLE_SIZE` bytes,
+/// evenly spread over the whole file.
+const SAMPLE_SIZE: usize = 128 << 10;
+const MAX_SAMPLES: u64 = 64;
+
+/// A trained zstd dictionary, shared by a family of nearly identical tarballs
+/// (e.g., the same release on different architectures).
+///
+/// The dictionary is required for both compression & decompression:
+///
+/// ```sh
+/// zstd -d -D rootfs.dict rootfs.tar.zst
+/// ```
+///
+/// # Example
+///
+/// ```no_run
+/// # use repack::{compression::Upack, dict::{ZstdDict, DEFAULT_DICT_SIZE}};
+/// # fn main() -> repack::Result<()> {
+/// let tars = ["amd64.tar", "arm64.tar", "riscv64.tar"];
+/// let dict = ZstdDict::train(&tars, DEFAULT_DICT_SIZE)?;
+/// dict.save("rootfs.dict")?;
+///
+/// Upack::new("amd64.tar", "amd64.tar.zst")
+///     .encode_with_max_lv()
+///     .with_zstd_dict(dict)
+///     .run()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZstdDict {
+    data: Vec<u8>,
+}
+
+impl ZstdDict {
+    /// Trains a dictionary (at most `max_size` bytes) from samples of `files`.
+    pub fn train<P: AsRef<Path>>(files: &[P], max_size: usize) -> io::Result<Self> {
+        let mut samples = Vec::new();
+        let mut sizes = Vec::new();
+
+        for path in files {
+            let mut file = File::open(path)?;
+            let len = file.metadata()?.len();
+            let n = (len / SAMPLE_SIZE as u64).clamp(1, MAX_SAMPLES);
+            let stride = len / n;
+
+            for i in 0..n {
+                file.seek(SeekFrom::Start(i * stride))?;
+                let start = samples.len();
+                Read::by_ref(&mut file)
+                    .take(SAMPLE_SIZE as _)
+                    .read_to_end(&mut samples)?;
+                match samples.len() - start {
+                    0 => {}
+                    size => sizes.push(size),
+                }
+            }
+        }
+
+        zstd::dict::from_continuous(&samples, &sizes, max_size).map(Self::from)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        fs::read(path).map(Self::from)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        create_file(path)?.write_all(&self.data)
+    }
+
+    /// The dictionary ID, which is also stored in the frame headers.
+    ///
+    /// Returns None if it is a raw content dictionary.
+    pub fn id(&self) -> Option<u32> {
+        zstd::zstd_safe::get_dict_id(&self.data).map(|x| x.get())
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl From<Vec<u8>> for ZstdDict {
+    fn from(data: Vec<u8>) -> Self {
+        Self { data }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::{Operation, Upack};
+
+    #[test]
+    fn train_and_compress_with_dict() -> crate::Result<()> {
+        let tmp = tempfile::tempdir()?;
+
+        let tars = ["amd64", "arm64", "i386", "riscv64"].map(|arch| {
+            let path = tmp
+                .path()
+                .join(format!("{arch}.tar"));
+            let content = (0..8192)
+                .map(|i| format!("/usr/lib/{arch}-linux-gnu/lib{i}.so.{}\n", i % 7))
+                .collect::<String>();
+            fs::write(&path, content).map(|_| path)
+        });
+        let tars = tars
+            .into_iter()
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let dict = ZstdDict::train(&tars, 16 << 10)?;
+        assert!(dict.id().is_some());
+
+        let dict_path = tmp.path().join("rootfs.dict");
+        dict.save(&dict_path)?;
+        let dict = ZstdDict::load(&dict_path)?;
+
+        let zst = tmp.path().join("amd64.tar.zst");
+        Upack::new(&tars[0], &zst)
+            .with_operation(Operation::encode(Some(3)))
+            .with_zstd_dict(dict.clone())
+            .run()?;
+
+        // The dictionary is required for decompression.
+        let decompressed = tmp.path().join("b.tar");
+        assert!(Upack::new(&zst, &decompressed)
+            .run()
+            .is_err());
+
+        Upack::new(&zst, &decompressed)
+            .with_zstd_dict(dict)
+            .run()?;
+        assert_eq!(fs::read(&decompressed)?, fs::read(&tars[0])?);
+        Ok(())
+    }
+}
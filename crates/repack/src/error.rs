@@ -0,0 +1,159 @@
+use crate::{compression::Operation, format::Format};
+use std::{
+    error::Error as StdError,
+    io::{self, BufRead, Read, Write},
+};
+use thiserror::Error;
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error(
+        "Unsupported operation: {operation:?}, src-fmt: {src:?}, dst-fmt: {dst:?}"
+    )]
+    UnsupportedOperation {
+        operation: Operation,
+        src: Format,
+        dst: Format,
+    },
+
+    #[error("Unsupported format: {0:?}")]
+    UnsupportedFormat(Format),
+
+    /// Failed to encode/decode the stream, e.g., a corrupted or truncated file.
+    #[error("{format:?} codec error: {source}")]
+    Codec {
+        format: Format,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl Error {
+    /// `.map_err(Error::codec(format))`
+    ///
+    /// The errors of a [FileIo] (e.g., reading the source, writing the target) are kept as [Error::Io].
+    pub(crate) fn codec(format: Format) -> impl FnOnce(io::Error) -> Self {
+        move |source| match Origin::of(&source) {
+            Some(Origin::File) => Self::Io(source),
+            _ => Self::Codec { format, source },
+        }
+    }
+
+    /// `.map_err(Error::decoded(format))`, for the consumer of a [DecoderIo] (e.g., Unpacker).
+    ///
+    /// Only the errors of the decoder are [Error::Codec], the others (e.g., creating files) are [Error::Io].
+    pub(crate) fn decoded(format: Format) -> impl FnOnce(io::Error) -> Self {
+        move |source| match Origin::of(&source) {
+            Some(Origin::Decoder) => Self::Codec { format, source },
+            _ => Self::Io(source),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+#[error(transparent)]
+struct FileError(io::Error);
+
+#[derive(Error, Debug)]
+#[error(transparent)]
+struct DecoderError(io::Error);
+
+enum Origin {
+    File,
+    Decoder,
+}
+
+impl Origin {
+    /// Finds the tag of the error, also when it is wrapped (e.g., by tar: "failed to unpack ...").
+    fn of(e: &io::Error) -> Option<Self> {
+        let mut cur: &(dyn StdError + 'static) = e.get_ref()?;
+        loop {
+            if cur.is::<FileError>() {
+                return Some(Self::File);
+            }
+            if cur.is::<DecoderError>() {
+                return Some(Self::Decoder);
+            }
+            cur = match cur.downcast_ref::<io::Error>() {
+                Some(e) => e.get_ref()?,
+                None => cur.source()?,
+            };
+        }
+    }
+}
+
+/// Tags the I/O errors of the source/target file, see [Error::codec].
+pub(crate) struct FileIo<T>(pub(crate) T);
+
+impl<T> FileIo<T> {
+    fn tag(e: io::Error) -> io::Error {
+        io::Error::new(e.kind(), FileError(e))
+    }
+}
+
+impl<R: Read> Read for FileIo<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0
+            .read(buf)
+            .map_err(Self::tag)
+    }
+}
+
+impl<R: BufRead> BufRead for FileIo<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.0
+            .fill_buf()
+            .map_err(Self::tag)
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.0.consume(amt)
+    }
+}
+
+impl<W: Write> Write for FileIo<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .write(buf)
+            .map_err(Self::tag)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0
+            .flush()
+            .map_err(Self::tag)
+    }
+}
+
+/// Tags the errors of the decoder, see [Error::decoded].
+///
+/// The errors of the underlying [FileIo] are passed through as is.
+pub(crate) struct DecoderIo<R>(pub(crate) R);
+
+impl<R: Read> Read for DecoderIo<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0
+            .read(buf)
+            .map_err(|e| match Origin::of(&e) {
+                Some(Origin::File) => e,
+                _ => io::Error::new(e.kind(), DecoderError(e)),
+            })
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(value: Error) -> Self {
+        match value {
+            Error::Io(e) => e,
+            Error::UnsupportedOperation { .. } | Error::UnsupportedFormat(_) => {
+                io::Error::new(io::ErrorKind::Unsupported, value)
+            }
+            e => io::Error::other(e),
+        }
+    }
+}
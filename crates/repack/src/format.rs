@@ -1,9 +1,22 @@
-#[derive(Debug, Copy, Clone)]
+use flate2::read::MultiGzDecoder;
+use std::{
+    fs::File,
+    io::{self, BufReader, Read, Seek},
+    path::Path,
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Format {
     TarZstd,
     Zstd,
     TarGz,
     Gz,
+    TarXz,
+    Xz,
+    TarBz2,
+    Bz2,
+    TarLz,
+    Lz,
     Tar,
     Unknown,
 }
@@ -14,6 +27,7 @@ impl Format {
         match self {
             Zstd | TarZstd => Some(22),
             TarGz | Gz => Some(flate2::Compression::best().level()),
+            TarXz | Xz | TarBz2 | Bz2 | TarLz | Lz => Some(9),
             _ => None,
         }
     }
@@ -24,6 +38,98 @@ impl Format {
     pub fn is_tar(&self) -> bool {
         matches!(self, Self::Tar)
     }
+
+    /// The compression layer, e.g., TarGz => Gz, Tar => Unknown
+    pub fn outer(&self) -> Self {
+        use Format::*;
+        match self {
+            TarZstd => Zstd,
+            TarGz => Gz,
+            TarXz => Xz,
+            TarBz2 => Bz2,
+            TarLz => Lz,
+            Tar => Unknown,
+            x => *x,
+        }
+    }
+
+    /// Wraps a tar layer into the compression layer, e.g., Gz => TarGz
+    pub fn with_tar(&self) -> Self {
+        use Format::*;
+        match self {
+            Zstd => TarZstd,
+            Gz => TarGz,
+            Xz => TarXz,
+            Bz2 => TarBz2,
+            Lz => TarLz,
+            Unknown => Tar,
+            x => *x,
+        }
+    }
+}
+
+/// The magic field of a ustar/pax/GNU tar header is located at offset 257.
+const TAR_MAGIC_OFFSET: usize = 257;
+const TAR_BLOCK_SIZE: u64 = 512;
+
+fn is_tar_header(block: &[u8]) -> bool {
+    block
+        .get(TAR_MAGIC_OFFSET..TAR_MAGIC_OFFSET + 5)
+        .is_some_and(|x| x == b"ustar")
+}
+
+/// Reads the first tar block (or less if EOF).
+fn read_block<R: Read>(reader: R) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(TAR_BLOCK_SIZE as _);
+    reader
+        .take(TAR_BLOCK_SIZE)
+        .read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+impl Format {
+    /// Detects the outermost format by the magic bytes.
+    ///
+    /// For tar, `head` needs to contain at least the first 262 bytes.
+    ///
+    /// ```
+    /// use repack::format::Format;
+    ///
+    /// assert_eq!(Format::from_magic_bytes(&[0x1f, 0x8b, 0x08]), Format::Gz);
+    /// assert_eq!(Format::from_magic_bytes(b"LZIP\x01"), Format::Lz);
+    /// assert_eq!(Format::from_magic_bytes(b"text"), Format::Unknown);
+    /// ```
+    pub fn from_magic_bytes(head: &[u8]) -> Self {
+        match head {
+            [0x1f, 0x8b, ..] => Self::Gz,
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Self::Zstd,
+            [0xfd, b'7', b'z', b'X', b'Z', 0x00, ..] => Self::Xz,
+            [b'B', b'Z', b'h', ..] => Self::Bz2,
+            [b'L', b'Z', b'I', b'P', ..] => Self::Lz,
+            h if is_tar_header(h) => Self::Tar,
+            _ => Self::Unknown,
+        }
+    }
+
+    /// Detects the format of an existing file by its content.
+    ///
+    /// If the compression layer can be decoded (gz & zstd), the first block of the decoded stream is also checked, i.e. `base.tgz` => TarGz, `file.gz` => Gz.
+    pub fn sniff<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let outer = Self::from_magic_bytes(&read_block(&mut file)?);
+        file.rewind()?;
+
+        let inner = match outer {
+            Self::Gz => read_block(MultiGzDecoder::new(BufReader::new(file))),
+            Self::Zstd => read_block(zstd::Decoder::new(file)?),
+            _ => return Ok(outer),
+        };
+
+        Ok(match inner {
+            Ok(block) if is_tar_header(&block) => outer.with_tar(),
+            _ => outer,
+        })
+    }
 }
 
 impl<T: AsRef<str>> From<Option<T>> for Format {
@@ -35,6 +141,7 @@ impl<T: AsRef<str>> From<Option<T>> for Format {
     }
 }
 
+#[allow(clippy::derivable_impls)]
 impl Default for Format {
     fn default() -> Self {
         Self::Unknown
@@ -51,6 +158,9 @@ impl Format {
     /// - f2.tar.gz => Format::TarGz
     /// - f3.tgz  => Format::TarGz
     /// - f4.gzip => Format::Gz
+    /// - f5.tar.xz => Format::TarXz
+    /// - f6.tbz2 => Format::TarBz2
+    /// - f7.tar.lz => Format::TarLz
     ///
     /// Note:
     /// - For files that already exist, it is more accurate to recognize the Magic bytes of the file.
@@ -69,10 +179,52 @@ impl Format {
         match fmt_arr {
             ["tar" | "pax", "zst" | "zstd"] | [_, "tzst" | "tzstd"] => Self::TarZstd,
             ["tar" | "pax", "gz" | "gzip"] | [_, "tgz" | "tgzip"] => Self::TarGz,
+            ["tar" | "pax", "xz"] | [_, "txz"] => Self::TarXz,
+            ["tar" | "pax", "bz2" | "bzip2"] | [_, "tbz" | "tbz2"] => Self::TarBz2,
+            ["tar" | "pax", "lz" | "lzip"] => Self::TarLz,
             [_, "zst" | "zstd"] => Self::Zstd,
             [_, "gz" | "gzip"] => Self::Gz,
+            [_, "xz"] => Self::Xz,
+            [_, "bz2" | "bzip2"] => Self::Bz2,
+            [_, "lz" | "lzip"] => Self::Lz,
             [_, "tar" | "pax"] => Self::Tar,
             _ => Self::Unknown,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn sniff_misnamed_tgz() -> io::Result<()> {
+        let tmp = tempfile::tempdir()?;
+
+        let mut tar = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_ustar();
+        header.set_path("etc/hostname")?;
+        header.set_size(0);
+        header.set_cksum();
+        tar.append(&header, io::empty())?;
+        let tar = tar.into_inner()?;
+
+        // an uncompressed tar named "*.tgz"
+        let base_tgz = tmp.path().join("base.tgz");
+        std::fs::write(&base_tgz, &tar)?;
+        assert_eq!(Format::sniff(&base_tgz)?, Format::Tar);
+
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+        gz.write_all(&tar)?;
+        let base_gz = tmp.path().join("base.gz");
+        std::fs::write(&base_gz, gz.finish()?)?;
+        assert_eq!(Format::sniff(&base_gz)?, Format::TarGz);
+
+        let zst = tmp.path().join("hostname.zst");
+        std::fs::write(&zst, zstd::encode_all(&b"debian"[..], 3)?)?;
+        assert_eq!(Format::sniff(&zst)?, Format::Zstd);
+
+        Ok(())
+    }
+}
@@ -1,10 +1,7 @@
-use crate::{
-    compression::Upack,
-    io_buffer::{buf_reader, buf_writer},
-};
-use flate2::read::MultiGzDecoder;
+use crate::{compression::Upack, error::Error};
+use flate2::{write::GzEncoder, Compression};
 use std::{
-    io::{self, Write},
+    io::{self, Read, Write},
     path::Path,
 };
 
@@ -13,12 +10,28 @@ where
     S: AsRef<Path>,
     D: AsRef<Path>,
 {
-    pub(crate) fn decompress_gz(&self) -> io::Result<()> {
-        let gz_file = buf_reader(&self.source.path)?;
-        let mut decoder = MultiGzDecoder::new(gz_file);
+    /// Single-threaded, the header has no file name & mtime (i.e., reproducible).
+    ///
+    /// Compresses `src` into `out`, then returns it (not flushed).
+    pub(crate) fn compress_to_gz<R: Read, W: Write>(
+        &self,
+        level: u32,
+        src: R,
+        out: W,
+    ) -> crate::Result<W> {
+        let codec_err = || Error::codec(*self.target.get_format());
+
+        let mut src_file = self.progress_reader(src);
+
+        let gz_file = src_file.counting_writer(out);
+        let mut encoder = GzEncoder::new(gz_file, Compression::new(level.min(9)));
+
+        io::copy(&mut src_file, &mut encoder).map_err(codec_err())?;
+        let gz_file = encoder
+            .finish()
+            .map_err(codec_err())?;
+        src_file.finish();
 
-        let mut dst_file = buf_writer(&self.target.path)?;
-        io::copy(&mut decoder, &mut dst_file)?;
-        dst_file.flush()
+        Ok(gz_file.into_inner())
     }
 }
@@ -1,7 +1,16 @@
+pub mod archive;
 pub mod compression;
 pub mod cpu;
+pub mod dict;
+pub mod error;
 pub mod format;
 pub mod io_buffer;
+pub mod progress;
 
+pub use error::{Error, Result};
+
+mod decode;
 mod gz;
+mod lzip;
+mod xz;
 mod zstd;
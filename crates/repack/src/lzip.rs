@@ -0,0 +1,50 @@
+use crate::{compression::Upack, cpu, error::Error};
+use lzma_rust2::{LzipOptions, LzipWriterMt};
+use std::{
+    io::{self, Read, Write},
+    num::NonZeroU64,
+    path::Path,
+};
+
+impl<S, D> Upack<S, D>
+where
+    S: AsRef<Path>,
+    D: AsRef<Path>,
+{
+    /// Compresses `src` into `out`, then returns it (not flushed).
+    pub(crate) fn compress_to_lz<R: Read, W: Write>(
+        &self,
+        level: u32,
+        src: R,
+        out: W,
+    ) -> crate::Result<W> {
+        let codec_err = || Error::codec(*self.target.get_format());
+
+        let mut src_file = self.progress_reader(src);
+
+        let lz_file = src_file.counting_writer(out);
+        let mut encoder = new_encoder(lz_file, level).map_err(codec_err())?;
+
+        io::copy(&mut src_file, &mut encoder).map_err(codec_err())?;
+        let lz_file = encoder
+            .finish()
+            .map_err(codec_err())?;
+        src_file.finish();
+
+        Ok(lz_file.into_inner())
+    }
+}
+
+/// Creates a multi-threaded lzip encoder (level: 0 ~ 9), similar to `plzip`.
+///
+/// Same as plzip, the input is split into members of 2 * dictionary size.
+pub(crate) fn new_encoder<W: Write>(
+    writer: W,
+    level: u32,
+) -> io::Result<LzipWriterMt<W>> {
+    let mut options = LzipOptions::with_preset(level.min(9));
+    let member_size = 2 * u64::from(options.lzma_options.dict_size);
+    options.set_member_size(NonZeroU64::new(member_size));
+
+    LzipWriterMt::new(writer, options, *cpu::num() as _)
+}
@@ -0,0 +1,277 @@
+use crate::compression::Upack;
+use core::fmt;
+use std::{
+    io::{self, BufRead, Read, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// A snapshot of a running [Upack] operation.
+#[derive(Debug, Clone, Copy)]
+pub struct Progress {
+    /// Bytes read from the source
+    pub read: u64,
+    /// Bytes written to the target (0 if unknown, e.g., extracting to a directory)
+    pub written: u64,
+    /// Size of the source file
+    pub total: Option<u64>,
+    pub elapsed: Duration,
+    /// true: this is the last report of the operation
+    pub done: bool,
+}
+
+impl Progress {
+    /// written / read, e.g., 0.25 => compressed to 25%
+    pub fn ratio(&self) -> Option<f64> {
+        match (self.read, self.written) {
+            (0, _) | (_, 0) => None,
+            (r, w) => Some(w as f64 / r as f64),
+        }
+    }
+
+    /// Throughput of the source (MiB/s)
+    pub fn mib_per_sec(&self) -> f64 {
+        match self.elapsed.as_secs_f64() {
+            s if s > 0.0 => self.read as f64 / (1 << 20) as f64 / s,
+            _ => 0.0,
+        }
+    }
+
+    /// 0 ~ 100
+    pub fn percent(&self) -> Option<f64> {
+        self.total
+            .filter(|&t| t > 0)
+            .map(|t| self.read as f64 * 100.0 / t as f64)
+    }
+}
+
+/// Callback of [Progress], invoked at most once per `interval` (and once at the end).
+///
+/// # Example
+///
+/// ```no_run
+/// # use repack::{compression::Upack, progress::ProgressCallback};
+/// # use std::time::Duration;
+/// # fn main() -> repack::Result<()> {
+/// Upack::new("rootfs.tar", "rootfs.tar.zst")
+///     .encode_with_max_lv()
+///     .with_progress(ProgressCallback::new(Duration::from_secs(10), |p| {
+///         eprintln!("{} bytes, {:.2} MiB/s", p.read, p.mib_per_sec())
+///     }))
+///     .run()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ProgressCallback {
+    interval: Duration,
+    f: Arc<dyn Fn(&Progress) + Send + Sync>,
+}
+
+impl ProgressCallback {
+    pub fn new<F>(interval: Duration, f: F) -> Self
+    where
+        F: Fn(&Progress) + Send + Sync + 'static,
+    {
+        Self {
+            interval,
+            f: Arc::new(f),
+        }
+    }
+
+    pub fn get_interval(&self) -> Duration {
+        self.interval
+    }
+}
+
+impl fmt::Debug for ProgressCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProgressCallback")
+            .field("interval", &self.interval)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Counts the bytes read from `inner`, and reports them through the [ProgressCallback].
+///
+/// The bytes written to the target are counted by [ProgressReader::counting_writer].
+pub struct ProgressReader<R> {
+    inner: R,
+    read: u64,
+    written: Arc<AtomicU64>,
+    total: Option<u64>,
+    start: Instant,
+    last: Instant,
+    callback: Option<ProgressCallback>,
+}
+
+impl<R: Read> ProgressReader<R> {
+    pub fn new(inner: R, callback: Option<ProgressCallback>) -> Self {
+        let now = Instant::now();
+        Self {
+            inner,
+            read: 0,
+            written: Default::default(),
+            total: None,
+            start: now,
+            last: now,
+            callback,
+        }
+    }
+
+    pub fn with_total(mut self, total: Option<u64>) -> Self {
+        self.total = total;
+        self
+    }
+
+    /// Wraps the target, so that [Progress::written] is available.
+    pub fn counting_writer<W: Write>(&self, inner: W) -> CountingWriter<W> {
+        CountingWriter {
+            inner,
+            written: self.written.clone(),
+        }
+    }
+
+    pub fn progress(&self) -> Progress {
+        Progress {
+            read: self.read,
+            written: self
+                .written
+                .load(Ordering::Relaxed),
+            total: self.total,
+            elapsed: self.start.elapsed(),
+            done: false,
+        }
+    }
+
+    /// Sends the last report. It should be called after the target is flushed.
+    pub fn finish(self) -> Progress {
+        let p = Progress {
+            done: true,
+            ..self.progress()
+        };
+        if let Some(cb) = &self.callback {
+            (cb.f)(&p)
+        }
+        p
+    }
+
+    fn advance(&mut self, n: usize) {
+        self.read += n as u64;
+
+        if let Some(cb) = &self.callback {
+            if self.last.elapsed() >= cb.interval {
+                self.last = Instant::now();
+                (cb.f)(&self.progress())
+            }
+        }
+    }
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.advance(n);
+        Ok(n)
+    }
+}
+
+/// Decoders (e.g., `bufread::MultiGzDecoder`) read through `fill_buf` & `consume`.
+impl<R: BufRead> BufRead for ProgressReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt);
+        self.advance(amt)
+    }
+}
+
+pub struct CountingWriter<W> {
+    inner: W,
+    written: Arc<AtomicU64>,
+}
+
+impl<W> CountingWriter<W> {
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.written
+            .fetch_add(n as _, Ordering::Relaxed);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<S, D> Upack<S, D>
+where
+    S: AsRef<Path>,
+    D: AsRef<Path>,
+{
+    /// Wraps the source reader (total: the size of the source file).
+    pub(crate) fn progress_reader<R: Read>(&self, reader: R) -> ProgressReader<R> {
+        let total = self
+            .source
+            .path
+            .as_ref()
+            .metadata()
+            .ok()
+            .map(|m| m.len());
+
+        ProgressReader::new(reader, self.progress.clone()).with_total(total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::Operation;
+    use std::{fs, sync::Mutex};
+
+    #[test]
+    fn report_compression_progress() -> crate::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let tar = tmp.path().join("a.tar");
+        fs::write(&tar, "debian ".repeat(65536))?;
+
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let cb = {
+            let reports = reports.clone();
+            ProgressCallback::new(Duration::ZERO, move |p| {
+                reports.lock().unwrap().push(*p)
+            })
+        };
+
+        Upack::new(&tar, tmp.path().join("a.tar.zst"))
+            .with_operation(Operation::encode(Some(3)))
+            .with_progress(cb)
+            .run()?;
+
+        let reports = reports.lock().unwrap();
+        let last = reports
+            .last()
+            .expect("No progress");
+        assert!(last.done);
+        assert_eq!(last.read, 7 * 65536);
+        assert_eq!(last.percent(), Some(100.0));
+        assert!(
+            last.ratio()
+                .is_some_and(|r| r < 0.1),
+            "{last:?}"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,52 @@
+use crate::{compression::Upack, cpu, error::Error};
+use liblzma::{
+    stream::{Check, MtStreamBuilder},
+    write::XzEncoder,
+};
+use std::{
+    io::{self, Read, Write},
+    path::Path,
+};
+
+impl<S, D> Upack<S, D>
+where
+    S: AsRef<Path>,
+    D: AsRef<Path>,
+{
+    /// Compresses `src` into `out`, then returns it (not flushed).
+    pub(crate) fn compress_to_xz<R: Read, W: Write>(
+        &self,
+        level: u32,
+        src: R,
+        out: W,
+    ) -> crate::Result<W> {
+        let codec_err = || Error::codec(*self.target.get_format());
+
+        let mut src_file = self.progress_reader(src);
+
+        let xz_file = src_file.counting_writer(out);
+        let mut encoder = new_encoder(xz_file, level).map_err(codec_err())?;
+
+        io::copy(&mut src_file, &mut encoder).map_err(codec_err())?;
+        let xz_file = encoder
+            .finish()
+            .map_err(codec_err())?;
+        src_file.finish();
+
+        Ok(xz_file.into_inner())
+    }
+}
+
+/// Creates a multi-threaded xz encoder (preset: 0 ~ 9, check: crc64), same as `xz -T0`.
+pub(crate) fn new_encoder<W: Write>(
+    writer: W,
+    level: u32,
+) -> io::Result<XzEncoder<W>> {
+    let stream = MtStreamBuilder::new()
+        .preset(level.min(9))
+        .threads(*cpu::num() as _)
+        .check(Check::Crc64)
+        .encoder()?;
+
+    Ok(XzEncoder::new_stream(writer, stream))
+}
@@ -1,34 +1,67 @@
 use crate::{
-    compression::Upack,
+    compression::{Upack, ZstdParams},
     cpu,
-    io_buffer::{buf_reader, create_file},
+    dict::ZstdDict,
+    error::Error,
+};
+use std::{
+    io::{self, Read, Write},
+    path::Path,
 };
-use std::{io, path::Path};
 
 impl<S, D> Upack<S, D>
 where
     S: AsRef<Path>,
     D: AsRef<Path>,
 {
-    pub(crate) fn compress_to_zst(&self, level: i32) -> io::Result<()> {
-        // let zstd_file = buf_writer(&self.target)?;
+    /// Compresses `src` into `out`, then returns it (not flushed).
+    ///
+    /// `out` does not have to be buffered, [the zstd library has its own internal input buffer](https://docs.rs/zstd/latest/zstd/stream/write/struct.Encoder.html).
+    pub(crate) fn compress_to_zst<R: Read, W: Write>(
+        &self,
+        level: i32,
+        src: R,
+        out: W,
+    ) -> crate::Result<W> {
+        let codec_err = || Error::codec(*self.target.get_format());
 
-        // > The zstd library has its own internal input buffer
-        // https://docs.rs/zstd/latest/zstd/stream/write/struct.Encoder.html
-        let zstd_file = create_file(&self.target.path)?;
+        let mut src_file = self.progress_reader(src);
 
-        let mut encoder = zstd::Encoder::new(zstd_file, level)?;
-        encoder.long_distance_matching(true)?;
-        encoder.window_log(27)?;
+        let zstd_file = src_file.counting_writer(out);
+        let mut encoder = new_encoder(
+            zstd_file,
+            level,
+            &self.zstd_params,
+            self.zstd_dict.as_deref(),
+        )
+        .map_err(codec_err())?;
 
-        encoder.multithread(*cpu::num() as _)?;
+        io::copy(&mut src_file, &mut encoder).map_err(codec_err())?;
+        let zstd_file = encoder
+            .finish()
+            .map_err(codec_err())?;
+        src_file.finish();
 
-        let mut src_file = buf_reader(&self.source.path)?;
-        io::copy(&mut src_file, &mut encoder)?;
-        encoder.finish()?;
+        Ok(zstd_file.into_inner())
+    }
+}
 
-        Ok(())
+/// Creates a multi-threaded zstd encoder.
+pub(crate) fn new_encoder<W: Write>(
+    writer: W,
+    level: i32,
+    params: &ZstdParams,
+    dict: Option<&ZstdDict>,
+) -> io::Result<zstd::Encoder<'static, W>> {
+    let dict = dict.map_or(&[][..], ZstdDict::as_bytes);
+    let mut encoder = zstd::Encoder::with_dictionary(writer, level, dict)?;
+    encoder.long_distance_matching(params.long_distance)?;
+    if let Some(n) = params.window_log {
+        encoder.window_log(n)?;
     }
+
+    encoder.multithread(*cpu::num() as _)?;
+    Ok(encoder)
 }
 
 #[cfg(test)]